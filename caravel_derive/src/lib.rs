@@ -0,0 +1,307 @@
+#![warn(missing_docs)]
+
+//! Derive macros for the agent types of the [`caravela`](https://docs.rs/caravela) crate.
+//!
+//! These macros are re-exported by `caravela` under its `derive` feature, next to the types they implement,
+//!  so they are used through `caravela::agent::{Agent, behavior}` rather than from this crate directly.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, Ident, ImplItem,
+    ItemImpl, Meta, Type,
+};
+
+/// How a field is initialised by the generated builder.
+enum Init {
+    /// The `Agent` built by the platform.
+    Agent,
+    /// The whole parameter of the agent.
+    Param,
+    /// The field of the parameter with the same name.
+    ParamField,
+    /// [`Default::default`].
+    Default,
+}
+
+/// Implement `AgentBuild` or `AgentBuildParam`, plus `AsRef<Agent>`, for a struct with named fields.
+///
+/// Exactly one field is marked `#[agent]` and holds the `Agent` built by the platform.
+///  Without a parameter every other field is initialised with [`Default`].
+///  With a `#[agent(param = Type)]` attribute on the struct, `AgentBuildParam` is implemented instead and
+///  every other field is moved from the field of the parameter with the same name,
+///  unless it is marked `#[agent(default)]`. A field marked `#[agent(param)]` receives the whole parameter,
+///  in which case the remaining fields are initialised with [`Default`].
+///
+/// ```ignore
+/// #[derive(Agent)]
+/// #[agent(param = SensorConfig)]
+/// struct Sensor {
+///     #[agent]
+///     core: Agent,
+///     threshold: u64,
+///     #[agent(default)]
+///     readings: Vec<u64>,
+/// }
+/// ```
+#[proc_macro_derive(Agent, attributes(agent))]
+pub fn derive_agent(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_agent(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_agent(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let mut param: Option<Type> = None;
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("agent")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("param") {
+                param = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `param = Type`"))
+            }
+        })?;
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "Agent can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "Agent can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut inits = Vec::with_capacity(fields.len());
+    for field in fields {
+        let mut init = None;
+        for attr in field.attrs.iter().filter(|x| x.path().is_ident("agent")) {
+            let marked = if let Meta::Path(_) = attr.meta {
+                Init::Agent
+            } else {
+                let mut marked = None;
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("param") {
+                        marked = Some(Init::Param);
+                    } else if meta.path.is_ident("default") {
+                        marked = Some(Init::Default);
+                    } else {
+                        return Err(meta.error("expected `param` or `default`"));
+                    }
+                    Ok(())
+                })?;
+                marked.ok_or_else(|| Error::new(attr.span(), "expected `param` or `default`"))?
+            };
+            if init.replace(marked).is_some() {
+                return Err(Error::new(attr.span(), "field is marked more than once"));
+            }
+        }
+        inits.push((field, init));
+    }
+
+    let count = |kind: fn(&Init) -> bool| {
+        inits
+            .iter()
+            .filter(|(_, init)| init.as_ref().is_some_and(kind))
+            .count()
+    };
+    if count(|x| matches!(x, Init::Agent)) != 1 {
+        return Err(Error::new(
+            name.span(),
+            "exactly one field must be marked #[agent]",
+        ));
+    }
+    let whole_param = count(|x| matches!(x, Init::Param));
+    if whole_param > 1 {
+        return Err(Error::new(
+            name.span(),
+            "at most one field can be marked #[agent(param)]",
+        ));
+    }
+    if whole_param == 1 && param.is_none() {
+        return Err(Error::new(
+            name.span(),
+            "#[agent(param)] requires #[agent(param = Type)] on the struct",
+        ));
+    }
+
+    let base_agent = format_ident!("base_agent");
+    let parameter = format_ident!("param");
+    let initialisers = inits.into_iter().map(|(field, init)| {
+        let ident = &field.ident;
+        let init = init.unwrap_or(if param.is_some() && whole_param == 0 {
+            Init::ParamField
+        } else {
+            Init::Default
+        });
+        match init {
+            Init::Agent => quote!(#ident: #base_agent),
+            Init::Param => quote!(#ident: #parameter),
+            Init::ParamField => quote!(#ident: #parameter.#ident),
+            Init::Default => quote!(#ident: ::core::default::Default::default()),
+        }
+    });
+    let agent_field = fields
+        .iter()
+        .find(|field| {
+            field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("agent") && matches!(attr.meta, Meta::Path(_)))
+        })
+        .and_then(|field| field.ident.as_ref());
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let build = match &param {
+        None => quote! {
+            impl #impl_generics ::caravela::agent::AgentBuild for #name #ty_generics #where_clause {
+                fn agent_builder(#base_agent: ::caravela::agent::Agent) -> Self {
+                    Self { #(#initialisers),* }
+                }
+            }
+        },
+        Some(param) => quote! {
+            impl #impl_generics ::caravela::agent::AgentBuildParam for #name #ty_generics #where_clause {
+                type Parameter = #param;
+                #[allow(unused_variables)]
+                fn agent_with_param_builder(
+                    #base_agent: ::caravela::agent::Agent,
+                    #parameter: Self::Parameter,
+                ) -> Self {
+                    Self { #(#initialisers),* }
+                }
+            }
+        },
+    };
+    Ok(quote! {
+        #build
+
+        impl #impl_generics ::core::convert::AsRef<::caravela::agent::Agent> for #name #ty_generics #where_clause {
+            fn as_ref(&self) -> &::caravela::agent::Agent {
+                &self.#agent_field
+            }
+        }
+    })
+}
+
+/// Methods of `Behavior` that can be wired by [`macro@behavior`].
+const HOOKS: [&str; 6] = [
+    "setup",
+    "action",
+    "done",
+    "failure_detection",
+    "failure_identification",
+    "failure_recovery",
+];
+
+/// Implement `Behavior` for the type of an inherent `impl` block from its methods marked with the name of a hook:
+///  `#[setup]`, `#[action]`, `#[done]`, `#[failure_detection]`, `#[failure_identification]` or `#[failure_recovery]`.
+///  Each marked method must have the signature of the hook, although it can take `&self` instead of `&mut self`.
+///  Hooks without a marked method keep their default behavior.
+///
+/// ```ignore
+/// #[behavior]
+/// impl Sensor {
+///     #[action]
+///     fn measure(&mut self) -> Result<(), ErrorCode> {
+///         self.core.send_to("Monitor", MessageType::Inform, Content::Expression("1".to_string()))
+///     }
+///     #[done]
+///     fn finished(&self) -> bool {
+///         true
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn behavior(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = TokenStream2::from(args);
+    if !args.is_empty() {
+        return Error::new(args.span(), "#[behavior] takes no arguments")
+            .into_compile_error()
+            .into();
+    }
+    let input = parse_macro_input!(input as ItemImpl);
+    expand_behavior(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand_behavior(mut input: ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "#[behavior] expects an inherent impl block",
+        ));
+    }
+    let mut wired: Vec<(Ident, Ident)> = Vec::new();
+    for item in &mut input.items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let mut hooks = Vec::new();
+        method.attrs.retain(
+            |attr| match HOOKS.iter().find(|hook| attr.path().is_ident(hook)) {
+                Some(hook) => {
+                    hooks.push((format_ident!("{}", hook), attr.span()));
+                    false
+                }
+                None => true,
+            },
+        );
+        for (hook, span) in hooks {
+            if wired.iter().any(|(x, _)| *x == hook) {
+                return Err(Error::new(
+                    span,
+                    format!("{} is wired more than once", hook),
+                ));
+            }
+            wired.push((hook, method.sig.ident.clone()));
+        }
+    }
+
+    let hooks = wired.iter().map(|(hook, method)| {
+        let result = quote!(::core::result::Result<(), ::caravela::ErrorCode>);
+        match hook.to_string().as_str() {
+            "setup" | "action" => quote! {
+                fn #hook(&mut self) -> #result {
+                    Self::#method(self)
+                }
+            },
+            "done" => quote! {
+                fn #hook(&mut self) -> bool {
+                    Self::#method(self)
+                }
+            },
+            "failure_detection" => quote! {
+                fn #hook(&mut self, action_result: &#result) -> bool {
+                    Self::#method(self, action_result)
+                }
+            },
+            _ => quote! {
+                fn #hook(&mut self, action_result: &#result) {
+                    Self::#method(self, action_result)
+                }
+            },
+        }
+    });
+    let self_ty = &input.self_ty;
+    let (impl_generics, _, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #input
+
+        impl #impl_generics ::caravela::agent::behavior::Behavior for #self_ty #where_clause {
+            #(#hooks)*
+        }
+    })
+}
@@ -0,0 +1,12 @@
+//! Launch a platform from a configuration file and control it interactively.
+//!  Only the built-in `Echo` and `Sink` agent types are linked in, applications register their own
+//!  types in an [`AgentRegistry`] and call [`cli::main`] from their binary.
+
+use caravela::{cli, config::AgentRegistry};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut registry = AgentRegistry::new();
+    cli::register_builtins(&mut registry);
+    cli::main(registry)
+}
@@ -0,0 +1,405 @@
+use crate::{
+    agent::{behavior::Behavior, Agent, AgentBuild, AgentBuildParam},
+    config::{AgentRegistry, PlatformConfig},
+    deck::deck,
+    entity::try_dispatch,
+    journal::Stub,
+    messaging::{Content, Message, MessageType},
+    service::sniffer::{SniffedMessage, Sniffer},
+    Description, ErrorCode, Platform, DEFAULT_STACK,
+};
+use std::{
+    fmt::Debug,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Nickname of the agent sending the messages typed in a [`Session`] and printing their replies.
+pub const OPERATOR: &str = "operator";
+const OPERATOR_PRIORITY: u8 = 1;
+const TAIL_PERIOD: Duration = Duration::from_millis(100);
+
+const USAGE: &str = "usage: caravela <config.toml|config.json>";
+const HELP: &str = "\
+agents                              list the agents with their state and counters
+suspend <nickname>                  suspend the agent
+resume <nickname>                   resume the agent
+terminate <nickname>                terminate the agent and remove it from the platform
+reset <nickname>                    terminate the agent and start it again as declared in the configuration
+send <nickname> <performative> <text>
+                                    send a message from the operator, replies are printed as they arrive
+sniff <nickname>                    copy the messages of the agent to the sniffer
+unsniff <nickname>                  stop copying the messages of the agent
+trace                               print the messages captured by the sniffer so far
+tail                                toggle printing the messages captured by the sniffer as they arrive
+help                                print this help
+quit                                leave the session";
+
+/// Shared output of a [`Session`], also written by the operator agent and the sniffer tail.
+#[derive(Clone)]
+pub struct Console(Arc<Mutex<dyn Write + Send>>);
+
+impl Console {
+    /// Create a console writing to `writer`.
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+    /// Write a line to the console. Errors of the underlying writer are ignored.
+    pub fn line(&self, text: impl AsRef<str>) {
+        let mut writer = self.0.lock().expect("Console is poisoned");
+        let _ = writeln!(writer, "{}", text.as_ref());
+        let _ = writer.flush();
+    }
+}
+
+impl Debug for Console {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Console")
+    }
+}
+
+make_agent_with_param!(Operator, Console);
+
+impl Behavior for Operator {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        self.param.line(format!(
+            "<- {} from {}: {}",
+            msg.message_type(),
+            msg.sender(),
+            msg.content()
+        ));
+        Ok(())
+    }
+}
+
+make_agent!(Echo);
+
+impl Behavior for Echo {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        self.agent.send_to_aid(
+            msg.sender().clone(),
+            MessageType::Inform,
+            msg.content().clone(),
+        )
+    }
+}
+
+/// Register the agent types shipped with the `caravela` binary:
+///  `Echo`, which informs the sender of every message with its content, and `Sink`, which discards every message.
+pub fn register_builtins(registry: &mut AgentRegistry) {
+    registry.register::<Echo>("Echo").register::<Stub>("Sink");
+}
+
+/// Interactive control of a platform launched from a [`PlatformConfig`].
+#[derive(Debug)]
+pub struct Session {
+    platform: Platform,
+    config: PlatformConfig,
+    registry: AgentRegistry,
+    operator: Description,
+    console: Console,
+    sniffer: Option<Sniffer>,
+    tail: Option<Arc<AtomicBool>>,
+}
+
+impl Session {
+    /// Launch the platform declared by `config` and add the [`OPERATOR`] agent, writing to `console`.
+    /// A configuration that declares an agent named after the operator is rejected before the launch.
+    pub fn new(
+        config: PlatformConfig,
+        registry: AgentRegistry,
+        console: Console,
+    ) -> Result<Self, ErrorCode> {
+        if config.agents.iter().any(|agent| agent.nickname == OPERATOR) {
+            return Err(ErrorCode::InvalidConfig(format!(
+                "agent {} is reserved for the session",
+                OPERATOR
+            )));
+        }
+        let (platform, _) = config.launch(&registry)?;
+        let operator = platform.add_agent_with_param::<Operator>(
+            OPERATOR,
+            OPERATOR_PRIORITY,
+            DEFAULT_STACK,
+            console.clone(),
+        )?;
+        platform.start(&operator)?;
+        Ok(Self {
+            platform,
+            config,
+            registry,
+            operator,
+            console,
+            sniffer: None,
+            tail: None,
+        })
+    }
+
+    /// Get the controlled platform.
+    pub fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    /// Run the command in `line`, writing its outcome to the console.
+    ///  Returns `false` once the session is to be left.
+    pub fn execute(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return true;
+        };
+        let argument = words.next();
+        let result = match (command, argument) {
+            ("quit" | "exit", _) => return false,
+            ("help", _) => {
+                self.console.line(HELP);
+                Ok(())
+            }
+            ("agents", _) => {
+                self.list_agents();
+                Ok(())
+            }
+            ("trace", _) => self.trace(),
+            ("tail", _) => self.toggle_tail(),
+            ("suspend", Some(nickname)) => self
+                .find(nickname)
+                .and_then(|aid| self.platform.suspend(&aid)),
+            ("resume", Some(nickname)) => self
+                .find(nickname)
+                .and_then(|aid| self.platform.resume(&aid)),
+            ("terminate", Some(nickname)) => self
+                .find(nickname)
+                .and_then(|aid| self.platform.terminate(&aid)),
+            ("reset", Some(nickname)) => self.reset(nickname),
+            ("sniff", Some(nickname)) => self.sniff(nickname, true),
+            ("unsniff", Some(nickname)) => self.sniff(nickname, false),
+            ("send", Some(nickname)) => {
+                let performative = words.next().unwrap_or_default();
+                let text = words.collect::<Vec<_>>().join(" ");
+                self.send(nickname, performative, text)
+            }
+            _ => {
+                self.console
+                    .line(format!("unknown command: {}, try help", line.trim()));
+                return true;
+            }
+        };
+        match result {
+            Ok(()) => self.console.line("ok"),
+            Err(error) => self.console.line(format!("error: {}", error)),
+        }
+        true
+    }
+
+    /// Execute every line of `input` until it ends or a `quit` command, prompting before each one.
+    pub fn run(&mut self, input: impl BufRead) {
+        let prompt = |console: &Console| {
+            let mut writer = console.0.lock().expect("Console is poisoned");
+            let _ = write!(writer, "caravela> ");
+            let _ = writer.flush();
+        };
+        prompt(&self.console);
+        for line in input.lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if !self.execute(&line) {
+                break;
+            }
+            prompt(&self.console);
+        }
+        self.stop_tail();
+    }
+
+    fn find(&self, nickname: &str) -> Result<Description, ErrorCode> {
+        let name = format!("{}@{}", nickname, self.platform.name());
+        deck().read().get_aid_from_name(&name)
+    }
+
+    fn list_agents(&self) {
+        let mut agents = self.platform.agents();
+        agents.sort_by(|a, b| a.nickname.cmp(&b.nickname));
+        self.console.line(format!(
+            "{:<16} {:<10} {:>8} {:>7} {:>8} {:>8} {:>10}",
+            "NICKNAME", "STATE", "PRIORITY", "MAILBOX", "SENT", "RECEIVED", "ITERATIONS"
+        ));
+        for agent in agents {
+            self.console.line(format!(
+                "{:<16} {:<10} {:>8} {:>7} {:>8} {:>8} {:>10}",
+                agent.nickname,
+                format!("{:?}", agent.state),
+                agent.priority,
+                agent.mailbox_depth,
+                agent.messages_sent,
+                agent.messages_received,
+                agent.iterations
+            ));
+        }
+    }
+
+    /// Terminate the agent if it is still present, then add and start it again as declared.
+    fn reset(&self, nickname: &str) -> Result<(), ErrorCode> {
+        let config = self
+            .config
+            .agents
+            .iter()
+            .find(|x| x.nickname == nickname)
+            .ok_or(ErrorCode::NotFound)?;
+        if let Ok(aid) = self.find(nickname) {
+            self.platform.terminate(&aid)?;
+        }
+        let aid = self.registry.spawn(&self.platform, config)?;
+        self.platform.start(&aid)
+    }
+
+    /// Send a message from the operator, without waiting for room in the mailbox of the receiver
+    ///  so the session does not freeze.
+    fn send(&self, nickname: &str, performative: &str, text: String) -> Result<(), ErrorCode> {
+        let receiver = self.find(nickname)?;
+        let message_type: MessageType = performative.parse()?;
+        let msg = Message::new(
+            self.operator.clone(),
+            receiver.clone(),
+            message_type,
+            Content::Expression(text),
+        );
+        try_dispatch(msg).inspect_err(|error| {
+            if *error == ErrorCode::ChannelFull {
+                self.console.line(format!(
+                    "the mailbox of {} is full, send the message again later",
+                    nickname
+                ));
+            }
+        })
+    }
+
+    /// Get the sniffer, booting it on first use.
+    fn sniffer(&mut self) -> Result<&Sniffer, ErrorCode> {
+        if self.sniffer.is_none() {
+            self.sniffer = Some(self.platform.boot_sniffer()?);
+        }
+        self.sniffer.as_ref().ok_or(ErrorCode::NotFound)
+    }
+
+    fn sniff(&mut self, nickname: &str, sniff: bool) -> Result<(), ErrorCode> {
+        let aid = self.find(nickname)?;
+        let sniffer = self.sniffer()?;
+        if sniff {
+            sniffer.sniff(&aid)
+        } else {
+            sniffer.unsniff(&aid)
+        }
+    }
+
+    fn trace(&mut self) -> Result<(), ErrorCode> {
+        let trace = self.sniffer()?.trace();
+        for sniffed in trace {
+            self.console.line(describe(&sniffed));
+        }
+        Ok(())
+    }
+
+    /// Start printing the captured messages from a background thread, or stop it if already running.
+    fn toggle_tail(&mut self) -> Result<(), ErrorCode> {
+        if self.stop_tail() {
+            return Ok(());
+        }
+        let sniffer = self.sniffer()?.clone();
+        let console = self.console.clone();
+        let running = Arc::new(AtomicBool::new(true));
+        let tail = running.clone();
+        thread::Builder::new()
+            .name("caravela-tail".to_string())
+            .spawn(move || {
+                let mut printed = newest(&sniffer.trace());
+                while tail.load(Ordering::Acquire) {
+                    let trace = sniffer.trace();
+                    for sniffed in &trace[unprinted(&trace, printed)..] {
+                        console.line(describe(sniffed));
+                    }
+                    printed = newest(&trace);
+                    thread::sleep(TAIL_PERIOD);
+                }
+            })
+            .map_err(|_| ErrorCode::AgentPanic)?;
+        self.tail = Some(running);
+        Ok(())
+    }
+
+    fn stop_tail(&mut self) -> bool {
+        match self.tail.take() {
+            Some(running) => {
+                running.store(false, Ordering::Release);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Timestamp of the newest captures of the trace and how many of them share it.
+///  The tail keeps its place by timestamp, since the sniffer inserts and removes captures within its trace.
+fn newest(trace: &[SniffedMessage]) -> (SystemTime, usize) {
+    let timestamp = trace.last().map_or(UNIX_EPOCH, |x| x.timestamp());
+    let count = trace
+        .iter()
+        .rev()
+        .take_while(|x| x.timestamp() == timestamp)
+        .count();
+    (timestamp, count)
+}
+
+/// Index of the first capture of the trace newer than those printed.
+fn unprinted(trace: &[SniffedMessage], (timestamp, count): (SystemTime, usize)) -> usize {
+    let start = trace.partition_point(|x| x.timestamp() < timestamp);
+    let same = trace[start..]
+        .iter()
+        .take_while(|x| x.timestamp() == timestamp)
+        .count();
+    start + same.min(count)
+}
+
+fn describe(sniffed: &SniffedMessage) -> String {
+    let msg = sniffed.message();
+    format!(
+        "[{}] {} -> {}: {} ({})",
+        sniffed.capture(),
+        msg.sender(),
+        msg.receiver(),
+        msg.message_type(),
+        msg.content()
+    )
+}
+
+/// Entry point of the `caravela` binary: launch the platform declared in the configuration file given as argument
+///  with the agent types of `registry`, then control it interactively from the standard input.
+pub fn main(registry: AgentRegistry) -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let (Some(path), None) = (args.next(), args.next()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+    if path == "-h" || path == "--help" {
+        println!("{}\n\n{}", USAGE, HELP);
+        return ExitCode::SUCCESS;
+    }
+    let session = PlatformConfig::load(&path)
+        .and_then(|config| Session::new(config, registry, Console::new(io::stdout())));
+    match session {
+        Ok(mut session) => {
+            session.run(io::stdin().lock());
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("caravela: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
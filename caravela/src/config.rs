@@ -0,0 +1,312 @@
+use crate::{
+    agent::{behavior::Behavior, AgentBuild, AgentBuildParam},
+    messaging::LanePolicy,
+    service::{AmsConditions, DefaultConditions, OwnershipConditions},
+    Description, ErrorCode, Platform, DEFAULT_STACK,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::HashMap, fmt::Debug, fs, path::Path};
+
+/// Parameters of an agent as given in the configuration file.
+pub type Params = serde_json::Value;
+
+type AgentConstructor = Box<dyn Fn(&Platform, &AgentConfig) -> Result<Description, ErrorCode>>;
+type PlatformConstructor = Box<dyn Fn(&str) -> Result<Platform, ErrorCode>>;
+
+/// Declaration of an agent in a [`PlatformConfig`].
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    /// Nickname of the agent.
+    pub nickname: String,
+    /// Name under which the agent type is registered in the [`AgentRegistry`].
+    #[serde(rename = "type")]
+    pub agent_type: String,
+    /// Priority of the agent.
+    pub priority: u8,
+    /// Stack size of the agent thread. [`DEFAULT_STACK`] if not given.
+    #[serde(default)]
+    pub stack: Option<usize>,
+    /// Whether the agent runs on the worker pool instead of a thread of its own, in which case the stack size is ignored.
+    ///  See [`Platform::add_lightweight_agent`].
+    #[serde(default)]
+    pub lightweight: bool,
+    /// Number of messages the mailbox of the agent can hold. One if not given.
+    #[serde(default)]
+    pub mailbox: Option<usize>,
+    /// Number of messages that may overtake a lower priority one waiting in the mailbox, see [`LanePolicy::Aging`].
+    ///  Strict priority if not given.
+    #[serde(default)]
+    pub aging: Option<u32>,
+    /// Core the agent is pinned to, if any.
+    #[serde(default)]
+    pub affinity: Option<usize>,
+    /// Parameters given to the constructor of the agent type.
+    #[serde(default)]
+    pub params: Params,
+}
+
+/// Declaration of a platform and its agents, read from a TOML or JSON file.
+///
+/// ```toml
+/// hap = "plant"
+/// ams_conditions = "ownership"
+/// start = ["Monitor", "Sensor"]
+/// pool_workers = 2
+///
+/// [[agents]]
+/// nickname = "Sensor"
+/// type = "Sensor"
+/// priority = 10
+/// mailbox = 4
+/// aging = 8
+/// lightweight = true
+///
+/// [[agents]]
+/// nickname = "Monitor"
+/// type = "Monitor"
+/// priority = 20
+/// stack = 65536
+/// affinity = 0
+/// params = { threshold = 3 }
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlatformConfig {
+    /// Name of the Host Agent Platform (HAP).
+    pub hap: String,
+    /// Name under which the AMS conditions are registered in the [`AgentRegistry`]. `default` if not given.
+    #[serde(default)]
+    pub ams_conditions: Option<String>,
+    /// Agents to be added to the platform, in order.
+    #[serde(default)]
+    pub agents: Vec<AgentConfig>,
+    /// Nicknames of the agents to be started, in order. Every agent in declaration order if not given.
+    #[serde(default)]
+    pub start: Option<Vec<String>>,
+    /// Number of worker threads running the lightweight agents. The available parallelism if not given.
+    #[serde(default)]
+    pub pool_workers: Option<usize>,
+}
+
+impl PlatformConfig {
+    /// Read a configuration in TOML format.
+    pub fn from_toml(text: &str) -> Result<Self, ErrorCode> {
+        toml::from_str(text).map_err(|error| ErrorCode::InvalidConfig(error.to_string()))
+    }
+
+    /// Read a configuration in JSON format.
+    pub fn from_json(text: &str) -> Result<Self, ErrorCode> {
+        serde_json::from_str(text).map_err(|error| ErrorCode::InvalidConfig(error.to_string()))
+    }
+
+    /// Read a configuration file, in JSON format if its extension is `json` and in TOML format otherwise.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ErrorCode> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|error| ErrorCode::InvalidConfig(format!("{}: {}", path.display(), error)))?;
+        if path.extension().is_some_and(|x| x == "json") {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+    }
+
+    /// Nicknames of the agents to start, in order.
+    fn start_order(&self) -> Vec<&str> {
+        match &self.start {
+            Some(order) => order.iter().map(String::as_str).collect(),
+            None => self.agents.iter().map(|x| x.nickname.as_str()).collect(),
+        }
+    }
+
+    /// Check that every type is registered and every started agent is declared, before anything is spawned.
+    fn validate(&self, registry: &AgentRegistry) -> Result<(), ErrorCode> {
+        let conditions = self.ams_conditions.as_deref().unwrap_or("default");
+        if !registry.conditions.contains_key(conditions) {
+            return Err(ErrorCode::InvalidConfig(format!(
+                "unknown AMS conditions {}",
+                conditions
+            )));
+        }
+        for (index, agent) in self.agents.iter().enumerate() {
+            if !registry.agents.contains_key(&agent.agent_type) {
+                return Err(ErrorCode::InvalidConfig(format!(
+                    "unknown type {} of agent {}",
+                    agent.agent_type, agent.nickname
+                )));
+            }
+            if self.agents[..index]
+                .iter()
+                .any(|x| x.nickname == agent.nickname)
+            {
+                return Err(ErrorCode::InvalidConfig(format!(
+                    "agent {} is declared twice",
+                    agent.nickname
+                )));
+            }
+        }
+        for nickname in self.start_order() {
+            if !self.agents.iter().any(|x| x.nickname == nickname) {
+                return Err(ErrorCode::InvalidConfig(format!(
+                    "agent {} is started but not declared",
+                    nickname
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the platform, add every agent and start them in order.
+    ///  Returns the platform and the [`Description`] of every agent in declaration order.
+    pub fn launch(
+        &self,
+        registry: &AgentRegistry,
+    ) -> Result<(Platform, Vec<Description>), ErrorCode> {
+        self.validate(registry)?;
+        let conditions = self.ams_conditions.as_deref().unwrap_or("default");
+        let platform = registry.conditions[conditions](&self.hap)?;
+        if let Some(workers) = self.pool_workers {
+            platform.set_pool_workers(workers)?;
+        }
+        let mut agents = Vec::with_capacity(self.agents.len());
+        for agent in &self.agents {
+            agents.push(registry.spawn(&platform, agent)?);
+        }
+        for nickname in self.start_order() {
+            if let Some(aid) = agents.iter().find(|x| x.nickname() == nickname) {
+                platform.start(aid)?;
+            }
+        }
+        Ok((platform, agents))
+    }
+}
+
+/// Agent types and AMS conditions registered by name, so they can be declared in a [`PlatformConfig`].
+///  The `default` and `ownership` AMS conditions are registered from the start.
+pub struct AgentRegistry {
+    agents: HashMap<String, AgentConstructor>,
+    conditions: HashMap<String, PlatformConstructor>,
+}
+
+impl Debug for AgentRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AgentRegistry")
+            .field("agents", &self.agents.keys().collect::<Vec<_>>())
+            .field("conditions", &self.conditions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Default for AgentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AgentRegistry {
+    /// Create a registry without agent types.
+    pub fn new() -> Self {
+        let mut registry = Self {
+            agents: HashMap::new(),
+            conditions: HashMap::new(),
+        };
+        registry.register_conditions("default", || DefaultConditions);
+        registry.register_conditions("ownership", || OwnershipConditions);
+        registry
+    }
+
+    /// Register an agent type without parameters. Any parameters given in the configuration are ignored.
+    pub fn register<T: Behavior + AgentBuild + Send + 'static>(&mut self, name: &str) -> &mut Self {
+        let constructor: AgentConstructor = Box::new(|platform, config| {
+            if config.lightweight {
+                return platform
+                    .add_lightweight_agent::<T>(config.nickname.as_str(), config.priority);
+            }
+            platform.add_agent::<T>(
+                config.nickname.as_str(),
+                config.priority,
+                config.stack.unwrap_or(DEFAULT_STACK),
+            )
+        });
+        self.agents.insert(name.to_string(), constructor);
+        self
+    }
+
+    /// Register an agent type whose parameter is built from the configuration by `parse`.
+    pub fn register_with_param<T, F>(&mut self, name: &str, parse: F) -> &mut Self
+    where
+        T: Behavior + AgentBuildParam + Send + 'static,
+        F: Fn(&Params) -> Result<T::Parameter, ErrorCode> + 'static,
+    {
+        let constructor: AgentConstructor = Box::new(move |platform, config| {
+            let param = parse(&config.params)?;
+            if config.lightweight {
+                return platform.add_lightweight_agent_with_param::<T>(
+                    config.nickname.as_str(),
+                    config.priority,
+                    param,
+                );
+            }
+            platform.add_agent_with_param::<T>(
+                config.nickname.as_str(),
+                config.priority,
+                config.stack.unwrap_or(DEFAULT_STACK),
+                param,
+            )
+        });
+        self.agents.insert(name.to_string(), constructor);
+        self
+    }
+
+    /// Register an agent type whose parameter is deserialized from the configuration.
+    pub fn register_deserialized<T>(&mut self, name: &str) -> &mut Self
+    where
+        T: Behavior + AgentBuildParam + Send + 'static,
+        T::Parameter: DeserializeOwned,
+    {
+        self.register_with_param::<T, _>(name, |params| {
+            T::Parameter::deserialize(params)
+                .map_err(|error| ErrorCode::InvalidConfig(error.to_string()))
+        })
+    }
+
+    /// Register AMS conditions, built by `conditions` when the platform is launched.
+    pub fn register_conditions<T, F>(&mut self, name: &str, conditions: F) -> &mut Self
+    where
+        T: AmsConditions + Send + 'static,
+        F: Fn() -> T + 'static,
+    {
+        let constructor: PlatformConstructor =
+            Box::new(move |hap| Platform::new_with_conditions(hap, conditions()));
+        self.conditions.insert(name.to_string(), constructor);
+        self
+    }
+
+    /// Add the agent declared by `config` to the platform, without starting it.
+    pub fn spawn(
+        &self,
+        platform: &Platform,
+        config: &AgentConfig,
+    ) -> Result<Description, ErrorCode> {
+        let constructor = self.agents.get(&config.agent_type).ok_or_else(|| {
+            ErrorCode::InvalidConfig(format!("unknown type {}", config.agent_type))
+        })?;
+        let aid = constructor(platform, config)?;
+        if let Some(capacity) = config.mailbox {
+            platform.set_mailbox_capacity(&aid, capacity)?;
+        }
+        if let Some(limit) = config.aging {
+            platform.set_lane_policy(&aid, LanePolicy::Aging(limit))?;
+        }
+        if let Some(core) = config.affinity {
+            platform.set_affinity(&aid, core)?;
+        }
+        Ok(aid)
+    }
+
+    /// Whether or not an agent type is registered under the name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.agents.contains_key(name)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    deck::deck,
+    entity::dispatch,
+    messaging::{Message, Reason, SyncType},
+    ErrorCode, DEAD_LETTER_CAPACITY,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+static LETTERS: Mutex<DeadLetters> = Mutex::new(DeadLetters {
+    letters: VecDeque::new(),
+    next: 0,
+});
+
+/// Identifier of a dead letter, used to redeliver it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeadLetterId(u64);
+
+/// A message that could not be delivered, along with the reason and the time it was given up.
+#[derive(Clone, Debug)]
+pub struct DeadLetter {
+    id: DeadLetterId,
+    message: Message,
+    reason: Reason,
+    at: SystemTime,
+}
+
+impl DeadLetter {
+    /// Get the identifier of the dead letter.
+    pub fn id(&self) -> DeadLetterId {
+        self.id
+    }
+
+    /// Get the message that could not be delivered.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    /// Get the reason the message could not be delivered.
+    pub fn reason(&self) -> &Reason {
+        &self.reason
+    }
+
+    /// Get the time the message was given up.
+    pub fn at(&self) -> SystemTime {
+        self.at
+    }
+}
+
+#[derive(Debug)]
+struct DeadLetters {
+    letters: VecDeque<DeadLetter>,
+    next: u64,
+}
+
+fn letters() -> MutexGuard<'static, DeadLetters> {
+    LETTERS
+        .lock()
+        .expect("Dead-letter queue is poisoned - Lost messages")
+}
+
+/// Keep an undeliverable message, discarding the oldest one when the queue is full.
+pub(crate) fn record(message: Message, reason: Reason) {
+    caravela_message!(message, "Message dead-lettered");
+    let mut letters = letters();
+    let id = DeadLetterId(letters.next);
+    letters.next += 1;
+    if letters.letters.len() >= DEAD_LETTER_CAPACITY {
+        letters.letters.pop_front();
+    }
+    letters.letters.push_back(DeadLetter {
+        id,
+        message,
+        reason,
+        at: SystemTime::now(),
+    });
+}
+
+/// Copy the dead letters that satisfy `filter`, oldest first.
+pub(crate) fn list(filter: impl Fn(&DeadLetter) -> bool) -> Vec<DeadLetter> {
+    letters()
+        .letters
+        .iter()
+        .filter(|letter| filter(letter))
+        .cloned()
+        .collect()
+}
+
+/// Drop every dead letter.
+pub(crate) fn clear() {
+    letters().letters.clear();
+}
+
+/// Send a dead letter satisfying `filter` again to the agent now registered under the name of its receiver,
+///  since the description it was addressed with may be stale. The redelivered message does not expire,
+///  and it becomes a new dead letter if it still cannot be delivered.
+pub(crate) fn redeliver(
+    id: DeadLetterId,
+    filter: impl Fn(&DeadLetter) -> bool,
+) -> Result<(), ErrorCode> {
+    let message = letters()
+        .letters
+        .iter()
+        .find(|letter| letter.id == id && filter(letter))
+        .map(|letter| letter.message.clone())
+        .ok_or(ErrorCode::NotFound)?;
+    let receiver = deck()
+        .view()
+        .get_aid_from_name(&message.receiver().to_string())?;
+    let mut letters = letters();
+    let index = letters
+        .letters
+        .iter()
+        .position(|letter| letter.id == id)
+        .ok_or(ErrorCode::NotFound)?;
+    letters.letters.remove(index);
+    drop(letters);
+    dispatch(message.readdressed(receiver), SyncType::NonBlocking)
+}
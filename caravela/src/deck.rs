@@ -1,26 +1,46 @@
 use crate::{
     agent::AgentState,
-    entity::{agent::ControlBlockArc, Description},
-    ErrorCode, MAX_SUBSCRIBERS,
+    entity::{
+        agent::{AgentStats, ControlBlockArc},
+        messaging::LanePolicy,
+        service::{
+            organization::Organization,
+            sniffer::{self, TraceArc},
+            Role,
+        },
+        Description,
+    },
+    executor::SignalArc,
+    pool::{self, TaskId, Wake},
+    timer, ErrorCode, MAX_SUBSCRIBERS,
 };
 use std::{
-    collections::HashMap,
-    sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    thread::{JoinHandle, Thread, ThreadId},
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    ops::{Deref, DerefMut},
+    sync::{Arc, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    thread::{self, JoinHandle, Thread, ThreadId},
+    time::{Duration, Instant},
 };
 use thread_priority::ThreadPriority;
 
 pub(crate) type AgentDirectory = HashMap<Description, AgentEntry>;
+pub(crate) type Properties = HashMap<String, String>;
+pub(crate) type OrgDirectory = HashMap<String, Organization>;
+type NameIndex = HashMap<Arc<str>, Arc<Listing>>;
+type ThreadIndex = HashMap<ThreadId, Arc<Listing>>;
 //pub(crate) type AmsDirectory = HashMap<Description, AmsEntry>;
 
+const JOIN_POLL: Duration = Duration::from_millis(5);
+
 #[derive(Debug)]
-pub(crate) struct AmsEntry {
+pub(crate) struct ServiceEntry {
     aid: Description,
     #[allow(dead_code)]
     join_handle: JoinHandle<()>,
 }
 
-impl AmsEntry {
+impl ServiceEntry {
     pub(crate) fn aid(&self) -> &Description {
         &self.aid
     }
@@ -29,11 +49,56 @@ impl AmsEntry {
     //}
 }
 
+#[derive(Debug)]
+pub(crate) struct SnifferEntry {
+    service: ServiceEntry,
+    trace: TraceArc,
+    sniffed: Arc<HashSet<Description>>,
+}
+
+/// What executes an agent: its own thread, a task of the worker pool for lightweight agents,
+///  or a future on the executor for asynchronous agents.
+#[derive(Debug)]
+pub(crate) enum Runner {
+    Thread(JoinHandle<()>),
+    Pooled(TaskId),
+    Async(SignalArc),
+}
+
+impl Runner {
+    /// Wake the agent up so it notices a change of its state.
+    pub(crate) fn wake(&self) {
+        match self {
+            Runner::Thread(join_handle) => join_handle.thread().unpark(),
+            Runner::Pooled(id) => pool::wake(*id, Wake::Control),
+            Runner::Async(signal) => signal.wake(),
+        }
+    }
+
+    /// Wait for the thread of the agent to finish, other agents finish on their own within the pool or the executor.
+    ///  A thread still running after `timeout` is left detached and [`ErrorCode::Timeout`] is returned.
+    pub(crate) fn join(self, timeout: Duration) -> Result<(), ErrorCode> {
+        let Runner::Thread(join_handle) = self else {
+            return Ok(());
+        };
+        let until = Instant::now() + timeout;
+        while !join_handle.is_finished() {
+            if Instant::now() >= until {
+                return Err(ErrorCode::Timeout);
+            }
+            thread::sleep(JOIN_POLL);
+        }
+        join_handle.join().map_err(|_| ErrorCode::AgentPanic)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct AgentEntry {
-    pub(crate) join_handle: JoinHandle<()>,
+    pub(crate) runner: Runner,
+    added: u64,
     priority: ThreadPriority,
     control_block: ControlBlockArc,
+    properties: Properties,
 }
 
 impl AgentEntry {
@@ -45,35 +110,210 @@ impl AgentEntry {
         self.priority
     }
 
-    pub(crate) fn thread(&self) -> Thread {
-        self.join_handle.thread().clone()
+    /// The thread of the agent, [`None`] for a lightweight or asynchronous agent.
+    pub(crate) fn thread(&self) -> Option<Thread> {
+        match &self.runner {
+            Runner::Thread(join_handle) => Some(join_handle.thread().clone()),
+            Runner::Pooled(_) | Runner::Async(_) => None,
+        }
+    }
+
+    pub(crate) fn set_priority(&mut self, level: u8, priority: ThreadPriority) {
+        self.priority = priority;
+        self.control_block.set_priority(level, priority);
+    }
+
+    pub(crate) fn set_property(&mut self, key: &str, value: &str) {
+        self.properties.insert(key.to_string(), value.to_string());
+    }
+
+    pub(crate) fn remove_property(&mut self, key: &str) -> Result<(), ErrorCode> {
+        self.properties
+            .remove(key)
+            .map(|_| ())
+            .ok_or(ErrorCode::InvalidRequest(format!("no property {}", key)))
     }
 }
 
 #[derive(Debug)]
-pub(crate) struct DeckAccess(RwLock<Deck>);
+pub(crate) struct DeckAccess {
+    deck: RwLock<Deck>,
+    view: RwLock<Arc<DeckView>>,
+}
 
 impl DeckAccess {
     pub(crate) fn new() -> DeckAccess {
-        DeckAccess(RwLock::new(Deck::new()))
+        DeckAccess {
+            deck: RwLock::new(Deck::new()),
+            view: RwLock::default(),
+        }
     }
-    pub(crate) fn write(&self) -> RwLockWriteGuard<Deck> {
-        self.0
-            .write()
-            .expect("Deck is poisoned - Lost agent records")
+    /// Lock the deck to modify it. The [`DeckView`] is published again once the guard is dropped.
+    pub(crate) fn write(&self) -> DeckWriteGuard<'_> {
+        DeckWriteGuard {
+            deck: self
+                .deck
+                .write()
+                .expect("Deck is poisoned - Lost agent records"),
+            view: &self.view,
+        }
     }
-    pub(crate) fn read(&self) -> RwLockReadGuard<Deck> {
-        self.0
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, Deck> {
+        self.deck
             .read()
             .expect("Deck is poisoned - Lost agent records")
     }
+    /// Latest snapshot of the lookup tables, which never waits for a writer of the deck.
+    pub(crate) fn view(&self) -> Arc<DeckView> {
+        self.view.read().expect("Deck view is poisoned").clone()
+    }
+}
+
+pub(crate) struct DeckWriteGuard<'a> {
+    deck: RwLockWriteGuard<'a, Deck>,
+    view: &'a RwLock<Arc<DeckView>>,
+}
+
+impl Deref for DeckWriteGuard<'_> {
+    type Target = Deck;
+
+    fn deref(&self) -> &Deck {
+        &self.deck
+    }
+}
+
+impl DerefMut for DeckWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Deck {
+        &mut self.deck
+    }
+}
+
+impl Drop for DeckWriteGuard<'_> {
+    fn drop(&mut self) {
+        if self
+            .deck
+            .is_shown_in(&self.view.read().expect("Deck view is poisoned"))
+        {
+            return;
+        }
+        // published while the deck is still locked, so views are replaced in the order of the writes
+        *self.view.write().expect("Deck view is poisoned") = Arc::new(self.deck.view());
+    }
+}
+
+/// What the [`DeckView`] knows of a registered entity, shared by its name and thread indices.
+///  Services have no control block.
+#[derive(Clone, Debug)]
+pub(crate) struct Listing {
+    aid: Description,
+    control_block: Option<ControlBlockArc>,
+    owner: Option<Description>,
+    role: Role,
+}
+
+impl Listing {
+    fn new(aid: Description, control_block: Option<ControlBlockArc>) -> Self {
+        Self {
+            aid,
+            control_block,
+            owner: None,
+            role: Role::default(),
+        }
+    }
+}
+
+/// Read-copy-update snapshot of the lookup tables of the [`Deck`], used on the path of every message.
+///  Its tables are shared with the deck, which copies them on its next change and only then publishes a new view.
+#[derive(Debug, Default)]
+pub(crate) struct DeckView {
+    ams: Option<Description>,
+    names: Arc<NameIndex>,
+    threads: Arc<ThreadIndex>,
+    org_directory: Arc<OrgDirectory>,
+    trace: Option<TraceArc>,
+    sniffed: Arc<HashSet<Description>>,
+}
+
+impl DeckView {
+    pub(crate) fn ams_aid(&self) -> &Description {
+        self.ams.as_ref().expect("Platform has not been booted yet")
+    }
+
+    pub(crate) fn get_aid_from_name(&self, name: &str) -> Result<Description, ErrorCode> {
+        self.names
+            .get(name)
+            .map(|listing| listing.aid.clone())
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    pub(crate) fn get_aid_from_thread(&self, id: ThreadId) -> Result<Description, ErrorCode> {
+        self.threads
+            .get(&id)
+            .map(|listing| listing.aid.clone())
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    fn listing(&self, aid: &Description) -> Option<&Listing> {
+        self.names.get(&*aid.name()).map(Arc::as_ref)
+    }
+
+    pub(crate) fn owner(&self, aid: &Description) -> Option<Description> {
+        self.listing(aid).and_then(|listing| listing.owner.clone())
+    }
+
+    pub(crate) fn role(&self, aid: &Description) -> Role {
+        self.listing(aid)
+            .map(|listing| listing.role)
+            .unwrap_or_default()
+    }
+
+    /// State of a registered agent, [`None`] for services and unregistered agents.
+    pub(crate) fn agent_state(&self, aid: &Description) -> Option<AgentState> {
+        self.listing(aid)?
+            .control_block
+            .as_ref()
+            .map(|control_block| control_block.agent_state())
+    }
+
+    pub(crate) fn get_org(&self, name: &str) -> Result<&Organization, ErrorCode> {
+        self.org_directory.get(name).ok_or(ErrorCode::NotFound)
+    }
+
+    /// Check the visibility rules of every organization the receiver belongs to.
+    pub(crate) fn check_visibility(
+        &self,
+        sender: &Description,
+        receiver: &Description,
+    ) -> Result<(), ErrorCode> {
+        self.org_directory
+            .values()
+            .try_for_each(|org| org.check_visibility(sender, receiver))
+    }
+
+    /// Get the trace of the sniffer if either end of a message is being sniffed.
+    pub(crate) fn sniffer_trace(
+        &self,
+        sender: &Description,
+        receiver: &Description,
+    ) -> Option<TraceArc> {
+        self.trace
+            .as_ref()
+            .filter(|_| self.sniffed.contains(sender) || self.sniffed.contains(receiver))
+            .cloned()
+    }
 }
 
 #[derive(Debug)]
 pub struct Deck {
-    ams_entry: Option<AmsEntry>,
+    ams_entry: Option<ServiceEntry>,
+    org_entry: Option<ServiceEntry>,
+    sniffer_entry: Option<SnifferEntry>,
     //ams_directory: AmsDirectory,
     agent_directory: AgentDirectory,
+    names: Arc<NameIndex>,
+    threads: Arc<ThreadIndex>,
+    org_directory: Arc<OrgDirectory>,
+    added: u64,
 }
 
 impl Deck {
@@ -83,10 +323,91 @@ impl Deck {
         //let ams_directory = AmsDirectory::with_capacity(MAX_SUBSCRIBERS);
         Self {
             ams_entry,
+            org_entry: None,
+            sniffer_entry: None,
             //ams_directory,
             agent_directory,
+            names: Arc::default(),
+            threads: Arc::default(),
+            org_directory: Arc::default(),
+            added: 0,
         }
     }
+    fn view(&self) -> DeckView {
+        DeckView {
+            ams: self.ams_entry.as_ref().map(|entry| entry.aid.clone()),
+            names: self.names.clone(),
+            threads: self.threads.clone(),
+            org_directory: self.org_directory.clone(),
+            trace: self.sniffer_entry.as_ref().map(|entry| entry.trace.clone()),
+            sniffed: self
+                .sniffer_entry
+                .as_ref()
+                .map(|entry| entry.sniffed.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether the view shows the current tables, which are given new allocations whenever they change.
+    fn is_shown_in(&self, view: &DeckView) -> bool {
+        let sniffer = match (&self.sniffer_entry, &view.trace) {
+            (Some(entry), Some(trace)) => {
+                Arc::ptr_eq(&entry.trace, trace) && Arc::ptr_eq(&entry.sniffed, &view.sniffed)
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        sniffer
+            && self.ams_entry.is_some() == view.ams.is_some()
+            && Arc::ptr_eq(&self.names, &view.names)
+            && Arc::ptr_eq(&self.threads, &view.threads)
+            && Arc::ptr_eq(&self.org_directory, &view.org_directory)
+    }
+
+    fn index(&mut self, listing: Listing) {
+        let listing = Arc::new(listing);
+        if let Some(id) = listing.aid.id() {
+            Arc::make_mut(&mut self.threads).insert(id, listing.clone());
+        }
+        Arc::make_mut(&mut self.names).insert(listing.aid.name().into(), listing);
+    }
+
+    fn unindex(&mut self, aid: &Description) {
+        Arc::make_mut(&mut self.names).remove(&*aid.name());
+        if let Some(id) = aid.id() {
+            Arc::make_mut(&mut self.threads).remove(&id);
+        }
+    }
+
+    /// Change the listing of a registered agent in both indices.
+    fn relist(
+        &mut self,
+        aid: &Description,
+        change: impl FnOnce(&mut Listing),
+    ) -> Result<(), ErrorCode> {
+        self.search_agent(aid)?;
+        let mut listing = Listing::clone(
+            self.names
+                .get(&*aid.name())
+                .ok_or(ErrorCode::NotRegistered)?,
+        );
+        change(&mut listing);
+        self.index(listing);
+        Ok(())
+    }
+
+    pub(crate) fn set_owner(
+        &mut self,
+        aid: &Description,
+        owner: Description,
+    ) -> Result<(), ErrorCode> {
+        self.relist(aid, |listing| listing.owner = Some(owner))
+    }
+
+    pub(crate) fn set_role(&mut self, aid: &Description, role: Role) -> Result<(), ErrorCode> {
+        self.relist(aid, |listing| listing.role = role)
+    }
+
     /*pub(crate) fn get_ams_address_for_hap(&self, name: &str) -> Result<Description, ErrorCode> {
         self.ams_directory
             .keys()
@@ -111,13 +432,89 @@ impl Deck {
             },
         );*/
 
-        self.ams_entry = Some(AmsEntry {
+        self.index(Listing::new(aid.clone(), None));
+        self.ams_entry = Some(ServiceEntry {
             aid,
             //address,
             join_handle,
         });
     }
 
+    pub(crate) fn org_aid(&self) -> Result<&Description, ErrorCode> {
+        self.org_entry
+            .as_ref()
+            .map(|entry| entry.aid())
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    pub(crate) fn add_org_service(&mut self, aid: Description, join_handle: JoinHandle<()>) {
+        self.index(Listing::new(aid.clone(), None));
+        self.org_entry = Some(ServiceEntry { aid, join_handle });
+    }
+
+    pub(crate) fn sniffer_aid(&self) -> Result<&Description, ErrorCode> {
+        self.sniffer_entry
+            .as_ref()
+            .map(|entry| entry.service.aid())
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    pub(crate) fn add_sniffer(
+        &mut self,
+        aid: Description,
+        join_handle: JoinHandle<()>,
+        trace: TraceArc,
+    ) {
+        self.index(Listing::new(aid.clone(), None));
+        self.sniffer_entry = Some(SnifferEntry {
+            service: ServiceEntry { aid, join_handle },
+            trace,
+            sniffed: Arc::default(),
+        });
+    }
+
+    pub(crate) fn sniff(&mut self, aid: &Description) -> Result<(), ErrorCode> {
+        self.search_agent(aid)?;
+        let entry = self.sniffer_entry.as_mut().ok_or(ErrorCode::NotFound)?;
+        Arc::make_mut(&mut entry.sniffed).insert(aid.clone());
+        sniffer::set_active(true);
+        Ok(())
+    }
+
+    pub(crate) fn unsniff(&mut self, aid: &Description) -> Result<(), ErrorCode> {
+        let entry = self.sniffer_entry.as_mut().ok_or(ErrorCode::NotFound)?;
+        if !Arc::make_mut(&mut entry.sniffed).remove(aid) {
+            return Err(ErrorCode::NotFound);
+        }
+        sniffer::set_active(!entry.sniffed.is_empty());
+        Ok(())
+    }
+
+    pub(crate) fn get_org(&self, name: &str) -> Result<&Organization, ErrorCode> {
+        self.org_directory.get(name).ok_or(ErrorCode::NotFound)
+    }
+
+    pub(crate) fn get_org_mut(&mut self, name: &str) -> Result<&mut Organization, ErrorCode> {
+        Arc::make_mut(&mut self.org_directory)
+            .get_mut(name)
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    pub(crate) fn open_org(&mut self, name: &str, org: Organization) -> Result<(), ErrorCode> {
+        if self.org_directory.contains_key(name) {
+            return Err(ErrorCode::Duplicated);
+        }
+        Arc::make_mut(&mut self.org_directory).insert(name.to_string(), org);
+        Ok(())
+    }
+
+    pub(crate) fn close_org(&mut self, name: &str) -> Result<(), ErrorCode> {
+        Arc::make_mut(&mut self.org_directory)
+            .remove(name)
+            .map(|_| ())
+            .ok_or(ErrorCode::NotFound)
+    }
+
     pub(crate) fn search_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
         self.agent_directory
             .contains_key(aid)
@@ -131,73 +528,149 @@ impl Deck {
             .ok_or(ErrorCode::NotRegistered)
     }
 
+    pub(crate) fn get_agent_mut(
+        &mut self,
+        aid: &Description,
+    ) -> Result<&mut AgentEntry, ErrorCode> {
+        self.agent_directory
+            .get_mut(aid)
+            .ok_or(ErrorCode::NotRegistered)
+    }
+
     pub(crate) fn add_agent(
         &mut self,
         aid: Description,
-        join_handle: JoinHandle<()>,
+        runner: Runner,
         priority: ThreadPriority,
         control_block: ControlBlockArc,
         //address: Tx,
     ) -> Result<(), ErrorCode> {
         if self.search_agent(&aid).is_err() {
+            self.added += 1;
             let agent_entry = AgentEntry {
-                join_handle,
+                runner,
+                added: self.added,
                 priority,
-                control_block,
+                control_block: control_block.clone(),
+                properties: Properties::new(),
                 //address,
             };
-            self.agent_directory.insert(aid.clone(), agent_entry);
+            self.index(Listing::new(aid.clone(), Some(control_block)));
+            self.agent_directory.insert(aid, agent_entry);
             Ok(())
         } else {
             Err(ErrorCode::Duplicated)
         }
     }
 
+    /// Agents not started yet, from the highest priority down and in the order they were added for equal priorities.
+    pub(crate) fn start_order(&self) -> Vec<Description> {
+        let mut initiated: Vec<_> = self
+            .agent_directory
+            .iter()
+            .filter(|(_, entry)| entry.control_block.agent_state() == AgentState::Initiated)
+            .collect();
+        initiated
+            .sort_by_key(|(_, entry)| (Reverse(entry.control_block.priority_level()), entry.added));
+        initiated.into_iter().map(|(aid, _)| aid.clone()).collect()
+    }
+
+    /// Change the state of the agent, returning the state it had before.
     pub(crate) fn modify_agent(
         &self,
         aid: &Description,
         state: AgentState,
-    ) -> Result<(), ErrorCode> {
-        let entry = self.get_agent(aid)?;
+    ) -> Result<AgentState, ErrorCode> {
+        let (registered, entry) = self
+            .agent_directory
+            .get_key_value(aid)
+            .ok_or(ErrorCode::NotRegistered)?;
+        let previous = entry.control_block().agent_state();
         match state {
-            AgentState::Active => {
-                entry.control_block().active()?;
-                entry.join_handle.thread().unpark();
-                Ok(())
-            }
-            AgentState::Suspended => entry.control_block().suspend(),
-            AgentState::Terminated => {
-                entry.control_block().quit()
-                //join?
-            }
-            _ => Err(ErrorCode::InvalidStateChange(
-                entry.control_block().agent_state(),
-                state,
-            )),
+            AgentState::Active => entry.control_block().active()?,
+            AgentState::Suspended => entry.control_block().suspend()?,
+            AgentState::Terminated => entry.control_block().quit()?,
+            _ => return Err(ErrorCode::InvalidStateChange(previous, state)),
+        }
+        if state != AgentState::Active {
+            // blocking calls of the agent return ErrorCode::Interrupted
+            entry.control_block().interrupt();
+            registered.address().interrupt();
         }
+        entry.runner.wake();
+        Ok(previous)
+    }
+
+    pub(crate) fn set_mailbox_capacity(
+        &self,
+        aid: &Description,
+        capacity: usize,
+    ) -> Result<(), ErrorCode> {
+        let (registered, _) = self
+            .agent_directory
+            .get_key_value(aid)
+            .ok_or(ErrorCode::NotRegistered)?;
+        registered.address().set_capacity(capacity);
+        Ok(())
+    }
+
+    pub(crate) fn set_lane_policy(
+        &self,
+        aid: &Description,
+        policy: LanePolicy,
+    ) -> Result<(), ErrorCode> {
+        let (registered, _) = self
+            .agent_directory
+            .get_key_value(aid)
+            .ok_or(ErrorCode::NotRegistered)?;
+        registered.address().set_policy(policy);
+        Ok(())
     }
 
     pub(crate) fn remove_agent(&mut self, aid: &Description) -> Result<AgentEntry, ErrorCode> {
-        self.agent_directory
+        let entry = self
+            .agent_directory
             .remove(aid)
-            .ok_or(ErrorCode::NotRegistered)
+            .ok_or(ErrorCode::NotRegistered)?;
+        self.unindex(aid);
+        timer::cancel_all(aid);
+        if !self.org_directory.is_empty() {
+            Arc::make_mut(&mut self.org_directory).retain(|_, org| !org.purge(aid));
+        }
+        if let Some(sniffer_entry) = self.sniffer_entry.as_mut() {
+            if sniffer_entry.sniffed.contains(aid) {
+                Arc::make_mut(&mut sniffer_entry.sniffed).remove(aid);
+            }
+            sniffer::set_active(!sniffer_entry.sniffed.is_empty());
+        }
+        Ok(entry)
     }
 
     pub(crate) fn get_aid_from_name(&self, name: &str) -> Result<Description, ErrorCode> {
-        self.agent_directory
-            .keys()
-            .find(|x| x.name() == *name)
-            .cloned()
+        self.names
+            .get(name)
+            .map(|listing| listing.aid.clone())
             .ok_or(ErrorCode::NotFound)
     }
 
-    pub(crate) fn get_aid_from_thread(&self, id: ThreadId) -> Result<Description, ErrorCode> {
-        //.find(|aid| aid.id().is_some_and(|x| x == id))
+    /// Gather the stats of every agent, only reading their control blocks and mailboxes.
+    pub(crate) fn agent_stats(&self) -> Vec<AgentStats> {
+        self.agent_directory
+            .iter()
+            .map(|(aid, entry)| {
+                entry
+                    .control_block
+                    .stats(aid.nickname(), aid.address().len())
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(crate) fn mailbox_high_water(&self) -> Vec<(Description, usize)> {
         self.agent_directory
             .keys()
-            .find(|aid| aid.id().eq(&Some(id)))
-            .cloned()
-            .ok_or(ErrorCode::NotFound)
+            .map(|aid| (aid.clone(), aid.address().high_water()))
+            .collect()
     }
 }
 
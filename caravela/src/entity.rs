@@ -5,14 +5,26 @@ pub mod messaging;
 /// Service related features.
 pub mod service;
 
-use crate::{ErrorCode, Rx, Tx};
+use crate::{
+    dead_letter,
+    deck::deck,
+    events::{self, Event},
+    journal,
+    platform::RESERVED_NAMES,
+    simulation, ErrorCode, Rx, Tx, MAX_NAME_LENGTH,
+};
 //use messaging::{Content, Message, SendResult, SyncType};
-use messaging::{Message, SendResult, SyncType};
+use messaging::{Message, Reason, SyncType};
+use service::{
+    sniffer::{self, Capture, TraceArc},
+    Role,
+};
 use std::{
     fmt::Display,
     hash::{self, Hash},
-    sync::mpsc::{SendError, TrySendError},
+    sync::Arc,
     thread::ThreadId,
+    time::{Duration, Instant, SystemTime},
 };
 
 /// Agent Identifier (AID) that is unique to all entities across platforms.
@@ -22,8 +34,8 @@ use std::{
 /// - `Id` which is unique among the process since it identifies the thread executing the entity and it is given as type [`ThreadId`].
 #[derive(Clone, Debug)]
 pub struct Description {
-    nickname: &'static str,
-    hap: &'static str,
+    nickname: Arc<str>,
+    hap: Arc<str>,
     tx: Tx,
     id: Option<ThreadId>,
 }
@@ -40,7 +52,6 @@ impl Hash for Description {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
         self.nickname.hash(state);
         self.hap.hash(state);
-        self.id.hash(state);
     }
 }
 
@@ -51,10 +62,10 @@ impl Display for Description {
 }
 
 impl Description {
-    pub(crate) fn new(nickname: &'static str, hap: &'static str, tx: Tx) -> Self {
+    pub(crate) fn new(nickname: impl Into<Arc<str>>, hap: impl Into<Arc<str>>, tx: Tx) -> Self {
         Self {
-            nickname,
-            hap,
+            nickname: nickname.into(),
+            hap: hap.into(),
             tx,
             id: None,
         }
@@ -67,12 +78,18 @@ impl Description {
 
     /// Return a `&str` slice with the nickname of the name; the left side of nickname@hap.
     pub fn nickname(&self) -> &str {
-        self.nickname
+        &self.nickname
     }
 
     /// Return a `&str` slice with name of the Host Agent Platform (HAP) of the name; the right side of nickname@hap.
     pub fn hap(&self) -> &str {
-        self.hap
+        &self.hap
+    }
+
+    /// Return the nickname and the name of the HAP without copying them, to key records by agent.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn shared_name(&self) -> (Arc<str>, Arc<str>) {
+        (self.nickname.clone(), self.hap.clone())
     }
 
     pub(crate) fn address(&self) -> &Tx {
@@ -84,15 +101,154 @@ impl Description {
         self.id
     }
 
+    /// Return the [`Description`] of the agent that owns this one, if any.
+    pub fn owner(&self) -> Option<Description> {
+        deck().view().owner(self)
+    }
+
+    /// Return the [`Role`] of the agent within the platform. Unregistered agents have the default role.
+    pub fn role(&self) -> Role {
+        deck().view().role(self)
+    }
+
     pub(crate) fn set_id(&mut self, id: ThreadId) {
         //self.thread = Some(current().id());
         self.id = Some(id);
     }
 }
 
+/// Reason for a nickname or platform name to be rejected, given by [`ErrorCode::InvalidName`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NameError {
+    /// The name is empty.
+    Empty,
+    /// The name is longer than [`MAX_NAME_LENGTH`] bytes, given its length.
+    TooLong(usize),
+    /// The name contains a character other than ASCII letters, digits, `-`, `_` and `.`.
+    InvalidCharacter(char),
+    /// The nickname belongs to a platform service such as the AMS.
+    Reserved(String),
+}
+
+impl Display for NameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameError::Empty => write!(f, "the name is empty"),
+            NameError::TooLong(length) => write!(
+                f,
+                "the name is {} bytes long, the limit is {}",
+                length, MAX_NAME_LENGTH
+            ),
+            NameError::InvalidCharacter(c) => {
+                write!(f, "the character {:?} is not allowed in names", c)
+            }
+            NameError::Reserved(name) => {
+                write!(f, "{} is reserved for the platform services", name)
+            }
+        }
+    }
+}
+
+/// Check that the name of a Host Agent Platform (HAP), the right side of nickname@hap, can be used.
+pub(crate) fn validate_hap(name: &str) -> Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(NameError::TooLong(name.len()));
+    }
+    match name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+    {
+        Some(c) => Err(NameError::InvalidCharacter(c)),
+        None => Ok(()),
+    }
+}
+
+/// Check that the nickname of a user defined agent, the left side of nickname@hap, can be used.
+///  Nicknames follow the rules of platform names and cannot take the ones of the platform services.
+pub(crate) fn validate_nickname(nickname: &str) -> Result<(), NameError> {
+    validate_hap(nickname)?;
+    if RESERVED_NAMES.contains(&nickname) {
+        return Err(NameError::Reserved(nickname.to_string()));
+    }
+    Ok(())
+}
+
+/// Deliver the message to its receiver, as sent through any [`Hub`], so it is sniffed, counted and reported.
+///  Messages that expired, or whose receiver is gone or has no room, are kept in the dead-letter queue.
+pub(crate) fn dispatch(msg: Message, sync: SyncType) -> Result<(), ErrorCode> {
+    deliver(msg, sync, true)
+}
+
+/// Deliver the message only if there is room for it, leaving it to be sent again by the caller otherwise.
+pub(crate) fn try_dispatch(msg: Message) -> Result<(), ErrorCode> {
+    deliver(msg, SyncType::NonBlocking, false)
+}
+
+fn deliver(msg: Message, sync: SyncType, bury_full: bool) -> Result<(), ErrorCode> {
+    caravela_message!(msg, "Sending message");
+    let trace = sniffed(&msg);
+    // kept for the dead-letter queue, sharing the descriptions and content of the message
+    let copy = msg.clone();
+    let started = Instant::now();
+    // captured ahead of the delivery, since the receiver may capture the message before this thread gets to it
+    let sent_at = trace.as_ref().map(|trace| {
+        let sent_at = SystemTime::now();
+        trace.record_at(sent_at, Capture::Sent, copy.clone());
+        sent_at
+    });
+    let visibility = deck().view().check_visibility(msg.sender(), msg.receiver());
+    let result = visibility.and_then(|_| {
+        if msg.is_expired() {
+            return Err(ErrorCode::Expired);
+        }
+        match simulation::schedule(msg) {
+            None => Ok(()),
+            Some(msg) => {
+                let address = msg.receiver().address().clone();
+                match sync {
+                    SyncType::Blocking => address.send(msg),
+                    SyncType::NonBlocking => address.try_send(msg), //LIST MAY BE OUTDATED
+                    SyncType::Interruptible(control_block) => {
+                        control_block.block_on(Some(&address));
+                        let result = address.send_until(msg, || control_block.is_interrupted());
+                        control_block.block_on(None);
+                        result
+                    }
+                }
+            }
+        }
+    });
+    if result.is_ok() {
+        caravela_metric!(message_sent(&copy, started.elapsed()));
+    } else if let (Some(trace), Some(sent_at)) = (trace, sent_at) {
+        trace.retract(sent_at, Capture::Sent, &copy);
+    }
+    match &result {
+        // not dropped, the caller sends it again
+        Err(ErrorCode::ChannelFull) if !bury_full => {}
+        Ok(()) => events::emit(|| Event::MessageSent(copy.clone())),
+        Err(error) => {
+            events::emit(|| Event::MessageDropped(copy.clone(), error.into()));
+            if matches!(
+                error,
+                ErrorCode::Disconnected | ErrorCode::ChannelFull | ErrorCode::Expired
+            ) {
+                dead_letter::record(copy, error.into())
+            }
+        }
+    }
+    result
+}
+
 #[derive(Debug)]
 pub(crate) struct Hub {
     rx: Rx,
+    /// Whether the messages of the mailbox stay out of the events, metrics, sniffer, journal and dead letters
+    ///  of the process, as for the agents of a test harness.
+    isolated: bool,
     //deck: DeckAccess, //Arc<RwLock<Deck>>,
     //msg: Option<Message>,
 }
@@ -100,48 +256,110 @@ pub(crate) struct Hub {
 impl Hub {
     pub(crate) fn new(rx: Rx) -> Self {
         //let msg = None;
-        Self { rx }
+        Self {
+            rx,
+            isolated: false,
+        }
         //, msg }
     }
 
+    /// Keep the messages of the mailbox to itself, for agents that run outside of any platform.
+    #[cfg(feature = "testing")]
+    pub(crate) fn isolate(&mut self) {
+        self.isolated = true;
+    }
+
     /*pub(crate) fn msg(&self) -> Message {
         self.msg.clone()
     }*/
 
     pub(crate) fn send(&self, msg: Message, sync: SyncType) -> Result<(), ErrorCode> {
-        caravela_messaging!(
-            "{}: Sending {} to {}",
-            msg.sender(),
-            msg.message_type(),
-            msg.receiver()
-        );
-        //check memberships and roles
-        let address = msg.receiver().address().clone();
-        let result = match sync {
-            SyncType::Blocking => SendResult::Blocking(address.send(msg)),
-            SyncType::NonBlocking => SendResult::NonBlocking(address.try_send(msg)),
-        };
-        match result {
-            SendResult::Blocking(Ok(_)) => Ok(()),
-            SendResult::NonBlocking(Ok(_)) => Ok(()),
-            SendResult::Blocking(Err(SendError(_))) => Err(ErrorCode::Disconnected),
-            SendResult::NonBlocking(Err(error)) => match error {
-                TrySendError::Disconnected(_) => Err(ErrorCode::Disconnected), //LIST MAY BE OUTDATED
-                TrySendError::Full(_) => Err(ErrorCode::ChannelFull),
-            },
-        }
+        dispatch(msg, sync)
+    }
+
+    pub(crate) fn try_send(&self, msg: Message) -> Result<(), ErrorCode> {
+        try_dispatch(msg)
+    }
+
+    pub(crate) fn mailbox_depth(&self) -> usize {
+        self.rx.len()
     }
 
     pub(crate) fn receive(&self) -> Result<Message, ErrorCode> {
+        self.receive_until(|| false)
+    }
+
+    /// Wait for a message, unless `interrupted` holds once the mailbox is interrupted.
+    pub(crate) fn receive_until(
+        &self,
+        interrupted: impl Fn() -> bool,
+    ) -> Result<Message, ErrorCode> {
         //TBD: could use recv_timeout
-        self.rx.recv().map_err(ErrorCode::MpscRecv)
+        let started = Instant::now();
+        loop {
+            let msg = self.rx.recv_until(&interrupted)?;
+            if !self.expired(&msg) {
+                self.delivered(&msg, started.elapsed());
+                return Ok(msg);
+            }
+        }
+    }
+
+    /// Take a message from the mailbox only if there is one waiting.
+    pub(crate) fn try_receive(&self) -> Option<Message> {
+        loop {
+            let msg = self.rx.try_recv()?;
+            if !self.expired(&msg) {
+                self.delivered(&msg, Duration::ZERO);
+                return Some(msg);
+            }
+        }
+    }
+
+    /// Move the message to the dead-letter queue if it expired while waiting in the mailbox.
+    fn expired(&self, msg: &Message) -> bool {
+        if !msg.is_expired() {
+            return false;
+        }
+        if self.isolated {
+            return true;
+        }
+        events::emit(|| Event::MessageDropped(msg.clone(), Reason::Expired));
+        dead_letter::record(msg.clone(), Reason::Expired);
+        true
+    }
+
+    fn delivered(&self, msg: &Message, latency: Duration) {
+        caravela_message!(msg, "Message delivered");
+        if self.isolated {
+            return;
+        }
+        caravela_metric!(message_received(msg, latency));
+        if let Some(trace) = sniffed(msg) {
+            trace.record(Capture::Received, msg.clone());
+        }
+        journal::record(msg);
+        events::emit(|| Event::MessageDelivered(msg.clone()))
+    }
+}
+
+impl Drop for Hub {
+    /// The messages left in the mailbox of a finished agent are kept in the dead-letter queue.
+    fn drop(&mut self) {
+        let left = self.rx.close();
+        if self.isolated {
+            return;
+        }
+        for msg in left {
+            dead_letter::record(msg, Reason::NotRegistered);
+        }
+    }
+}
 
-        //match result {
-        //    Ok(received_msg) => {
-        //self.msg = Some(received_msg);
-        //        Ok(self.msg.message_type())
-        //    }
-        //    Err(err) => Err(ErrorCode::MpscRecv(err)),
-        //}
+/// Get the trace of the sniffer if the message involves a sniffed agent.
+fn sniffed(msg: &Message) -> Option<TraceArc> {
+    if !sniffer::is_active() {
+        return None;
     }
+    deck().view().sniffer_trace(msg.sender(), msg.receiver())
 }
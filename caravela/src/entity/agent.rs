@@ -1,29 +1,47 @@
 /// A collection of traits that give agents their behavior and formally defines them as agents.
 pub mod behavior;
 
+/// Derive `AgentBuild` or `AgentBuildParam` for arbitrary structs and wire methods into [`Behavior`](behavior::Behavior).
+#[cfg(feature = "derive")]
+pub use caravel_derive::{behavior, Agent};
+
 use crate::{
+    dead_letter::{self, DeadLetter, DeadLetterId},
     deck::deck,
     entity::{
         //messaging::{Content, Message, MessageType, RequestType, SyncType},
-        messaging::{ActionType, Content, Message, MessageType, SyncType},
+        messaging::{ActionType, Content, Message, MessagePriority, MessageType, Reason, SyncType},
+        service::organization::OrgRole,
         Description,
         Hub,
     },
-    ErrorCode, Rx, MAX_SUBSCRIBERS,
+    events::{self, Event},
+    executor::{self, SignalArc},
+    platform::pin_current_thread,
+    pool::{self, TaskId, Yield},
+    simulation::{self, Blocked},
+    timer::{self, TimerHandle},
+    ErrorCode, Rx, Tx, MAX_SUBSCRIBERS,
 };
 use std::{
     collections::HashMap,
     fmt::Display,
-    hint,
+    future::{self, Future},
+    pin::pin,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
     },
+    task::{Context, Poll},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use thread_priority::ThreadPriority;
+
+#[cfg(feature = "testing")]
+use crate::testing::MockContextArc;
 
-type ContactList = HashMap<String, Description>;
+type ContactList = HashMap<String, Arc<Description>>;
 
 /// The different states in an Agent Lifecycle.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
@@ -66,63 +84,280 @@ impl From<usize> for AgentState {
     }
 }
 
+/// Snapshot of the state and counters of an agent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AgentStats {
+    /// Nickname of the agent.
+    pub nickname: String,
+    /// Lifecycle state of the agent.
+    pub state: AgentState,
+    /// Priority assigned to the agent.
+    pub priority: u8,
+    /// Stack size of the agent thread.
+    pub stack_size: usize,
+    /// Number of messages waiting in the mailbox of the agent.
+    pub mailbox_depth: usize,
+    /// Number of messages sent by the agent.
+    pub messages_sent: u64,
+    /// Number of messages received by the agent.
+    pub messages_received: u64,
+    /// Number of times [`Behavior::action`](behavior::Behavior::action) has been executed.
+    pub iterations: u64,
+    /// Last error returned by [`Behavior::action`](behavior::Behavior::action), if any.
+    pub last_error: Option<String>,
+    /// Time elapsed since the agent was started. Zero if it has not been started yet.
+    pub uptime: Duration,
+}
+
 #[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    iterations: AtomicU64,
+    last_error: Mutex<Option<String>>,
+}
+
+/// Handshake between the platform starting an agent and the thread of the agent waiting to be started.
+#[derive(Debug, Default)]
+struct StartGate {
+    passed: Mutex<bool>,
+    changed: Condvar,
+}
+
+#[derive(Debug)]
 pub(crate) struct ControlBlock {
     state: AtomicUsize,
+    priority: Mutex<Option<ThreadPriority>>,
+    affinity: Mutex<Option<usize>>,
+    level: AtomicU8,
+    stack_size: usize,
+    started: OnceLock<Instant>,
+    yielded: Mutex<Option<Yield>>,
+    blocked_on: Mutex<Option<Tx>>,
+    gate: StartGate,
+    forwarding: AtomicBool,
+    counters: Counters,
 }
 
 pub(crate) type ControlBlockArc = Arc<ControlBlock>;
 
 impl ControlBlock {
+    pub(crate) fn new(priority: u8, stack_size: usize) -> Self {
+        Self {
+            state: AtomicUsize::default(),
+            priority: Mutex::default(),
+            affinity: Mutex::default(),
+            level: AtomicU8::new(priority),
+            stack_size,
+            started: OnceLock::new(),
+            yielded: Mutex::default(),
+            blocked_on: Mutex::default(),
+            gate: StartGate::default(),
+            forwarding: AtomicBool::new(false),
+            counters: Counters::default(),
+        }
+    }
     pub(crate) fn agent_state(&self) -> AgentState {
         self.state.load(Ordering::Relaxed).into()
     }
     fn set_state(&self, state: AgentState) {
         self.state.store(state as usize, Ordering::Relaxed);
     }
+    /// Move to `target` only from one of the `from` states, atomically so it cannot race with the agent.
+    fn transition(&self, from: &[AgentState], target: AgentState) -> Result<(), ErrorCode> {
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                from.contains(&current.into()).then_some(target as usize)
+            })
+            .map(|_| ())
+            .map_err(|current| ErrorCode::InvalidStateChange(current.into(), target))
+    }
     pub(crate) fn quit(&self) -> Result<(), ErrorCode> {
-        let current = self.agent_state();
-        let target = AgentState::Terminated;
-        current
-            .eq(&AgentState::Active)
-            .then(|| self.set_state(target))
-            .ok_or(ErrorCode::InvalidStateChange(current, target))
+        self.transition(
+            &[AgentState::Active, AgentState::Waiting],
+            AgentState::Terminated,
+        )
     }
     pub(crate) fn suspend(&self) -> Result<(), ErrorCode> {
-        let current = self.agent_state();
-        let target = AgentState::Suspended;
-        current
-            .eq(&AgentState::Active)
-            .then(|| self.set_state(target))
-            .ok_or(ErrorCode::InvalidStateChange(current, target))
+        self.transition(
+            &[AgentState::Active, AgentState::Waiting],
+            AgentState::Suspended,
+        )
+    }
+    pub(crate) fn wait(&self) -> Result<(), ErrorCode> {
+        self.transition(&[AgentState::Active], AgentState::Waiting)
+    }
+    fn end_wait(&self) -> Result<(), ErrorCode> {
+        self.transition(&[AgentState::Waiting], AgentState::Active)
+    }
+    /// Whether a lifecycle command asks the agent to stop blocking.
+    pub(crate) fn is_interrupted(&self) -> bool {
+        matches!(
+            self.agent_state(),
+            AgentState::Suspended | AgentState::Terminated
+        )
+    }
+    /// Remember the mailbox the agent is blocked sending to, if any, so a lifecycle command can interrupt it.
+    pub(crate) fn block_on(&self, address: Option<&Tx>) {
+        *self.blocked_on.lock().expect("Control block is poisoned") = address.cloned();
+    }
+    /// Wake the agent if it is blocked sending a message.
+    pub(crate) fn interrupt(&self) {
+        if let Some(address) = self
+            .blocked_on
+            .lock()
+            .expect("Control block is poisoned")
+            .as_ref()
+        {
+            address.interrupt();
+        }
+    }
+    pub(crate) fn priority_level(&self) -> u8 {
+        self.level.load(Ordering::Relaxed)
+    }
+    pub(crate) fn set_priority(&self, level: u8, priority: ThreadPriority) {
+        self.level.store(level, Ordering::Relaxed);
+        *self.priority.lock().expect("Control block is poisoned") = Some(priority);
+    }
+    fn take_priority(&self) -> Option<ThreadPriority> {
+        self.priority
+            .lock()
+            .expect("Control block is poisoned")
+            .take()
+    }
+    pub(crate) fn set_affinity(&self, core: usize) {
+        *self.affinity.lock().expect("Control block is poisoned") = Some(core);
+    }
+    pub(crate) fn set_forwarding(&self, enabled: bool) {
+        self.forwarding.store(enabled, Ordering::Relaxed);
+    }
+    fn is_forwarding(&self) -> bool {
+        self.forwarding.load(Ordering::Relaxed)
+    }
+    fn take_affinity(&self) -> Option<usize> {
+        self.affinity
+            .lock()
+            .expect("Control block is poisoned")
+            .take()
+    }
+    fn yield_to(&self, reason: Yield) {
+        *self.yielded.lock().expect("Control block is poisoned") = Some(reason);
     }
-    pub(crate) fn wait(&self) {
-        self.set_state(AgentState::Waiting);
+    pub(crate) fn take_yield(&self) -> Option<Yield> {
+        self.yielded
+            .lock()
+            .expect("Control block is poisoned")
+            .take()
     }
     pub(crate) fn active(&self) -> Result<(), ErrorCode> {
         let current = self.agent_state();
         let target = AgentState::Active;
         { current.ne(&AgentState::Active) && current.ne(&AgentState::Terminated) }
-            .then(|| self.set_state(target))
+            .then(|| {
+                self.started.get_or_init(Instant::now);
+                // changed with the gate locked, so a thread about to wait on it is not missed
+                let _passed = self.gate.passed.lock().expect("Start gate is poisoned");
+                self.set_state(target);
+                self.gate.changed.notify_all();
+            })
             .ok_or(ErrorCode::InvalidStateChange(current, target))
     }
+    /// Block the thread of the agent until it is started, then let the platform know it is running.
+    pub(crate) fn pass_gate(&self) {
+        let mut passed = self
+            .gate
+            .changed
+            .wait_while(
+                self.gate.passed.lock().expect("Start gate is poisoned"),
+                |_| self.agent_state() == AgentState::Initiated,
+            )
+            .expect("Start gate is poisoned");
+        *passed = true;
+        self.gate.changed.notify_all();
+    }
+    /// Wait for the thread of the agent to pass the start gate, for up to `timeout`.
+    pub(crate) fn await_gate(&self, timeout: Duration) -> Result<(), ErrorCode> {
+        let (passed, _) = self
+            .gate
+            .changed
+            .wait_timeout_while(
+                self.gate.passed.lock().expect("Start gate is poisoned"),
+                timeout,
+                |passed| !*passed,
+            )
+            .expect("Start gate is poisoned");
+        (*passed).then_some(()).ok_or(ErrorCode::Timeout)
+    }
+    fn count_sent(&self) {
+        self.counters.sent.fetch_add(1, Ordering::Relaxed);
+    }
+    fn count_received(&self) {
+        self.counters.received.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn count_iteration(&self, result: &Result<(), ErrorCode>) {
+        self.counters.iterations.fetch_add(1, Ordering::Relaxed);
+        if let Err(error) = result {
+            *self
+                .counters
+                .last_error
+                .lock()
+                .expect("Control block is poisoned") = Some(error.to_string());
+        }
+    }
+    /// Gather the counters of the agent, the mailbox depth is provided by the caller.
+    pub(crate) fn stats(&self, nickname: &str, mailbox_depth: usize) -> AgentStats {
+        AgentStats {
+            nickname: nickname.to_string(),
+            state: self.agent_state(),
+            priority: self.priority_level(),
+            stack_size: self.stack_size,
+            mailbox_depth,
+            messages_sent: self.counters.sent.load(Ordering::Relaxed),
+            messages_received: self.counters.received.load(Ordering::Relaxed),
+            iterations: self.counters.iterations.load(Ordering::Relaxed),
+            last_error: self
+                .counters
+                .last_error
+                .lock()
+                .expect("Control block is poisoned")
+                .clone(),
+            uptime: self.started.get().map(Instant::elapsed).unwrap_or_default(),
+        }
+    }
+}
+
+/// Where an agent runs, which decides how it waits for messages and time.
+#[derive(Debug)]
+enum Host {
+    /// A thread of its own, which identifies the agent. Its description is kept once looked up.
+    Thread(OnceLock<Arc<Description>>),
+    /// The worker pool, where the agent yields instead of blocking.
+    Pool(Arc<Description>, TaskId),
+    /// An executor, where the agent awaits instead of blocking.
+    Executor(Arc<Description>, SignalArc),
 }
 
 /// The base agent type with AID, life cycle control, and messaging functionality.
 #[derive(Debug)]
 pub struct Agent {
-    nickname: &'static str,
-    hap: &'static str,
+    nickname: Arc<str>,
+    hap: Arc<str>,
     hub: Hub,
     directory: ContactList,
     control_block: ControlBlockArc,
+    host: Host,
+    awaiting: Mutex<Vec<(Description, TimerHandle)>>,
+    /// The last message received by the current action, answered if its content is not understood.
+    received: Mutex<Option<Message>>,
+    #[cfg(feature = "testing")]
+    mock: Option<MockContextArc>,
     //pub membership,
 }
 
 impl Agent {
     pub(crate) fn new(
-        nickname: &'static str,
-        hap: &'static str,
+        nickname: Arc<str>,
+        hap: Arc<str>,
         rx: Rx,
         control_block: ControlBlockArc,
     ) -> Self {
@@ -134,35 +369,160 @@ impl Agent {
             hub,
             directory,
             control_block,
+            host: Host::Thread(OnceLock::new()),
+            awaiting: Mutex::default(),
+            received: Mutex::default(),
+            #[cfg(feature = "testing")]
+            mock: None,
         }
     }
+
+    /// Build an agent that is multiplexed onto the worker pool with other lightweight agents.
+    ///  It has no thread of its own to be identified by, so it keeps its [`Description`].
+    pub(crate) fn new_pooled(
+        aid: Description,
+        id: TaskId,
+        rx: Rx,
+        control_block: ControlBlockArc,
+    ) -> Self {
+        Self {
+            host: Host::Pool(Arc::new(aid.clone()), id),
+            ..Self::new(aid.nickname.clone(), aid.hap.clone(), rx, control_block)
+        }
+    }
+
+    /// Build an agent whose behavior is a future run by the executor, woken through `signal`.
+    pub(crate) fn new_async(
+        aid: Description,
+        rx: Rx,
+        control_block: ControlBlockArc,
+        signal: SignalArc,
+    ) -> Self {
+        Self {
+            host: Host::Executor(Arc::new(aid.clone()), signal),
+            ..Self::new(aid.nickname.clone(), aid.hap.clone(), rx, control_block)
+        }
+    }
+
+    /// Build an agent that runs within the mock context of a test harness instead of a platform.
+    #[cfg(feature = "testing")]
+    pub(crate) fn new_mock(
+        nickname: Arc<str>,
+        hap: Arc<str>,
+        rx: Rx,
+        control_block: ControlBlockArc,
+        mock: MockContextArc,
+    ) -> Self {
+        let mut agent = Self {
+            mock: Some(mock),
+            ..Self::new(nickname, hap, rx, control_block)
+        };
+        agent.hub.isolate();
+        agent
+    }
     /// Get the Agent's name as the formated string `nickname@hap`.
     pub fn name(&self) -> String {
         format!("{}@{}", self.nickname, self.hap)
     }
     /// Get the Agent Identifier Description (AID) of the agent as [`Description`].
     pub fn aid(&self) -> Result<Description, ErrorCode> {
-        deck().read().get_aid_from_thread(thread::current().id())
+        self.own_aid().map(Arc::unwrap_or_clone)
+    }
+
+    /// The description of the agent as shared by every message it sends.
+    fn own_aid(&self) -> Result<Arc<Description>, ErrorCode> {
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            return Ok(Arc::new(mock.aid().clone()));
+        }
+        match &self.host {
+            Host::Pool(aid, _) | Host::Executor(aid, _) => Ok(aid.clone()),
+            Host::Thread(own) => match own.get() {
+                Some(aid) => Ok(aid.clone()),
+                None => {
+                    let aid = deck().view().get_aid_from_thread(thread::current().id())?;
+                    Ok(own.get_or_init(|| Arc::new(aid)).clone())
+                }
+            },
+        }
     }
 
     /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent.
     /// The receiver shall be addressed by its nickname, if a [`Description`] is to be used, employ [`self.send_to_aid`] instead.
+    ///  The content may be given as an `Arc<Content>` already shared with other messages, which is not copied.
+    ///  A lightweight agent does not block while the mailbox of the receiver is full, it gets [`ErrorCode::WouldBlock`]
+    ///  and its step is run again once a message is taken from that mailbox.
     //TBD: add block/nonblock parameter
     pub fn send_to(
         &self,
         nickname: &str,
         message_type: MessageType,
-        content: Content,
+        content: impl Into<Arc<Content>>,
         //content: String,
     ) -> Result<(), ErrorCode> {
-        let agent_aid = if let Some(agent_aid) = self.directory.get(nickname) {
-            agent_aid.to_owned()
-        } else {
-            //only looking for local agents
-            let name = self.fmt_local_agent(nickname);
-            deck().read().get_aid_from_name(&name)?
+        let agent_aid = self.contact(nickname)?;
+        self.send(Message::new(
+            self.own_aid()?,
+            agent_aid,
+            message_type,
+            content,
+        ))
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its nickname,
+    ///  waiting asynchronously instead of blocking while the mailbox of the receiver is full.
+    ///  Agents that are not asynchronous send as in [`send_to`](Self::send_to).
+    pub async fn send_to_async(
+        &self,
+        nickname: &str,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<(), ErrorCode> {
+        let agent_aid = self.contact(nickname)?;
+        let msg = Message::new(self.own_aid()?, agent_aid, message_type, content);
+        self.send_async(msg).await
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its [`Description`],
+    ///  waiting asynchronously instead of blocking while its mailbox is full.
+    pub async fn send_to_aid_async(
+        &self,
+        aid: Description,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<(), ErrorCode> {
+        let msg = Message::new(self.own_aid()?, aid, message_type, content);
+        self.send_async(msg).await
+    }
+
+    async fn send_async(&self, msg: Message) -> Result<(), ErrorCode> {
+        let Host::Executor(_, signal) = &self.host else {
+            return self.send(msg);
         };
-        self.send_to_aid(agent_aid, message_type, content)
+        loop {
+            future::poll_fn(|cx| {
+                if self.control_block.is_interrupted() {
+                    return Poll::Ready(Err(ErrorCode::Interrupted));
+                }
+                signal.register(cx.waker());
+                msg.receiver().address().poll_room(cx).map(Ok)
+            })
+            .await?;
+            match self.hub.try_send(msg.clone()) {
+                // another sender took the room first
+                Err(ErrorCode::ChannelFull) => continue,
+                result => return result.inspect(|_| self.control_block.count_sent()),
+            }
+        }
+    }
+
+    /// Look up a contact by its nickname, or else a local agent.
+    fn contact(&self, nickname: &str) -> Result<Arc<Description>, ErrorCode> {
+        match self.directory.get(nickname) {
+            Some(agent_aid) => Ok(agent_aid.clone()),
+            //only looking for local agents
+            None => self.get_aid_from_nickname(nickname).map(Arc::new),
+        }
     }
 
     /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent.
@@ -171,41 +531,493 @@ impl Agent {
         &self,
         aid: Description,
         message_type: MessageType,
-        content: Content,
+        content: impl Into<Arc<Content>>,
         //content: String,
     ) -> Result<(), ErrorCode> {
-        let msg = Message::new(self.aid()?, aid, message_type, content);
-        self.hub.send(msg, SyncType::Blocking)
+        let msg = Message::new(self.own_aid()?, aid, message_type, content);
+        self.send(msg)
     }
 
     /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to all the agents in the contact list.
+    ///  Every message shares the same content.
     pub fn send_to_all(
         &self,
         message_type: MessageType,
-        content: Content,
+        content: impl Into<Arc<Content>>,
         //content: String,
     ) -> Result<(), ErrorCode> {
-        let agents = self.directory.values();
-        for aid in agents {
-            self.send_to_aid(aid.clone(), message_type.clone(), content.clone())?;
+        let sender = self.own_aid()?;
+        let content = content.into();
+        for aid in self.directory.values() {
+            let msg = Message::new(
+                sender.clone(),
+                aid.clone(),
+                message_type.clone(),
+                content.clone(),
+            );
+            self.send(msg)?;
         }
         Ok(())
     }
 
-    /// Wait for a [`Message`] to arrive. This operation blocks the agent.
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the members of an organization,
+    ///  or only to those with the given [`OrgRole`]. The agent must be a member allowed to address the whole organization.
+    ///  Every message shares the same content.
+    pub fn send_to_org(
+        &self,
+        org: &str,
+        role: Option<OrgRole>,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<(), ErrorCode> {
+        #[cfg(feature = "testing")]
+        if self.mock.is_some() {
+            // organizations only exist within a platform
+            return Err(ErrorCode::NotFound);
+        }
+        let sender = self.own_aid()?;
+        let content = content.into();
+        let recipients = deck().view().get_org(org)?.recipients(&sender, role)?;
+        for aid in recipients {
+            let msg = Message::new(sender.clone(), aid, message_type.clone(), content.clone());
+            self.send(msg)?;
+        }
+        Ok(())
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its nickname,
+    ///  in the lane of its mailbox for the given [`MessagePriority`]. The control lane is reserved for the AMS.
+    pub fn send_to_with_priority(
+        &self,
+        nickname: &str,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+        priority: MessagePriority,
+    ) -> Result<(), ErrorCode> {
+        let agent_aid = self.contact(nickname)?;
+        let msg = Message::new(self.own_aid()?, agent_aid, message_type, content);
+        self.send(msg.with_priority(check_priority(priority)?))
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its [`Description`],
+    ///  in the lane of its mailbox for the given [`MessagePriority`].
+    pub fn send_to_aid_with_priority(
+        &self,
+        aid: Description,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+        priority: MessagePriority,
+    ) -> Result<(), ErrorCode> {
+        let msg = Message::new(self.own_aid()?, aid, message_type, content);
+        self.send(msg.with_priority(check_priority(priority)?))
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its nickname,
+    ///  which is not delivered once `ttl` has passed. An expired message is kept in the dead-letter queue instead.
+    pub fn send_to_with_ttl(
+        &self,
+        nickname: &str,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+        ttl: Duration,
+    ) -> Result<(), ErrorCode> {
+        let agent_aid = self.contact(nickname)?;
+        let msg = Message::new(self.own_aid()?, agent_aid, message_type, content);
+        self.send(msg.with_ttl(ttl))
+    }
+
+    /// Send a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent addressed by its [`Description`],
+    ///  which is not delivered once `ttl` has passed.
+    pub fn send_to_aid_with_ttl(
+        &self,
+        aid: Description,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+        ttl: Duration,
+    ) -> Result<(), ErrorCode> {
+        let msg = Message::new(self.own_aid()?, aid, message_type, content);
+        self.send(msg.with_ttl(ttl))
+    }
+
+    /// Get the messages sent by the agent that could not be delivered, oldest first.
+    pub fn dead_letters(&self) -> Result<Vec<DeadLetter>, ErrorCode> {
+        let own = self.own_aid()?;
+        Ok(dead_letter::list(|letter| {
+            letter.message().sender() == &*own
+        }))
+    }
+
+    /// Send again one of the messages of the agent that could not be delivered,
+    ///  to the agent currently registered under the name of its receiver.
+    pub fn redeliver(&self, id: DeadLetterId) -> Result<(), ErrorCode> {
+        let own = self.own_aid()?;
+        dead_letter::redeliver(id, |letter| letter.message().sender() == &*own)
+    }
+
+    /// Post a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent once `delay` has passed.
+    ///  The timer is cancelled by its handle, or when either agent leaves the platform.
+    ///  Posting does not block, so the message is dropped if the mailbox of the receiver is full.
+    pub fn send_after(
+        &self,
+        aid: Description,
+        delay: Duration,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<TimerHandle, ErrorCode> {
+        self.check_timers()?;
+        let own = self.own_aid()?;
+        let msg = Message::new(own.clone(), aid, message_type, content);
+        Ok(timer::start(Arc::unwrap_or_clone(own), msg, delay, None))
+    }
+
+    /// Post a [`Message`] with the desired [`MessageType`] and [`Content`] to the target agent every `period`,
+    ///  the first one after a whole period. Every posted message shares the same content.
+    ///  A zero period is an [`ErrorCode::InvalidRequest`].
+    pub fn send_every(
+        &self,
+        aid: Description,
+        period: Duration,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<TimerHandle, ErrorCode> {
+        self.check_timers()?;
+        if period.is_zero() {
+            return Err(ErrorCode::InvalidRequest(
+                "a repeating timer needs a period".to_string(),
+            ));
+        }
+        let own = self.own_aid()?;
+        let msg = Message::new(own.clone(), aid, message_type, content);
+        Ok(timer::start(
+            Arc::unwrap_or_clone(own),
+            msg,
+            period,
+            Some(period),
+        ))
+    }
+
+    /// Cancel a timer started by the agent. It is [`ErrorCode::NotFound`] once a one-shot timer has fired.
+    ///  Cancelling the deadline of a reply leaves the agent waiting for no reply from that target.
+    pub fn cancel_timer(&self, handle: TimerHandle) -> Result<(), ErrorCode> {
+        timer::cancel(&*self.own_aid()?, handle)?;
+        self.awaiting
+            .lock()
+            .expect("Agent is poisoned")
+            .retain(|&(_, x)| x != handle);
+        Ok(())
+    }
+
+    /// Send a [`Message`] to the target agent, expecting any message back from it within `timeout`.
+    ///  Otherwise the agent receives a [`MessageType::Failure`] from itself, whose content is the
+    ///  [`Reason::NoReply`] of the target. The returned handle cancels the deadline, which is also done
+    ///  on receiving the first message from the target.
+    pub fn send_to_aid_with_reply_by(
+        &self,
+        aid: Description,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+        timeout: Duration,
+    ) -> Result<TimerHandle, ErrorCode> {
+        self.check_timers()?;
+        let own = self.own_aid()?;
+        let msg = Message::new(own.clone(), aid.clone(), message_type, content);
+        self.send(msg.with_reply_by(Instant::now() + timeout))?;
+        let notice = Message::new(
+            own.clone(),
+            own.clone(),
+            MessageType::Failure,
+            Content::Reason(ActionType::Other("reply_by"), Reason::NoReply(aid.clone())),
+        );
+        let handle = timer::start(Arc::unwrap_or_clone(own), notice, timeout, None);
+        self.awaiting
+            .lock()
+            .expect("Agent is poisoned")
+            .push((aid, handle));
+        Ok(handle)
+    }
+
+    /// Timers are run by a thread of their own, which neither a simulation nor a mock context can drive.
+    fn check_timers(&self) -> Result<(), ErrorCode> {
+        #[cfg(feature = "testing")]
+        if self.mock.is_some() {
+            return Err(ErrorCode::InvalidRequest(
+                "timers are not available in a mock context".to_string(),
+            ));
+        }
+        if simulation::is_active() {
+            return Err(ErrorCode::InvalidRequest(
+                "timers are not available under simulation".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Answer a received [`Message`], only with a [`MessageType`] that is a valid reply to it as per [`MessageType::is_reply_to`].
+    ///  Otherwise nothing is sent and the result is [`ErrorCode::InvalidMessageType`].
+    pub fn reply(
+        &self,
+        to: &Message,
+        message_type: MessageType,
+        content: impl Into<Arc<Content>>,
+    ) -> Result<(), ErrorCode> {
+        if !message_type.is_reply_to(to.message_type()) {
+            return Err(ErrorCode::InvalidMessageType);
+        }
+        self.send_to_aid(to.sender().clone(), message_type, content)
+    }
+
+    /// Handle a message taken from the mailbox before the behavior gets it.
+    fn accept(&self, msg: Message) -> Message {
+        self.replied(&msg);
+        *self.received.lock().expect("Agent is poisoned") = Some(msg.clone());
+        if self.control_block.is_forwarding()
+            && matches!(
+                msg.message_type(),
+                MessageType::Proxy | MessageType::Propagate
+            )
+        {
+            return self.forward(msg);
+        }
+        msg
+    }
+
+    /// Send the message embedded in a [`MessageType::Proxy`] or [`MessageType::Propagate`] on to its targets,
+    ///  answering the requester with a single [`MessageType::Failure`] if some of them could not be reached,
+    ///  which gives the reason for each of those targets when there are several.
+    ///  A propagated message is then received by the agent itself, as sent by the requester.
+    fn forward(&self, msg: Message) -> Message {
+        let Content::Forward(targets, message_type, content) = msg.content() else {
+            let _ = self.reply(&msg, MessageType::NotUnderstood, msg.shared_content());
+            return msg;
+        };
+        let Ok(own) = self.own_aid() else {
+            return msg;
+        };
+        let failed: Vec<(&Description, ErrorCode)> = targets
+            .iter()
+            .filter_map(|target| {
+                let forwarded = Message::new(
+                    own.clone(),
+                    target.clone(),
+                    message_type.clone(),
+                    content.clone(),
+                );
+                self.send_now(forwarded).err().map(|error| (target, error))
+            })
+            .collect();
+        let reason = match failed.as_slice() {
+            [] => None,
+            [(_, error)] => Some(Reason::from(error)),
+            several => Some(Reason::Other(
+                several
+                    .iter()
+                    .map(|(target, error)| format!("{}: {}", target, Reason::from(error)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        };
+        if let Some(reason) = reason {
+            let failure = Message::new(
+                own.clone(),
+                msg.sender().clone(),
+                MessageType::Failure,
+                Content::Reason(ActionType::Other("forward"), reason),
+            );
+            let _ = self.send_now(failure);
+        }
+        if *msg.message_type() == MessageType::Proxy {
+            return msg;
+        }
+        Message::new(
+            msg.sender().clone(),
+            own,
+            message_type.clone(),
+            content.clone(),
+        )
+    }
+
+    /// Answer the last message received by the action with [`MessageType::NotUnderstood`]
+    ///  if the action failed with [`ErrorCode::InvalidContent`], such as from [`Message::parse_content`].
+    pub(crate) fn answer_not_understood(&self, res: &Result<(), ErrorCode>) {
+        let received = self.received.lock().expect("Agent is poisoned").take();
+        let (Err(ErrorCode::InvalidContent(_)), Some(msg)) = (res, received) else {
+            return;
+        };
+        if *msg.message_type() != MessageType::NotUnderstood && msg.sender() != msg.receiver() {
+            caravela_messaging!("{}: not understood from {}", self.name(), msg.sender());
+            let _ = self.reply(&msg, MessageType::NotUnderstood, msg.shared_content());
+        }
+    }
+
+    /// Settle the oldest reply deadline on the sender of `msg`, or the one whose notice `msg` is.
+    fn replied(&self, msg: &Message) {
+        let mut awaiting = self.awaiting.lock().expect("Agent is poisoned");
+        if awaiting.is_empty() {
+            return;
+        }
+        let peer = match (msg.message_type(), msg.content()) {
+            (MessageType::Failure, Content::Reason(_, Reason::NoReply(peer)))
+                if msg.sender() == msg.receiver() =>
+            {
+                peer
+            }
+            _ => msg.sender(),
+        };
+        if let Some(index) = awaiting.iter().position(|(aid, _)| aid == peer) {
+            let (_, handle) = awaiting.remove(index);
+            let _ = timer::cancel(msg.receiver(), handle);
+        }
+    }
+
+    /// Wait for a [`Message`] to arrive. This operation blocks the agent,
+    ///  unless it is suspended or terminated in the meantime, in which case it returns [`ErrorCode::Interrupted`].
+    ///  A lightweight agent does not block, it gets [`ErrorCode::WouldBlock`] when its mailbox is empty
+    ///  and its step is run again once a message arrives.
+    ///  An asynchronous agent blocks its executor, it should use [`receive_async`](Self::receive_async) instead.
     pub fn receive(&self) -> Result<Message, ErrorCode> {
         caravela_messaging!("{}: waiting for message", self.name());
-        self.hub.receive().map(|x| {
+        #[cfg(feature = "testing")]
+        if self.mock.is_some() {
+            // nothing else can fill the mailbox, so waiting would never end
+            return self
+                .hub
+                .try_receive()
+                .inspect(|_| self.control_block.count_received())
+                .ok_or(ErrorCode::MpscRecv(std::sync::mpsc::RecvError));
+        }
+        let received = if let Host::Pool(..) = self.host {
+            self.hub.try_receive().ok_or_else(|| {
+                self.control_block.yield_to(Yield::Receive);
+                ErrorCode::WouldBlock
+            })
+        } else if simulation::is_active() {
+            loop {
+                if let Some(msg) = self.hub.try_receive() {
+                    break Ok(msg);
+                }
+                simulation::block(Blocked::Receive);
+            }
+        } else {
+            self.hub
+                .receive_until(|| self.control_block.is_interrupted())
+        };
+        received.map(|msg| {
+            self.control_block.count_received();
             caravela_messaging!("{}: message received!", self.name());
-            x
+            self.accept(msg)
         })
     }
 
+    /// Wait asynchronously for a [`Message`] to arrive, without blocking the executor.
+    ///  Agents that are not asynchronous receive as in [`receive`](Self::receive).
+    pub async fn receive_async(&self) -> Result<Message, ErrorCode> {
+        let Host::Executor(_, signal) = &self.host else {
+            return self.receive();
+        };
+        caravela_messaging!("{}: waiting for message", self.name());
+        let poll = || {
+            if let Some(msg) = self.hub.try_receive() {
+                Poll::Ready(Ok(msg))
+            } else if self.control_block.is_interrupted() {
+                Poll::Ready(Err(ErrorCode::Interrupted))
+            } else {
+                Poll::Pending
+            }
+        };
+        let msg = future::poll_fn(|cx| match poll() {
+            Poll::Pending => {
+                signal.register(cx.waker());
+                poll()
+            }
+            ready => ready,
+        })
+        .await?;
+        self.control_block.count_received();
+        caravela_messaging!("{}: message received!", self.name());
+        Ok(self.accept(msg))
+    }
+
+    /// Wait asynchronously until the state of the agent satisfies `ready`, woken whenever the platform changes it.
+    pub(crate) async fn until_state(&self, ready: impl Fn(AgentState) -> bool) {
+        let Host::Executor(_, signal) = &self.host else {
+            return;
+        };
+        future::poll_fn(|cx| {
+            if ready(self.control_block.agent_state()) {
+                return Poll::Ready(());
+            }
+            signal.register(cx.waker());
+            if ready(self.control_block.agent_state()) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Get a snapshot of the state and counters of the agent.
+    pub fn stats(&self) -> AgentStats {
+        self.control_block
+            .stats(&self.nickname, self.hub.mailbox_depth())
+    }
+
+    /// Report that the agent missed one of its deadlines, counted by the metrics exporter.
+    pub fn deadline_missed(&self) {
+        caravela_metric!(deadline_missed(&self.name()));
+    }
+
+    fn send(&self, msg: Message) -> Result<(), ErrorCode> {
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            return mock.send(msg).inspect(|_| self.control_block.count_sent());
+        }
+        if let Host::Pool(_, id) = self.host {
+            return self.send_or_yield(msg, id);
+        }
+        self.hub
+            .send(msg, SyncType::Interruptible(&self.control_block))
+            .inspect(|_| self.control_block.count_sent())
+    }
+
+    /// Send while a received message is being accepted, which a lightweight or asynchronous agent would lose
+    ///  by yielding or blocking its host, so a full mailbox fails with [`ErrorCode::ChannelFull`] instead.
+    fn send_now(&self, msg: Message) -> Result<(), ErrorCode> {
+        #[cfg(feature = "testing")]
+        if self.mock.is_some() {
+            return self.send(msg);
+        }
+        match self.host {
+            Host::Thread(_) => self.send(msg),
+            Host::Pool(..) | Host::Executor(..) => self
+                .hub
+                .try_send(msg)
+                .inspect(|_| self.control_block.count_sent()),
+        }
+    }
+
+    /// Send without blocking the worker of a lightweight agent, which yields while the mailbox of the receiver is full
+    ///  and is woken once a message is taken from it.
+    fn send_or_yield(&self, msg: Message, id: TaskId) -> Result<(), ErrorCode> {
+        let waker = pool::room_waker(id);
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match self.hub.try_send(msg.clone()) {
+                Err(ErrorCode::ChannelFull) => (),
+                result => return result.inspect(|_| self.control_block.count_sent()),
+            }
+            // room may have been made since the attempt, in which case the waker is not kept
+            if msg.receiver().address().poll_room(&mut cx).is_pending() {
+                self.control_block.yield_to(Yield::Send);
+                return Err(ErrorCode::WouldBlock);
+            }
+        }
+    }
+
     /// Add an agent to the contact list. The target agent needs to be addressed by its nickname.
     pub fn add_contact(&mut self, nickname: &str) -> Result<(), ErrorCode> {
         //only looking for local agents
-        let name = self.fmt_local_agent(nickname);
-        let agent = deck().read().get_aid_from_name(&name)?;
+        let agent = self.get_aid_from_nickname(nickname)?;
         self.add_contact_aid(nickname, agent)
     }
 
@@ -220,38 +1032,159 @@ impl Agent {
         } else if self.directory.contains_key(nickname) {
             Err(ErrorCode::Duplicated)
         } else {
-            self.directory.insert(nickname.to_string(), description);
+            self.directory
+                .insert(nickname.to_string(), Arc::new(description));
             Ok(())
         }
     }
 
+    fn get_aid_from_nickname(&self, nickname: &str) -> Result<Description, ErrorCode> {
+        let name = self.fmt_local_agent(nickname);
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            return mock.get_aid_from_name(&name);
+        }
+        deck().view().get_aid_from_name(&name)
+    }
+
     pub(crate) fn fmt_local_agent(&self, nickname: &str) -> String {
         format!("{nickname}@{}", self.hap)
     }
 
     pub(crate) fn init(&self) {
-        while self.control_block.agent_state() == AgentState::Initiated {
-            hint::spin_loop()
+        if simulation::is_active() {
+            simulation::block(Blocked::Start);
+        }
+        self.control_block.pass_gate();
+    }
+
+    /// Hand control back to the simulation at the end of every step, if there is one.
+    pub(crate) fn end_step(&self) {
+        if simulation::is_active() {
+            simulation::block(Blocked::Ready);
         }
     }
 
     /// Halt the agent's operation for a specified duration of time in milliseconds.
-    pub fn wait(&self, time: u64) {
+    ///  It returns [`ErrorCode::Interrupted`] early if the agent is suspended or terminated in the meantime.
+    ///  A lightweight agent does not halt, its next step is only run once the time has passed.
+    ///  An asynchronous agent blocks its executor, it should use [`wait_async`](Self::wait_async) instead.
+    pub fn wait(&self, time: u64) -> Result<(), ErrorCode> {
         let dur = Duration::from_millis(time); //TBD could remove
-        self.control_block.wait();
+        self.begin_wait()?;
+        if let Host::Pool(..) = self.host {
+            self.control_block
+                .yield_to(Yield::Sleep(Instant::now() + dur));
+            return Ok(());
+        }
+        self.sleep(dur);
+        self.end_wait()
+    }
+
+    /// Halt the agent asynchronously for a specified duration of time in milliseconds, without blocking the executor.
+    ///  Agents that are not asynchronous wait as in [`wait`](Self::wait).
+    pub async fn wait_async(&self, time: u64) -> Result<(), ErrorCode> {
+        let Host::Executor(_, signal) = &self.host else {
+            return self.wait(time);
+        };
+        self.begin_wait()?;
+        let mut sleep = pin!(executor::sleep(Duration::from_millis(time)));
+        future::poll_fn(|cx| {
+            signal.register(cx.waker());
+            if self.control_block.agent_state() != AgentState::Waiting {
+                return Poll::Ready(());
+            }
+            sleep.as_mut().poll(cx)
+        })
+        .await;
+        self.end_wait()
+    }
+
+    fn begin_wait(&self) -> Result<(), ErrorCode> {
+        self.control_block
+            .wait()
+            .map_err(|_| ErrorCode::Interrupted)?;
+        self.emit(|aid| Event::StateChanged(aid, AgentState::Active, AgentState::Waiting));
         caravela_status!("{}: Waiting", self.name());
-        thread::park_timeout(dur);
-        caravela_status!("{}: Resuming", self.name());
-        if self.control_block.agent_state().ne(&AgentState::Active) {
-            let _ = self.control_block.active();
+        Ok(())
+    }
+
+    /// Bring the agent back to the active state once it is done waiting,
+    ///  unless a lifecycle command changed its state in the meantime.
+    pub(crate) fn end_wait(&self) -> Result<(), ErrorCode> {
+        match self.control_block.end_wait() {
+            Ok(()) => {
+                caravela_status!("{}: Resuming", self.name());
+                self.emit(|aid| Event::StateChanged(aid, AgentState::Waiting, AgentState::Active));
+                Ok(())
+            }
+            // resumed early
+            Err(ErrorCode::InvalidStateChange(AgentState::Active, _)) => Ok(()),
+            Err(_) => Err(ErrorCode::Interrupted),
+        }
+    }
+
+    fn sleep(&self, duration: Duration) {
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            return mock.wait(duration);
+        }
+        if simulation::is_active() {
+            simulation::sleep(duration);
+            return;
+        }
+        // parking may end early, so the time and the state are checked again
+        let until = Instant::now() + duration;
+        while self.control_block.agent_state() == AgentState::Waiting {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            thread::park_timeout(until - now);
         }
     }
 
     pub(crate) fn suspend(&self) {
         if self.control_block.agent_state().eq(&AgentState::Suspended) {
             caravela_status!("{}: Suspending", self.name());
-            thread::park();
+            self.emit(Event::AgentSuspended);
+            // parking may end early, so the state is checked again
+            while self.control_block.agent_state().eq(&AgentState::Suspended) {
+                if simulation::is_active() {
+                    simulation::block(Blocked::Suspended);
+                } else {
+                    thread::park();
+                }
+            }
             caravela_status!("{}: Resuming", self.name());
+            self.emit(Event::AgentResumed);
+        }
+    }
+
+    /// Publish an event about this agent, only looking up its AID if there are subscribers.
+    fn emit(&self, event: impl FnOnce(Description) -> Event) {
+        if events::is_active() {
+            if let Ok(aid) = self.aid() {
+                events::emit(|| event(aid));
+            }
+        }
+    }
+
+    pub(crate) fn count_iteration(&self, result: &Result<(), ErrorCode>) {
+        self.control_block.count_iteration(result);
+    }
+
+    pub(crate) fn update_priority(&self) {
+        if let Some(priority) = self.control_block.take_priority() {
+            caravela_status!("{}: Changing priority", self.name());
+            let _ = priority.set_for_current();
+        }
+    }
+
+    pub(crate) fn update_affinity(&self) {
+        if let Some(core) = self.control_block.take_affinity() {
+            caravela_status!("{}: Pinning to core {}", self.name(), core);
+            let _ = pin_current_thread(core);
         }
     }
 
@@ -263,19 +1196,57 @@ impl Agent {
             .is_some()
     }
 
+    /// Whether or not the agent runs within the mock context of a test harness.
+    fn is_mocked(&self) -> bool {
+        #[cfg(feature = "testing")]
+        return self.mock.is_some();
+        #[cfg(not(feature = "testing"))]
+        false
+    }
+
+    fn ams_aid(&self) -> Description {
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            return mock.ams().clone();
+        }
+        deck().view().ams_aid().clone()
+    }
+
     pub(crate) fn takedown(&self) -> Result<(), ErrorCode> {
         //let ams = deck().read().get_ams_address_for_hap(&self.hap)?;
-        let ams = deck().read().ams_aid().clone();
+        if simulation::is_active() && !self.is_mocked() {
+            // the AMS would join this thread while the simulation waits for it, so deregister directly
+            let aid = self.aid()?;
+            deck().write().remove_agent(&aid)?;
+            caravela_status!("{}: Terminating", self.name());
+            return Ok(());
+        }
+        let ams = self.ams_aid();
         let msg_type = MessageType::Request;
         let msg_content = Content::Action(ActionType::Deregister(self.aid()?));
         //let msg_content = format!("deregister {}", self.aid()?.name());
         self.send_to_aid(ams, msg_type, msg_content)?;
+        if !matches!(self.host, Host::Thread(_)) {
+            // the reply cannot be waited for without blocking a worker, it is dropped with the mailbox
+            caravela_status!("{}: Terminating", self.name());
+            return Ok(());
+        }
         self.receive().map(|_| {
             caravela_status!("{}: Terminating", self.name());
         })
     }
 }
 
+/// Validate a message priority given by an agent, since the control lane is reserved for the AMS.
+fn check_priority(priority: MessagePriority) -> Result<MessagePriority, ErrorCode> {
+    if priority == MessagePriority::Control {
+        return Err(ErrorCode::InvalidPriority(
+            "Control priority only allowed for the AMS",
+        ));
+    }
+    Ok(priority)
+}
+
 /// This trait defines how an agent without patameters must be built by the platform.
 pub trait AgentBuild {
     /// Required function to build the derived agent instance without a parameter field.
@@ -1,6 +1,17 @@
-use crate::ErrorCode;
+use crate::{
+    events::{self, Event},
+    pool::{Step, Task},
+    simulation::{self, Blocked},
+    ErrorCode,
+};
+use std::{
+    future::{self, Future},
+    panic::{self, AssertUnwindSafe},
+    pin::pin,
+    task::Poll,
+};
 
-use super::Agent;
+use super::{Agent, AgentState, Host};
 
 /// Establishes that an object is an agent.
 pub trait Behavior: AsRef<Agent> {
@@ -14,7 +25,6 @@ pub trait Behavior: AsRef<Agent> {
     fn done(&mut self) -> bool {
         caravela_dflt!("{}: agent behavior not done", self.as_ref().name());
         false
-        
     }
     /// Function that corresponds to the main repeating activity of the agent executed after [`Behavior::setup`].
     /// Empty by default.
@@ -53,7 +63,33 @@ pub trait Behavior: AsRef<Agent> {
 }
 
 pub(crate) fn execute(mut behavior: impl Behavior) {
+    caravela_span!(
+        "agent",
+        &*behavior.as_ref().nickname,
+        &*behavior.as_ref().hap
+    );
     behavior.as_ref().init();
+    let aid = behavior.as_ref().aid();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(&mut behavior)));
+    let panicked = result.is_err();
+    if let Ok(aid) = aid {
+        events::emit(|| {
+            if panicked {
+                Event::AgentPanicked(aid)
+            } else {
+                Event::AgentTerminated(aid)
+            }
+        });
+    }
+    if simulation::is_active() {
+        simulation::block(Blocked::Finished);
+    }
+    if let Err(payload) = result {
+        panic::resume_unwind(payload);
+    }
+}
+
+fn run(behavior: &mut impl Behavior) {
     let res = behavior.setup();
     if res.is_ok() {
         loop {
@@ -61,15 +97,248 @@ pub(crate) fn execute(mut behavior: impl Behavior) {
             if behavior.as_ref().quit() {
                 break;
             }
-            let res = behavior.action();
-            if behavior.failure_detection(&res) {
-                behavior.failure_identification(&res);
-                behavior.failure_recovery(&res);
-            }
-            if behavior.done() {
+            behavior.as_ref().update_priority();
+            behavior.as_ref().update_affinity();
+            if iterate(behavior) {
                 let _ = behavior.as_ref().takedown();
                 break;
             }
+            behavior.as_ref().end_step();
+        }
+    }
+}
+
+/// Run a single iteration of the behavior: its action, followed by the FDIR functions if a failure is detected.
+///  Returns whether the behavior is done.
+pub(crate) fn iterate(behavior: &mut impl Behavior) -> bool {
+    let res = behavior.action();
+    if matches!(res, Err(ErrorCode::WouldBlock | ErrorCode::Interrupted)) {
+        // a lightweight agent yielded, or a lifecycle command interrupted the agent:
+        //  the action is run again once it can continue
+        return false;
+    }
+    behavior.as_ref().answer_not_understood(&res);
+    behavior.as_ref().count_iteration(&res);
+    if behavior.failure_detection(&res) {
+        caravela_metric!(failure_detected(&behavior.as_ref().name()));
+        behavior.failure_identification(&res);
+        behavior.failure_recovery(&res);
+    }
+    behavior.done()
+}
+
+/// A lightweight agent stepped by the worker pool, each step running at most one [`Behavior::action`].
+pub(crate) struct Lightweight<T: Behavior> {
+    behavior: T,
+    set_up: bool,
+    suspended: bool,
+    finishing: bool,
+}
+
+impl<T: Behavior> Lightweight<T> {
+    pub(crate) fn new(behavior: T) -> Self {
+        Self {
+            behavior,
+            set_up: false,
+            suspended: false,
+            finishing: false,
+        }
+    }
+
+    fn finish(&self) -> Step {
+        let agent = self.behavior.as_ref();
+        agent.emit(Event::AgentTerminated);
+        Step::Finished
+    }
+}
+
+impl<T: Behavior + Send> Task for Lightweight<T> {
+    fn step(&mut self) -> Step {
+        let agent = self.behavior.as_ref();
+        caravela_span!("agent", &*agent.nickname, &*agent.hap);
+        match agent.control_block.agent_state() {
+            AgentState::Initiated => return Step::Parked,
+            AgentState::Terminated => return self.finish(),
+            AgentState::Suspended => {
+                if !self.suspended {
+                    self.suspended = true;
+                    caravela_status!("{}: Suspending", agent.name());
+                    agent.emit(Event::AgentSuspended);
+                }
+                return Step::Parked;
+            }
+            AgentState::Waiting => {
+                if agent.end_wait().is_err() {
+                    return Step::Ready;
+                }
+            }
+            AgentState::Active => (),
+        }
+        if self.suspended {
+            self.suspended = false;
+            agent.emit(Event::AgentResumed);
+        }
+        if self.finishing {
+            let _ = agent.takedown();
+            return self.finish();
+        }
+        let done = if self.set_up {
+            iterate(&mut self.behavior)
+        } else {
+            match self.behavior.setup() {
+                Err(ErrorCode::WouldBlock) => false,
+                Err(_) => return self.finish(),
+                Ok(()) => {
+                    self.set_up = true;
+                    false
+                }
+            }
+        };
+        self.finishing = done;
+        match self.behavior.as_ref().control_block.take_yield() {
+            Some(reason) => Step::Yielded(reason),
+            None => Step::Ready,
+        }
+    }
+}
+
+/// Asynchronous counterpart of [`Behavior`] for agents added with [`Platform::add_async_agent`](crate::Platform::add_async_agent),
+///  whose setup and action are futures run by the [`Executor`](crate::executor::Executor) of the platform.
+///  They await [`Agent::receive_async`], [`Agent::send_to_async`] and [`Agent::wait_async`] instead of blocking,
+///  and can await any other future, such as asynchronous I/O. They go through the same lifecycle as threaded agents,
+///  although a termination also interrupts the pending action.
+pub trait AsyncBehavior: AsRef<Agent> + Send {
+    /// Function awaited once after starting the agent; just before [`AsyncBehavior::action`]. Empty by default.
+    fn setup(&mut self) -> impl Future<Output = Result<(), ErrorCode>> + Send {
+        caravela_dflt!("{}: no setup implemented", self.as_ref().name());
+        async { Ok(()) }
+    }
+    /// Function executed after [`AsyncBehavior::action`] used to determined if the agent has reached the end of its life cycle.
+    /// Returns `false` by default.
+    fn done(&mut self) -> bool {
+        caravela_dflt!("{}: agent behavior not done", self.as_ref().name());
+        false
+    }
+    /// Function that corresponds to the main repeating activity of the agent awaited after [`AsyncBehavior::setup`].
+    /// Empty by default.
+    fn action(&mut self) -> impl Future<Output = Result<(), ErrorCode>> + Send {
+        caravela_dflt!("{}: no action implemented", self.as_ref().name());
+        async { Ok(()) }
+    }
+    /// Function used to include Fault Detection as part of the FDIR functionality of the agent.
+    /// Returns `false` by default.
+    fn failure_detection(&mut self, action_result: &Result<(), ErrorCode>) -> bool {
+        caravela_dflt!(
+            "{}: no failure detection implemented ({:?})",
+            self.as_ref().name(),
+            action_result
+        );
+        false
+    }
+    /// Function used to include Fault Identification as part of the FDIR functionality of the agent.
+    /// Empty by default.
+    fn failure_identification(&mut self, action_result: &Result<(), ErrorCode>) {
+        caravela_dflt!(
+            "{}: no failure identification implemented ({:?})",
+            self.as_ref().name(),
+            action_result
+        );
+    }
+    /// Function used to include Fault Recovery as part of the FDIR functionality of the agent.
+    /// Empty by default.
+    fn failure_recovery(&mut self, action_result: &Result<(), ErrorCode>) {
+        caravela_dflt!(
+            "{}: no failure recovery implemented ({:?})",
+            self.as_ref().name(),
+            action_result
+        );
+    }
+}
+
+/// Run an asynchronous agent through its lifecycle, catching a panic of any of its functions.
+pub(crate) async fn execute_async(mut behavior: impl AsyncBehavior) {
+    let aid = behavior.as_ref().aid();
+    #[cfg(feature = "tracing")]
+    let (nickname, hap) = (
+        behavior.as_ref().nickname.clone(),
+        behavior.as_ref().hap.clone(),
+    );
+    let mut run = pin!(run_async(&mut behavior));
+    let result = future::poll_fn(|cx| {
+        // entered on every poll, since the agent shares its thread with others between polls
+        caravela_span!("agent", &*nickname, &*hap);
+        panic::catch_unwind(AssertUnwindSafe(|| run.as_mut().poll(cx)))
+            .map_or(Poll::Ready(true), |x| x.map(|_| false))
+    })
+    .await;
+    if let Ok(aid) = aid {
+        events::emit(|| {
+            if result {
+                Event::AgentPanicked(aid)
+            } else {
+                Event::AgentTerminated(aid)
+            }
+        });
+    }
+}
+
+async fn run_async(behavior: &mut impl AsyncBehavior) {
+    behavior
+        .as_ref()
+        .until_state(|state| state != AgentState::Initiated)
+        .await;
+    if behavior.setup().await.is_err() {
+        return;
+    }
+    loop {
+        let agent = behavior.as_ref();
+        if agent.control_block.agent_state() == AgentState::Suspended {
+            caravela_status!("{}: Suspending", agent.name());
+            agent.emit(Event::AgentSuspended);
+            agent
+                .until_state(|state| state != AgentState::Suspended)
+                .await;
+            caravela_status!("{}: Resuming", agent.name());
+            agent.emit(Event::AgentResumed);
+        }
+        if agent.quit() {
+            break;
+        }
+        let control_block = agent.control_block.clone();
+        let Host::Executor(_, signal) = &agent.host else {
+            break;
+        };
+        let signal = signal.clone();
+        let res = {
+            let mut action = pin!(behavior.action());
+            future::poll_fn(|cx| {
+                // checked on every wake up, so a termination interrupts the action
+                signal.register(cx.waker());
+                if control_block.agent_state() == AgentState::Terminated {
+                    return Poll::Ready(None);
+                }
+                action.as_mut().poll(cx).map(Some)
+            })
+            .await
+        };
+        let Some(res) = res else {
+            caravela_status!("{}: Terminating", behavior.as_ref().name());
+            break;
+        };
+        if res == Err(ErrorCode::Interrupted) {
+            // suspended while awaiting, the action is run again once resumed
+            continue;
+        }
+        behavior.as_ref().answer_not_understood(&res);
+        behavior.as_ref().count_iteration(&res);
+        if behavior.failure_detection(&res) {
+            caravela_metric!(failure_detected(&behavior.as_ref().name()));
+            behavior.failure_identification(&res);
+            behavior.failure_recovery(&res);
+        }
+        if behavior.done() {
+            let _ = behavior.as_ref().takedown();
+            break;
         }
     }
 }
@@ -1,42 +1,161 @@
-use crate::{entity::Description, ErrorCode};
+pub(crate) mod mailbox;
+
+use crate::{
+    agent::{AgentState, ControlBlock},
+    entity::Description,
+    service::{organization::OrgAction, sniffer::SniffAction, Role},
+    ErrorCode,
+};
 use std::{
     fmt::Display,
-    sync::mpsc::{SendError, TrySendError},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
 #[derive(Debug)]
-pub(crate) enum SyncType {
+pub(crate) enum SyncType<'a> {
     Blocking,
-    #[allow(dead_code)]
     NonBlocking, //USE?
+    /// Blocking until a lifecycle command interrupts the sending agent.
+    Interruptible(&'a ControlBlock),
 }
 
-#[derive(Debug)]
-pub(crate) enum SendResult {
-    Blocking(Result<(), SendError<Message>>),
-    NonBlocking(Result<(), TrySendError<Message>>),
-}
-/*
-// Agent state changes that can be requested via the [`ModifyRequest::Ams`] enum.
-#[derive(Clone, PartialEq, Eq, Debug)]
+/// Agent state changes that can be requested via the [`ModifyAgent::State`] variant.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum StateOp {
-    // Resume the agent from the [`AgentState::Waiting`](enum@crate::agent::AgentState) and [`AgentState::Suspended`](enum@crate::agent::AgentState) states.
+    /// Resume the agent from the [`AgentState::Waiting`] and [`AgentState::Suspended`] states.
     Resume,
-    // Supend the agent from the [`AgentState::Active`](enum@crate::agent::AgentState) state.
+    /// Supend the agent from the [`AgentState::Active`] and [`AgentState::Waiting`] states.
     Suspend,
-    // Terminate the agent from the [`AgentState::Active`](enum@crate::agent::AgentState) state.
+    /// Terminate the agent from the [`AgentState::Active`] and [`AgentState::Waiting`] states.
     Terminate,
 }
 
-// Modification request types that can be aimed toward services or other agents.
+impl Display for StateOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateOp::Resume => write!(f, "Resume"),
+            StateOp::Suspend => write!(f, "Suspend"),
+            StateOp::Terminate => write!(f, "Terminate"),
+        }
+    }
+}
+
+/// Modification requests that can be aimed toward the AMS through [`ActionType::Modify`].
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum ModifyAgent {
-    // Modification requests targeted to the AMS which only allows state changes.
+    /// Change the lifecycle state of the agent.
     State(StateOp),
-    // Modification requests targeted to other elements of unknown nature.
+    /// Change the priority of the agent. The maximum priority is reserved for services.
+    Priority(u8),
+    /// Change the number of messages the mailbox of the agent can hold.
+    MailboxCapacity(usize),
+    /// Set the agent that owns the target agent.
+    Owner(Description),
+    /// Set the role of the target agent.
+    Role(Role),
+    /// Set a user defined property of the agent as a key-value pair.
+    Property(String, String),
+    /// Remove a user defined property of the agent.
+    RemoveProperty(String),
+}
+
+impl Display for ModifyAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModifyAgent::State(x) => write!(f, "State {}", x),
+            ModifyAgent::Priority(x) => write!(f, "Priority {}", x),
+            ModifyAgent::MailboxCapacity(x) => write!(f, "Mailbox Capacity {}", x),
+            ModifyAgent::Owner(x) => write!(f, "Owner {}", x),
+            ModifyAgent::Role(x) => write!(f, "Role {}", x),
+            ModifyAgent::Property(key, value) => write!(f, "Property {}={}", key, value),
+            ModifyAgent::RemoveProperty(key) => write!(f, "Remove Property {}", key),
+        }
+    }
+}
+
+/// Typed reasons included in [`MessageType::Refuse`] and [`MessageType::Failure`] replies from services.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Reason {
+    /// The requester is not allowed to perform the action upon the target.
+    Unauthorized,
+    /// The target agent is not registered.
+    NotRegistered,
+    /// The requested state change is not possible.
+    InvalidStateChange(AgentState, AgentState),
+    /// The requested priority is not allowed.
+    InvalidPriority(&'static str),
+    /// The requested mailbox capacity is not allowed.
+    InvalidCapacity(usize),
+    /// The agent could not be joined after finishing.
+    AgentPanic,
+    /// The requester is not a member of the organization.
+    NotMember,
+    /// The requester is banned from the organization.
+    Banned,
+    /// The requested action is not supported by the service.
+    Unsupported,
+    /// No reply arrived from the agent before the deadline given with `reply_by`.
+    NoReply(Description),
+    /// The mailbox of the receiver was full.
+    MailboxFull,
+    /// The message outlived its time to live before being delivered.
+    Expired,
+    /// Any other reason given as text.
     Other(String),
 }
-*/
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reason::Unauthorized => write!(f, "Requester is not authorized"),
+            Reason::NotRegistered => write!(f, "Target agent is not registered"),
+            Reason::InvalidStateChange(current, next) => {
+                write!(f, "Transtion from {} to {} is not possible", current, next)
+            }
+            Reason::InvalidPriority(x) => write!(f, "Invalid priority: {}", x),
+            Reason::InvalidCapacity(x) => write!(f, "Invalid mailbox capacity: {}", x),
+            Reason::AgentPanic => write!(f, "Agent panicked"),
+            Reason::NotMember => write!(f, "Requester is not a member"),
+            Reason::Banned => write!(f, "Requester is banned"),
+            Reason::Unsupported => write!(f, "Unsupported action"),
+            Reason::NoReply(aid) => write!(f, "No reply from {} in time", aid),
+            Reason::MailboxFull => write!(f, "Mailbox of the receiver was full"),
+            Reason::Expired => write!(f, "Message expired"),
+            Reason::Other(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl From<&ErrorCode> for Reason {
+    fn from(value: &ErrorCode) -> Self {
+        match value {
+            ErrorCode::NotRegistered | ErrorCode::NotFound | ErrorCode::Disconnected => {
+                Reason::NotRegistered
+            }
+            ErrorCode::ChannelFull => Reason::MailboxFull,
+            ErrorCode::Expired => Reason::Expired,
+            ErrorCode::InvalidStateChange(current, next) => {
+                Reason::InvalidStateChange(*current, *next)
+            }
+            ErrorCode::InvalidPriority(x) => Reason::InvalidPriority(x),
+            ErrorCode::InvalidCapacity(x) => Reason::InvalidCapacity(*x),
+            ErrorCode::AgentPanic => Reason::AgentPanic,
+            ErrorCode::NotMember => Reason::NotMember,
+            ErrorCode::Banned => Reason::Banned,
+            ErrorCode::NotVisible => Reason::Unauthorized,
+            ErrorCode::InvalidRequest(_) => Reason::Unsupported,
+            x => Reason::Other(x.to_string()),
+        }
+    }
+}
+
+impl From<ErrorCode> for Reason {
+    fn from(value: ErrorCode) -> Self {
+        Reason::from(&value)
+    }
+}
 
 /// All communicative acts allowed between agents.
 ///
@@ -65,18 +184,20 @@ pub enum MessageType {
     InformRef,
     /// Inform that the content of a message was not understood.
     NotUnderstood,
-    /// Request the receiver to resend the message to another agent.
+    /// Request the receiver to act upon an embedded message and to send it on to the given agents.
     Propagate,
     /// Present a proposal to the receiver.
     Propose,
-    //Proxy,
+    /// Request the receiver to send an embedded message to the given agents.
+    Proxy,
     /// Ask the receiver if a proposition is true.
     QueryIf,
     /// Ask the receiver for a descriptor of a reference.
     QueryRef,
     /// Refuse to perform an action.
     Refuse,
-    //RejectProposal
+    /// Reject a previously presented proposal to perform some action.
+    RejectProposal,
     /// Request the receiver to perform some action.
     Request,
     /// Request the receiver to perform some action when a proposition becomes true.
@@ -106,9 +227,11 @@ impl Display for MessageType {
             MessageType::NotUnderstood => write!(f, "NotUnderstood Message"),
             MessageType::Propagate => write!(f, "Propagate Message"),
             MessageType::Propose => write!(f, "Propose Message"),
+            MessageType::Proxy => write!(f, "Proxy Message"),
             MessageType::QueryIf => write!(f, "QueryIf Message"),
             MessageType::QueryRef => write!(f, "QueryRef Message"),
             MessageType::Refuse => write!(f, "Refuse Message"),
+            MessageType::RejectProposal => write!(f, "Reject Proposal Message"),
             MessageType::Request => write!(f, "Request Message"),
             MessageType::RequestWhen => write!(f, "RequestWhen Message"),
             MessageType::RequestWhenever => write!(f, "RequestWhenever Message"),
@@ -118,7 +241,66 @@ impl Display for MessageType {
     }
 }
 
+impl FromStr for MessageType {
+    type Err = ErrorCode;
+
+    /// Read a message type from its variant name, ignoring case, such as `Inform` or `callforproposal`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MessageType::ALL
+            .into_iter()
+            .find(|x| format!("{:?}", x).eq_ignore_ascii_case(s))
+            .ok_or(ErrorCode::InvalidMessageType)
+    }
+}
+
 impl MessageType {
+    /// Every message type.
+    pub const ALL: [MessageType; 23] = [
+        MessageType::AcceptProposal,
+        MessageType::Agree,
+        MessageType::Cancel,
+        MessageType::CallForProposal,
+        MessageType::Confirm,
+        MessageType::Disconfirm,
+        MessageType::Failure,
+        MessageType::Inform,
+        MessageType::InformIf,
+        MessageType::InformRef,
+        MessageType::NotUnderstood,
+        MessageType::Propagate,
+        MessageType::Propose,
+        MessageType::Proxy,
+        MessageType::QueryIf,
+        MessageType::QueryRef,
+        MessageType::Refuse,
+        MessageType::RejectProposal,
+        MessageType::Request,
+        MessageType::RequestWhen,
+        MessageType::RequestWhenever,
+        MessageType::Subscribe,
+        MessageType::None,
+    ];
+
+    /// The message types that answer this one as per the FIPA interaction protocols, empty if no answer is expected.
+    ///  [`MessageType::NotUnderstood`] answers any of them and is left out.
+    pub fn replies(&self) -> &'static [MessageType] {
+        use MessageType::*;
+        match self {
+            Request | RequestWhen | RequestWhenever | QueryIf | QueryRef | Subscribe
+            | Propagate | Proxy => &[Agree, Refuse, Failure, Inform],
+            CallForProposal => &[Propose, Refuse],
+            Propose => &[AcceptProposal, RejectProposal],
+            AcceptProposal | Cancel => &[Inform, Failure],
+            _ => &[],
+        }
+    }
+
+    /// Whether this message type is a valid answer to `other`, as given by [`replies`](Self::replies).
+    pub fn is_reply_to(&self, other: &Self) -> bool {
+        (*self == MessageType::NotUnderstood && *other != MessageType::NotUnderstood)
+            || other.replies().contains(self)
+    }
+
     /// Check if message type is the desired type. This is added to reduce code repetition while trying to pattern match a one or multiple message type.
     pub fn is_message_type(&self, other: &Self) -> Result<(), ErrorCode> {
         if self.eq(other) {
@@ -135,12 +317,15 @@ pub enum ActionType {
     /// Request the target to search for an agent.
     Search(Description),
     /// Request the target to modify an agent.
-    Modify(Description, String),
-    //Modify(Description, ModifyAgent),
+    Modify(Description, ModifyAgent),
     /// Request the target to register an agent.
     Register(Description),
     /// Request the target to deregister an agent.
     Deregister(Description),
+    /// Request the organization service to act upon the named organization.
+    Organization(String, OrgAction),
+    /// Request the sniffer to start or stop capturing the messages of an agent.
+    Sniffer(SniffAction),
     /// Other non-specific action defined by the user.
     Other(&'static str),
 }
@@ -150,9 +335,11 @@ impl Display for ActionType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ActionType::Search(x) => write!(f, "Search {}", x),
-            ActionType::Modify(x, _) => write!(f, "Modify {}", x),
+            ActionType::Modify(x, modify) => write!(f, "Modify {}: {}", x, modify),
             ActionType::Register(x) => write!(f, "Registration {}", x),
             ActionType::Deregister(x) => write!(f, "Deregistration {}", x),
+            ActionType::Organization(x, action) => write!(f, "Organization {}: {}", x, action),
+            ActionType::Sniffer(action) => write!(f, "Sniffer: {}", action),
             ActionType::Other(x) => write!(f, "{}", x),
         }
     }
@@ -165,6 +352,12 @@ pub enum Content {
     Expression(String),
     /// A request to be done.
     Action(ActionType),
+    /// A request that could not be done and the reason why.
+    Reason(ActionType, Reason),
+    /// Raw bytes, such as sensor readings, shared rather than copied when the message is forwarded or broadcast.
+    Bytes(Arc<[u8]>),
+    /// A message to be sent on to the given agents, as the content of a [`MessageType::Proxy`] or [`MessageType::Propagate`].
+    Forward(Vec<Description>, MessageType, Arc<Content>),
     //Request(Description, RequestType),
     //RequestOrg(Performer, RequestType),
     //AMS agent description object.
@@ -176,35 +369,118 @@ impl Display for Content {
         match self {
             Self::Action(x) => write!(f, "{}", x),
             Self::Expression(x) => write!(f, "{}", x),
+            Self::Reason(x, reason) => write!(f, "{} ({})", x, reason),
+            Self::Bytes(bytes) => bytes.iter().try_for_each(|x| write!(f, "{:02x}", x)),
+            Self::Forward(targets, message_type, content) => {
+                write!(f, "{} ({}) to", message_type, content)?;
+                targets.iter().try_for_each(|x| write!(f, " {}", x))
+            }
         }
     }
 }
 
+/// Priority of a message, which selects the lane of the mailbox it waits in.
+///  Messages in higher lanes are received first, and in order of arrival within a lane.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum MessagePriority {
+    /// Bulk traffic that can wait.
+    Low,
+    /// Regular traffic.
+    #[default]
+    Normal,
+    /// Traffic that should overtake the regular one, such as safety notices.
+    High,
+    /// Platform control traffic. This lane is reserved for the AMS and does not count towards the mailbox capacity.
+    Control,
+}
+
+impl MessagePriority {
+    /// Number of lanes of a mailbox.
+    pub(crate) const LANES: usize = 4;
+
+    pub(crate) fn lane(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for MessagePriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessagePriority::Low => write!(f, "Low"),
+            MessagePriority::Normal => write!(f, "Normal"),
+            MessagePriority::High => write!(f, "High"),
+            MessagePriority::Control => write!(f, "Control"),
+        }
+    }
+}
+
+/// How a mailbox chooses between its lanes when a lower one has messages waiting.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LanePolicy {
+    /// The highest lane with messages is always received first, so lower lanes may starve.
+    #[default]
+    Strict,
+    /// A waiting lower lane is received once the given number of messages overtook it.
+    ///  The [`MessagePriority::Control`] lane is never overtaken.
+    Aging(u32),
+}
+
 /// Message object with a payload ([`RequestType`] and [`Content`]) and sender/receiver infromation.
+///  The descriptions and the content are shared, so cloning a message or broadcasting it does not copy them.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Message {
-    sender: Description,
-    receiver: Description,
+    sender: Arc<Description>,
+    receiver: Arc<Description>,
     message_type: MessageType,
     //content: String,
-    content: Content,
+    content: Arc<Content>,
+    reply_by: Option<Instant>,
+    expires_at: Option<Instant>,
+    priority: MessagePriority,
 }
 
 impl Message {
     pub(crate) fn new(
-        sender: Description,
-        receiver: Description,
+        sender: impl Into<Arc<Description>>,
+        receiver: impl Into<Arc<Description>>,
         message_type: MessageType,
-        content: Content,
+        content: impl Into<Arc<Content>>,
     ) -> Self {
         Self {
-            sender,
-            receiver,
+            sender: sender.into(),
+            receiver: receiver.into(),
             message_type,
-            content,
+            content: content.into(),
+            reply_by: None,
+            expires_at: None,
+            priority: MessagePriority::Normal,
         }
     }
 
+    pub(crate) fn with_priority(mut self, priority: MessagePriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub(crate) fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.expires_at = Some(Instant::now() + ttl);
+        self
+    }
+
+    /// The same message sent afresh to `receiver`, without its expiry.
+    pub(crate) fn readdressed(&self, receiver: impl Into<Arc<Description>>) -> Self {
+        Self {
+            receiver: receiver.into(),
+            expires_at: None,
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn with_reply_by(mut self, deadline: Instant) -> Self {
+        self.reply_by = Some(deadline);
+        self
+    }
+
     /// Retrieve a message's communicative act type.
     pub fn message_type(&self) -> &MessageType {
         &self.message_type
@@ -215,6 +491,11 @@ impl Message {
         &self.content
     }
 
+    /// Retrieve a message's contents as shared with every copy of the message, to send them on without copying.
+    pub fn shared_content(&self) -> Arc<Content> {
+        self.content.clone()
+    }
+
     /// Get a reference to the sender's [`Description`]
     pub fn sender(&self) -> &Description {
         &self.sender
@@ -224,4 +505,39 @@ impl Message {
     pub fn receiver(&self) -> &Description {
         &self.receiver
     }
+
+    /// Get the deadline the sender expects a reply by, if it gave one.
+    pub fn reply_by(&self) -> Option<Instant> {
+        self.reply_by
+    }
+
+    /// Get the instant after which the message is no longer delivered, if it has a time to live.
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    /// Parse the content of the message, which must be an [`Content::Expression`].
+    ///  It fails with [`ErrorCode::InvalidContent`], which a behavior can return from its action
+    ///  so the sender is answered with [`MessageType::NotUnderstood`].
+    pub fn parse_content<T: FromStr>(&self) -> Result<T, ErrorCode> {
+        match &*self.content {
+            Content::Expression(x) => x
+                .parse()
+                .map_err(|_| ErrorCode::InvalidContent(format!("cannot parse {}", x))),
+            x => Err(ErrorCode::InvalidContent(format!(
+                "expected an expression, not {}",
+                x
+            ))),
+        }
+    }
+
+    /// Get the priority of the message, the lane of the mailbox it waits in.
+    pub fn priority(&self) -> MessagePriority {
+        self.priority
+    }
+
+    /// Whether the time to live of the message ran out.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Instant::now())
+    }
 }
@@ -0,0 +1,369 @@
+use super::{LanePolicy, Message, MessagePriority};
+use crate::ErrorCode;
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::RecvError,
+        Arc, Condvar, Mutex, MutexGuard, OnceLock,
+    },
+    task::{self, Context, Poll},
+};
+
+type Waker = Box<dyn Fn() + Send + Sync>;
+
+/// Messages waiting in a mailbox, one queue per [`MessagePriority`].
+#[derive(Default)]
+struct Lanes {
+    lanes: [VecDeque<Message>; MessagePriority::LANES],
+    /// Number of messages that overtook the ones waiting in each lane.
+    skipped: [u32; MessagePriority::LANES],
+    policy: LanePolicy,
+}
+
+impl Lanes {
+    fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lanes.iter().all(VecDeque::is_empty)
+    }
+
+    /// Number of messages counting towards the capacity of the mailbox.
+    fn regular(&self) -> usize {
+        self.len() - self.lanes[MessagePriority::Control.lane()].len()
+    }
+
+    fn push_back(&mut self, msg: Message) {
+        self.lanes[msg.priority().lane()].push_back(msg);
+    }
+
+    /// Take the next message, from the highest lane unless a lower one waited too long as per the policy.
+    fn pop_front(&mut self) -> Option<Message> {
+        let top = self.lanes.iter().rposition(|lane| !lane.is_empty())?;
+        let lane = match self.policy {
+            LanePolicy::Aging(limit) if top != MessagePriority::Control.lane() => (0..top)
+                .rev()
+                .find(|&lane| !self.lanes[lane].is_empty() && self.skipped[lane] >= limit.max(1))
+                .unwrap_or(top),
+            _ => top,
+        };
+        // only aging reads the counts, which would otherwise grow without bound under a strict policy
+        if matches!(self.policy, LanePolicy::Aging(_)) && lane != MessagePriority::Control.lane() {
+            for below in 0..lane {
+                if self.lanes[below].is_empty() {
+                    self.skipped[below] = 0;
+                } else {
+                    self.skipped[below] = self.skipped[below].saturating_add(1);
+                }
+            }
+            self.skipped[lane] = 0;
+        }
+        self.lanes[lane].pop_front()
+    }
+
+    /// Take every message, highest lane first.
+    fn take(&mut self) -> VecDeque<Message> {
+        self.skipped = Default::default();
+        self.lanes
+            .iter_mut()
+            .rev()
+            .flat_map(std::mem::take)
+            .collect()
+    }
+}
+
+/// Bounded message queue shared by both halves of a mailbox.
+///  It follows the semantics of [`std::sync::mpsc::sync_channel`],
+///  but its capacity can be changed while the agent is running.
+///  Messages are received by priority, the control lane being exempt from the capacity.
+struct Shared {
+    queue: Mutex<Lanes>,
+    capacity: AtomicUsize,
+    high_water: AtomicUsize,
+    receiving: AtomicBool,
+    senders: AtomicUsize,
+    connected: AtomicBool,
+    not_empty: Condvar,
+    not_full: Condvar,
+    waker: OnceLock<Waker>,
+    room: Mutex<Vec<task::Waker>>,
+}
+
+impl Shared {
+    fn queue(&self) -> MutexGuard<'_, Lanes> {
+        self.queue
+            .lock()
+            .expect("Mailbox is poisoned - Lost messages")
+    }
+
+    fn is_full(&self, queue: &Lanes, priority: MessagePriority) -> bool {
+        priority != MessagePriority::Control
+            && queue.regular() >= self.capacity.load(Ordering::Relaxed)
+    }
+
+    fn push(&self, queue: &mut Lanes, msg: Message) {
+        queue.push_back(msg);
+        self.high_water.fetch_max(queue.len(), Ordering::Relaxed);
+        self.not_empty.notify_one();
+    }
+
+    /// Let blocked and asynchronous senders know there is room in the mailbox, called with the queue locked.
+    fn make_room(&self, all: bool) {
+        if all {
+            self.not_full.notify_all();
+        } else {
+            self.not_full.notify_one();
+        }
+        let wakers = std::mem::take(&mut *self.room.lock().expect("Mailbox is poisoned"));
+        wakers.into_iter().for_each(task::Waker::wake);
+    }
+
+    /// Run the waker of a receiver that does not block on the queue, called once the queue is released.
+    fn wake(&self) {
+        if let Some(waker) = self.waker.get() {
+            waker();
+        }
+    }
+}
+
+/// Sending half of a mailbox.
+pub(crate) struct Sender {
+    shared: Arc<Shared>,
+}
+
+/// Receiving half of a mailbox.
+pub(crate) struct Receiver {
+    shared: Arc<Shared>,
+}
+
+/// Create a new mailbox able to hold up to `capacity` messages.
+pub(crate) fn channel(capacity: usize) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::default(),
+        capacity: AtomicUsize::new(capacity.max(1)),
+        high_water: AtomicUsize::new(0),
+        receiving: AtomicBool::new(false),
+        senders: AtomicUsize::new(1),
+        connected: AtomicBool::new(true),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        waker: OnceLock::new(),
+        room: Mutex::default(),
+    });
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Receiver { shared },
+    )
+}
+
+impl Sender {
+    /// Send a message, blocking while the mailbox is full.
+    pub(crate) fn send(&self, msg: Message) -> Result<(), ErrorCode> {
+        self.send_until(msg, || false)
+    }
+
+    /// Send a message, blocking while the mailbox is full unless `interrupted` holds once woken by [`interrupt`](Self::interrupt).
+    pub(crate) fn send_until(
+        &self,
+        msg: Message,
+        interrupted: impl Fn() -> bool,
+    ) -> Result<(), ErrorCode> {
+        let mut queue = self.shared.queue();
+        loop {
+            if !self.shared.connected.load(Ordering::Relaxed) {
+                return Err(ErrorCode::Disconnected);
+            }
+            if !self.shared.is_full(&queue, msg.priority()) {
+                self.shared.push(&mut queue, msg);
+                drop(queue);
+                self.shared.wake();
+                return Ok(());
+            }
+            if interrupted() {
+                return Err(ErrorCode::Interrupted);
+            }
+            queue = self
+                .shared
+                .not_full
+                .wait(queue)
+                .expect("Mailbox is poisoned - Lost messages");
+        }
+    }
+
+    /// Wake every sender and the receiver blocked on the mailbox, so they check whether they were interrupted.
+    pub(crate) fn interrupt(&self) {
+        let _queue = self.shared.queue();
+        self.shared.not_empty.notify_all();
+        self.shared.not_full.notify_all();
+    }
+
+    /// Send a message only if there is room in the mailbox.
+    pub(crate) fn try_send(&self, msg: Message) -> Result<(), ErrorCode> {
+        let mut queue = self.shared.queue();
+        if !self.shared.connected.load(Ordering::Relaxed) {
+            Err(ErrorCode::Disconnected)
+        } else if self.shared.is_full(&queue, msg.priority()) {
+            Err(ErrorCode::ChannelFull)
+        } else {
+            self.shared.push(&mut queue, msg);
+            drop(queue);
+            self.shared.wake();
+            Ok(())
+        }
+    }
+
+    /// Ready once there is room in the mailbox or its receiver is gone, otherwise the task is woken when a message is taken.
+    pub(crate) fn poll_room(&self, cx: &mut Context<'_>) -> Poll<()> {
+        let queue = self.shared.queue();
+        if !self.shared.connected.load(Ordering::Relaxed)
+            || !self.shared.is_full(&queue, MessagePriority::Normal)
+        {
+            return Poll::Ready(());
+        }
+        let mut room = self.shared.room.lock().expect("Mailbox is poisoned");
+        if !room.iter().any(|x| x.will_wake(cx.waker())) {
+            room.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Change the number of messages the mailbox can hold.
+    ///  Messages already queued beyond the new capacity are kept.
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        let _queue = self.shared.queue();
+        self.shared
+            .capacity
+            .store(capacity.max(1), Ordering::Relaxed);
+        self.shared.make_room(true);
+    }
+
+    /// Change how the mailbox chooses between its lanes, counting the overtaken messages anew.
+    pub(crate) fn set_policy(&self, policy: LanePolicy) {
+        let mut queue = self.shared.queue();
+        queue.policy = policy;
+        queue.skipped = Default::default();
+    }
+
+    /// Number of messages the mailbox can hold.
+    pub(crate) fn capacity(&self) -> usize {
+        self.shared.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Number of messages waiting in the mailbox.
+    pub(crate) fn len(&self) -> usize {
+        self.shared.queue().len()
+    }
+
+    /// Whether the mailbox is empty and its receiver is waiting for messages.
+    pub(crate) fn is_idle(&self) -> bool {
+        let queue = self.shared.queue();
+        queue.is_empty() && self.shared.receiving.load(Ordering::Relaxed)
+    }
+
+    /// Largest number of messages the mailbox has held at once.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn high_water(&self) -> usize {
+        self.shared.high_water.load(Ordering::Relaxed)
+    }
+}
+
+impl Receiver {
+    /// Number of messages waiting in the mailbox.
+    pub(crate) fn len(&self) -> usize {
+        self.shared.queue().len()
+    }
+
+    /// Wait for a message to arrive, unless `interrupted` holds once woken by [`Sender::interrupt`].
+    pub(crate) fn recv_until(&self, interrupted: impl Fn() -> bool) -> Result<Message, ErrorCode> {
+        let mut queue = self.shared.queue();
+        loop {
+            if let Some(msg) = queue.pop_front() {
+                self.shared.make_room(false);
+                return Ok(msg);
+            }
+            if self.shared.senders.load(Ordering::Relaxed) == 0 {
+                return Err(ErrorCode::MpscRecv(RecvError));
+            }
+            if interrupted() {
+                return Err(ErrorCode::Interrupted);
+            }
+            self.shared.receiving.store(true, Ordering::Relaxed);
+            queue = self
+                .shared
+                .not_empty
+                .wait(queue)
+                .expect("Mailbox is poisoned - Lost messages");
+            self.shared.receiving.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Disconnect the mailbox from its senders and take the messages left in it.
+    pub(crate) fn close(&self) -> VecDeque<Message> {
+        let mut queue = self.shared.queue();
+        self.shared.connected.store(false, Ordering::Relaxed);
+        self.shared.make_room(true);
+        queue.take()
+    }
+
+    /// Run `waker` every time a message is placed in the mailbox, for receivers that do not block on it.
+    pub(crate) fn set_waker(&self, waker: impl Fn() + Send + Sync + 'static) {
+        let _ = self.shared.waker.set(Box::new(waker));
+    }
+
+    /// Take a message only if there is one waiting.
+    pub(crate) fn try_recv(&self) -> Option<Message> {
+        let mut queue = self.shared.queue();
+        let msg = queue.pop_front();
+        if msg.is_some() {
+            self.shared.make_room(false);
+        }
+        msg
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::Relaxed) == 1 {
+            let _queue = self.shared.queue();
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let _queue = self.shared.queue();
+        self.shared.connected.store(false, Ordering::Relaxed);
+        self.shared.make_room(true);
+    }
+}
+
+impl Debug for Sender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sender")
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+impl Debug for Receiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Receiver")
+            .field("capacity", &self.shared.capacity.load(Ordering::Relaxed))
+            .finish()
+    }
+}
@@ -1,12 +1,17 @@
 pub(crate) mod ams;
+/// Organizations, memberships and roles.
+pub mod organization;
+/// Capture and export of the messages exchanged by selected agents.
+pub mod sniffer;
 
 use crate::{
     entity::{
-        messaging::{Content, MessageType},
+        messaging::{ActionType, Content, MessageType, ModifyAgent, Reason},
         Description,
     },
     ErrorCode,
 };
+use std::fmt::Display;
 
 #[derive(Debug)]
 pub(crate) struct DefaultConditions;
@@ -15,8 +20,7 @@ pub(crate) trait Service {
     fn name(&self) -> String;
     fn init(&mut self);
     fn search_agent(&self, aid: &Description) -> Result<(), ErrorCode>;
-    //fn modify_agent(&self, aid: &Description, modify: &ModifyAgent) -> Result<(), ErrorCode>;
-    fn modify_agent(&self, aid: &Description, modifier: &str) -> Result<(), ErrorCode>;
+    fn modify_agent(&self, aid: &Description, modify: &ModifyAgent) -> Result<(), ErrorCode>;
     fn register_agent(&self, aid: &Description) -> Result<(), ErrorCode>;
     fn deregister_agent(&self, aid: &Description) -> Result<(), ErrorCode>;
     fn service_function(&mut self);
@@ -28,67 +32,155 @@ pub(crate) trait Service {
     ) -> Result<(), ErrorCode>;
 }
 
+/// Authority of an agent within the platform, used by service conditions to authorize requests.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Hash)]
+pub enum Role {
+    /// The agent can only act upon itself and the agents it owns.
+    #[default]
+    Regular,
+    /// The agent can act upon any other agent.
+    Supervisor,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Regular => write!(f, "Regular"),
+            Role::Supervisor => write!(f, "Supervisor"),
+        }
+    }
+}
+
 /// This trait defines a set of boolean functions whose purpose is to specify
 ///  under which conditions any service entity should provide its services:
-/// 
+///
 ///  - Search
 ///  - Modification
 ///  - Registration
 ///  - Deregistration
-/// 
-/// Whenever the internal directory is referenced, it corresponds to 
+///
+/// Each condition receives the [`Description`] of the agent making the request (`requester`)
+///  and of the agent the request is about (`target`).
+///  The owner and [`Role`] of both can be consulted with [`Description::owner`] and [`Description::role`].
+///
+/// Whenever the internal directory is referenced, it corresponds to
 ///  the White Pages directory for requests aimed at the AMS.
 pub trait ServiceConditions {
     /// Whether or not it is possible to search an agent in the internal directory;
-    fn search_condition(&self) -> bool {
+    fn search_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
     /// Whether or not it is possible to modify an agent registered in the internal directory;
-    fn modification_condition(&self) -> bool {
+    fn modification_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
     /// Whether or not it is possible to register an agent to the internal directory;
-    fn registration_condition(&self) -> bool {
+    fn registration_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
 
     /// Whether or not it is possible to deregister an agent from the internal directory;
-    fn deregistration_condition(&self) -> bool {
+    fn deregistration_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
+
+    /// Reason included in the [`MessageType::Refuse`] reply whenever a condition is not met.
+    ///  Returns [`Reason::Unauthorized`] by default.
+    fn refusal_reason(&self, _requester: &Description, _action: &ActionType) -> Reason {
+        Reason::Unauthorized
+    }
 }
 
 /// This trait defines a set of boolean functions whose purpose is to specify
 ///  under which conditions the AMS should provide its specific services:
-/// 
+///
 ///  - Suspension
 ///  - Resumption
 ///  - Termination
 ///  - Reset
+///  - Priority change
+///  - Mailbox capacity change
+///  - Ownership change
+///  - Role change
+///  - User property change
 ///
 /// This trait is a subtrait of [`ServiceConditions`]
 pub trait AmsConditions: ServiceConditions {
     /// Whether or not it is possible to suspend an agent. This is only doable by the AMS.
-    fn suspension_condition(&self) -> bool {
+    fn suspension_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
 
     /// Whether or not it is possible to resume an agent. This is only doable by the AMS.
-    fn resumption_condition(&self) -> bool {
+    fn resumption_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
 
     /// Whether or not it is possible to suspend an agent. This is only doable by the AMS.
-    fn termination_condition(&self) -> bool {
+    fn termination_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
 
     /// Whether or not it is possible to restart an agent, which means to terminate and relaunch the agent.
     ///  This is only doable by the AMS.
-    fn reset_condition(&self) -> bool {
+    fn reset_condition(&self, _requester: &Description, _target: &Description) -> bool {
+        true
+    }
+
+    /// Whether or not it is possible to change the priority of an agent. This is only doable by the AMS.
+    fn priority_condition(&self, _requester: &Description, _target: &Description) -> bool {
+        true
+    }
+
+    /// Whether or not it is possible to change the mailbox capacity of an agent. This is only doable by the AMS.
+    fn mailbox_condition(&self, _requester: &Description, _target: &Description) -> bool {
+        true
+    }
+
+    /// Whether or not it is possible to change the owner of an agent. This is only doable by the AMS.
+    fn ownership_condition(&self, _requester: &Description, _target: &Description) -> bool {
+        true
+    }
+
+    /// Whether or not it is possible to change the role of an agent. This is only doable by the AMS.
+    fn role_condition(&self, _requester: &Description, _target: &Description) -> bool {
+        true
+    }
+
+    /// Whether or not it is possible to set or remove user properties of an agent. This is only doable by the AMS.
+    fn property_condition(&self, _requester: &Description, _target: &Description) -> bool {
         true
     }
 }
 
 impl ServiceConditions for DefaultConditions {}
 impl AmsConditions for DefaultConditions {}
+
+/// Conditions that only allow an agent to be modified or deregistered by itself, by its owner or by a [`Role::Supervisor`].
+///  Searching and registering remain unrestricted, and roles can only be changed by supervisors.
+#[derive(Debug, Default)]
+pub struct OwnershipConditions;
+
+impl OwnershipConditions {
+    fn is_authorized(requester: &Description, target: &Description) -> bool {
+        requester == target
+            || requester.role() == Role::Supervisor
+            || target.owner().is_some_and(|owner| owner == *requester)
+    }
+}
+
+impl ServiceConditions for OwnershipConditions {
+    fn modification_condition(&self, requester: &Description, target: &Description) -> bool {
+        Self::is_authorized(requester, target)
+    }
+
+    fn deregistration_condition(&self, requester: &Description, target: &Description) -> bool {
+        Self::is_authorized(requester, target)
+    }
+}
+
+impl AmsConditions for OwnershipConditions {
+    fn role_condition(&self, requester: &Description, _target: &Description) -> bool {
+        requester.role() == Role::Supervisor
+    }
+}
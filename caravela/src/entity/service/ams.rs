@@ -6,14 +6,18 @@ use crate::{
         service::{AmsConditions, Service},
         Description, Hub,
     },
-    messaging::{ActionType, Content, Message, SyncType},
-    ErrorCode, Rx,
+    events::{self, Event},
+    messaging::{
+        ActionType, Content, Message, MessagePriority, ModifyAgent, Reason, StateOp, SyncType,
+    },
+    platform::agent_priority,
+    ErrorCode, Rx, JOIN_TIMEOUT,
 };
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 #[derive(Debug)]
 pub(crate) struct Ams<T: AmsConditions> {
-    hap: &'static str,
+    hap: Arc<str>,
     hub: Hub,
     conditions: T,
 }
@@ -31,18 +35,23 @@ impl<T: AmsConditions> Service for Ams<T> {
         deck().read().search_agent(aid)
     }
 
-    //fn modify_agent(&self, aid: &Description, modify: &ModifyAgent) -> Result<(), ErrorCode> {
-    fn modify_agent(&self, aid: &Description, modifier: &str) -> Result<(), ErrorCode> {
-        //if let ModifyAgent::State(state) = modify {
-        match modifier {
-            "resume" => self.resume_agent(aid),
-            "suspend" => self.suspend_agent(aid),
-            "terminate" => self.terminate_agent(aid),
-            x => Err(ErrorCode::InvalidContent(x.to_string())),
+    fn modify_agent(&self, aid: &Description, modify: &ModifyAgent) -> Result<(), ErrorCode> {
+        match modify {
+            ModifyAgent::State(StateOp::Resume) => self.resume_agent(aid),
+            ModifyAgent::State(StateOp::Suspend) => self.suspend_agent(aid),
+            ModifyAgent::State(StateOp::Terminate) => self.terminate_agent(aid),
+            ModifyAgent::Priority(priority) => self.change_priority(aid, *priority),
+            ModifyAgent::MailboxCapacity(capacity) => self.change_capacity(aid, *capacity),
+            ModifyAgent::Owner(owner) => self.change_owner(aid, owner),
+            ModifyAgent::Role(role) => deck().write().set_role(aid, *role),
+            ModifyAgent::Property(key, value) => {
+                deck().write().get_agent_mut(aid)?.set_property(key, value);
+                Ok(())
+            }
+            ModifyAgent::RemoveProperty(key) => {
+                deck().write().get_agent_mut(aid)?.remove_property(key)
+            }
         }
-        //} else {
-        //    Err(ErrorCode::InvalidContent)
-        //}
     }
 
     fn register_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
@@ -52,11 +61,12 @@ impl<T: AmsConditions> Service for Ams<T> {
     }
 
     fn deregister_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
-        let AgentEntry { join_handle, .. } = deck().write().remove_agent(aid)?;
-        join_handle.join().map_err(|_| ErrorCode::AgentPanic)
+        let AgentEntry { runner, .. } = deck().write().remove_agent(aid)?;
+        runner.join(JOIN_TIMEOUT)
     }
 
     fn service_function(&mut self) {
+        caravela_span!("service", "ams", &*self.hap);
         self.init();
         loop {
             caravela_messaging!("{}: Wating for a request...", self.name());
@@ -85,13 +95,14 @@ impl<T: AmsConditions> Service for Ams<T> {
             message_type,
             receiver
         );
-        let msg = Message::new(sender, receiver, message_type, content);
+        let msg = Message::new(sender, receiver, message_type, content)
+            .with_priority(MessagePriority::Control);
         self.hub.send(msg, SyncType::Blocking)
     }
 }
 
 impl<T: AmsConditions> Ams<T> {
-    pub(crate) fn new(hap: &'static str, rx: Rx, conditions: T) -> Self {
+    pub(crate) fn new(hap: Arc<str>, rx: Rx, conditions: T) -> Self {
         let hub = Hub::new(rx);
         Self {
             hap,
@@ -106,77 +117,150 @@ impl<T: AmsConditions> Ams<T> {
         if msg.message_type().clone() == MessageType::Request {
             caravela_messaging!("{}: Received Request!", self.name());
             if let Content::Action(request_type) = content.clone() {
-                if self.check_conditions(&request_type) {
-                    self.request_reply(receiver.clone(), MessageType::Agree, content.clone())?;
-                    let req_result = self.do_request(&request_type);
-                    match req_result {
-                        Ok(()) => {
-                            self.request_reply(receiver, MessageType::Inform, content)?;
-                        }
-                        Err(_) => {
-                            self.request_reply(receiver, MessageType::Refuse, content)?;
+                let outcome = match self.check_conditions(&receiver, &request_type) {
+                    Ok(()) => {
+                        // carried out even if the requester is gone, as a finished agent asks to be
+                        // deregistered without waiting for the answer
+                        let _ = self.request_reply(
+                            receiver.clone(),
+                            MessageType::Agree,
+                            content.clone(),
+                        );
+                        let req_result = self.do_request(&request_type);
+                        match req_result {
+                            Ok(()) => (MessageType::Inform, content),
+                            Err(error) => (
+                                MessageType::Failure,
+                                Content::Reason(request_type.clone(), error.into()),
+                            ),
                         }
                     }
-                } else {
-                    self.request_reply(receiver, MessageType::NotUnderstood, content)?;
+                    Err(reason) => (
+                        MessageType::Refuse,
+                        Content::Reason(request_type.clone(), reason),
+                    ),
                 };
+                let (message_type, content) = outcome;
+                caravela_metric!(ams_request(&request_type, &message_type));
+                events::emit(|| {
+                    Event::AmsRequestProcessed(receiver.clone(), request_type, message_type.clone())
+                });
+                self.request_reply(receiver, message_type, content)?;
+            } else {
+                self.request_reply(receiver, MessageType::NotUnderstood, content)?;
             };
         };
         Ok(())
     }
 
-    fn check_conditions(&self, action: &ActionType) -> bool {
-        match action {
-            ActionType::Search(_) => self.conditions.search_condition(),
-            ActionType::Modify(_, modifier) => {
-                self.conditions.modification_condition()
-                    && self.check_modification_conditions(modifier.to_lowercase().as_str())
+    fn check_conditions(&self, requester: &Description, action: &ActionType) -> Result<(), Reason> {
+        let conditions = &self.conditions;
+        let allowed = match action {
+            ActionType::Search(target) => conditions.search_condition(requester, target),
+            ActionType::Modify(target, modify) => {
+                conditions.modification_condition(requester, target)
+                    && self.check_modification_conditions(requester, target, modify)
             }
-            ActionType::Register(_) => self.conditions.registration_condition(),
-            ActionType::Deregister(_) => self.conditions.deregistration_condition(),
-            ActionType::Other(_) => unreachable!(),
-        }
+            ActionType::Register(target) => conditions.registration_condition(requester, target),
+            ActionType::Deregister(target) => {
+                conditions.deregistration_condition(requester, target)
+            }
+            ActionType::Organization(..) | ActionType::Sniffer(_) | ActionType::Other(_) => {
+                return Err(Reason::Unsupported)
+            }
+        };
+        allowed
+            .then_some(())
+            .ok_or_else(|| conditions.refusal_reason(requester, action))
     }
 
-    //fn check_modification_conditions(&self, modify: &ModifyAgent) -> bool {
-    fn check_modification_conditions(&self, modifier: &str) -> bool {
-        //if let ModifyAgent::State(state) = modify {
-        match modifier {
-            "resume" => self.conditions.resumption_condition(),
-            "suspend" => self.conditions.suspension_condition(),
-            "terminate" => self.conditions.termination_condition(),
-            _ => false,
+    fn check_modification_conditions(
+        &self,
+        requester: &Description,
+        target: &Description,
+        modify: &ModifyAgent,
+    ) -> bool {
+        let conditions = &self.conditions;
+        match modify {
+            ModifyAgent::State(StateOp::Resume) => {
+                conditions.resumption_condition(requester, target)
+            }
+            ModifyAgent::State(StateOp::Suspend) => {
+                conditions.suspension_condition(requester, target)
+            }
+            ModifyAgent::State(StateOp::Terminate) => {
+                conditions.termination_condition(requester, target)
+            }
+            ModifyAgent::Priority(_) => conditions.priority_condition(requester, target),
+            ModifyAgent::MailboxCapacity(_) => conditions.mailbox_condition(requester, target),
+            ModifyAgent::Owner(_) => conditions.ownership_condition(requester, target),
+            ModifyAgent::Role(_) => conditions.role_condition(requester, target),
+            ModifyAgent::Property(..) | ModifyAgent::RemoveProperty(_) => {
+                conditions.property_condition(requester, target)
+            }
         }
-        //} else {
-        //    false
-        //}
     }
 
     fn do_request(&self, request: &ActionType) -> Result<(), ErrorCode> {
         match request {
             ActionType::Search(aid) => self.search_agent(aid),
-            ActionType::Modify(aid, modifier) => {
-                self.modify_agent(aid, modifier.to_string().as_str())
-            }
+            ActionType::Modify(aid, modify) => self.modify_agent(aid, modify),
             ActionType::Register(aid) => self.register_agent(aid),
             ActionType::Deregister(aid) => self.deregister_agent(aid),
+            ActionType::Organization(..) | ActionType::Sniffer(_) => {
+                Err(ErrorCode::InvalidRequest(request.to_string()))
+            }
             ActionType::Other(x) => Err(ErrorCode::InvalidRequest(x.to_string())),
         }
     }
 
     pub(crate) fn terminate_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
-        deck().write().modify_agent(aid, AgentState::Terminated)?;
+        self.change_state(aid, AgentState::Terminated)?;
         self.deregister_agent(aid)
     }
 
     pub(crate) fn suspend_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
-        let deck_guard = deck().write();
-        deck_guard.modify_agent(aid, AgentState::Suspended)
+        self.change_state(aid, AgentState::Suspended)
     }
 
     pub(crate) fn resume_agent(&self, aid: &Description) -> Result<(), ErrorCode> {
-        let deck_guard = deck().write();
-        deck_guard.modify_agent(aid, AgentState::Active)
+        self.change_state(aid, AgentState::Active)
+    }
+
+    fn change_state(&self, aid: &Description, state: AgentState) -> Result<(), ErrorCode> {
+        let previous = deck().write().modify_agent(aid, state)?;
+        events::emit(|| Event::StateChanged(aid.clone(), previous, state));
+        Ok(())
+    }
+
+    pub(crate) fn change_priority(&self, aid: &Description, priority: u8) -> Result<(), ErrorCode> {
+        let thread_priority = agent_priority(priority)?;
+        deck()
+            .write()
+            .get_agent_mut(aid)?
+            .set_priority(priority, thread_priority);
+        Ok(())
+    }
+
+    pub(crate) fn change_capacity(
+        &self,
+        aid: &Description,
+        capacity: usize,
+    ) -> Result<(), ErrorCode> {
+        if capacity == 0 {
+            return Err(ErrorCode::InvalidCapacity(capacity));
+        }
+        deck().read().set_mailbox_capacity(aid, capacity)
+    }
+
+    pub(crate) fn change_owner(
+        &self,
+        aid: &Description,
+        owner: &Description,
+    ) -> Result<(), ErrorCode> {
+        let mut deck_guard = deck().write();
+        deck_guard.search_agent(owner)?;
+        deck_guard.set_owner(aid, owner.clone())
     }
 
     /*  pub(crate) fn restart_agent(&mut self, nickname: &str) {
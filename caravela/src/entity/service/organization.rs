@@ -1,116 +1,531 @@
-/*use crate::platform::agent::Description;
-use crate::platform::entity::messaging::MessageType;
-use crate::platform::ErrorCode;
+use crate::{
+    deck::deck,
+    entity::{
+        messaging::{ActionType, Content, Message, MessagePriority, MessageType, Reason, SyncType},
+        Description, Hub,
+    },
+    ErrorCode, Rx, MAX_SUBSCRIBERS,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::Arc,
+};
 
-use crate::platform::MAX_SUBSCRIBERS;
+/// Kinds of organizations, which determine what members are allowed to do.
+///
+/// - In a [`OrgType::Hierarchy`] only the owner, admins and moderators can invite agents or address the whole organization,
+///   and visitors can only address them.
+/// - In a [`OrgType::Team`] every member can invite agents and address the whole organization, except for visitors.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OrgType {
+    /// Organization with a chain of command.
+    Hierarchy,
+    /// Organization of peers.
+    Team,
+}
 
-#[derive(Clone, Copy)]
+/// Standing of an agent within an organization.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum OrgAffiliation {
+    /// The agent that opened the organization, or was later given its ownership.
     Owner,
+    /// A member allowed to manage the organization.
     Admin,
+    /// A regular member.
     Member,
+    /// The agent does not belong to the organization.
     NonMember,
 }
 
-#[derive(Clone, Copy)]
+/// Roles members can play within an organization.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum OrgRole {
+    /// A member allowed to invite, admit, kick and ban other members with less authority.
     Moderator,
+    /// A member allowed to take part in the organization. Default role of new members.
     Participant,
+    /// A member that can only listen and address the members in charge.
     Visitor,
+    /// The agent does not belong to the organization.
     NoRole,
 }
 
-#[derive(Clone, Copy)]
-pub enum OrgType {
-    Hierarchy,
-    Team,
+/// Requests that can be made to the organization service through [`ActionType::Organization`].
+///
+/// Invited agents are sent a [`MessageType::Propose`] with the invitation, and become members once they send a [`OrgAction::Join`] request.
+///  Agents that ask to join without an invitation become members once they are admitted with [`OrgAction::Admit`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum OrgAction {
+    /// Open a new organization owned by the requester.
+    Open(OrgType),
+    /// Close the organization. Only doable by the owner.
+    Close,
+    /// Invite an agent to the organization.
+    Invite(Description),
+    /// Join the organization, either by accepting an invitation or by applying to it.
+    Join,
+    /// Admit an agent that applied to join the organization.
+    Admit(Description),
+    /// Leave the organization. The owner cannot leave it.
+    Leave,
+    /// Remove a member from the organization.
+    Kick(Description),
+    /// Remove an agent from the organization and prevent it from joining or addressing its members.
+    Ban(Description),
+    /// Allow a banned agent to join and address the organization again.
+    LiftBan(Description),
+    /// Set the role of a member.
+    SetRole(Description, OrgRole),
+    /// Set the affiliation of a member. Only doable by the owner on another member, and the owner becomes an admin if ownership is given away.
+    SetAffiliation(Description, OrgAffiliation),
 }
 
-#[derive(Clone)]
-pub struct OrgInfo<'a> {
-    pub org_type: OrgType,
-    pub members: Vec<&'a Description>,
-    pub banned: Vec<&'a Description>,
-    pub owner: Option<&'a Description>,
-    pub admin: Option<&'a Description>,
-    pub moderator: Option<&'a Description>,
+impl Display for OrgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgType::Hierarchy => write!(f, "Hierarchy"),
+            OrgType::Team => write!(f, "Team"),
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct Organization<'a> {
-    info: OrgInfo<'a>,
+impl Display for OrgAffiliation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgAffiliation::Owner => write!(f, "Owner"),
+            OrgAffiliation::Admin => write!(f, "Admin"),
+            OrgAffiliation::Member => write!(f, "Member"),
+            OrgAffiliation::NonMember => write!(f, "NonMember"),
+        }
+    }
 }
 
-pub fn new<'a>(org_type: OrgType) -> Organization<'a> {
-    let info = OrgInfo {
-        org_type,
-        members: Vec::<&'a Description>::with_capacity(MAX_SUBSCRIBERS),
-        banned: Vec::<&'a Description>::with_capacity(MAX_SUBSCRIBERS),
-        owner: None,
-        admin: None,
-        moderator: None,
-    };
-    Organization { info }
+impl Display for OrgRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgRole::Moderator => write!(f, "Moderator"),
+            OrgRole::Participant => write!(f, "Participant"),
+            OrgRole::Visitor => write!(f, "Visitor"),
+            OrgRole::NoRole => write!(f, "NoRole"),
+        }
+    }
 }
 
-impl<'a> Organization<'a> {
-    /*fn open(&self) -> ErrorCode {
-        if current().id() ==  {
-            return ErrorCode::Invalid;
+impl Display for OrgAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrgAction::Open(x) => write!(f, "Open {}", x),
+            OrgAction::Close => write!(f, "Close"),
+            OrgAction::Invite(x) => write!(f, "Invite {}", x),
+            OrgAction::Join => write!(f, "Join"),
+            OrgAction::Admit(x) => write!(f, "Admit {}", x),
+            OrgAction::Leave => write!(f, "Leave"),
+            OrgAction::Kick(x) => write!(f, "Kick {}", x),
+            OrgAction::Ban(x) => write!(f, "Ban {}", x),
+            OrgAction::LiftBan(x) => write!(f, "Lift Ban {}", x),
+            OrgAction::SetRole(x, role) => write!(f, "Set Role {} {}", x, role),
+            OrgAction::SetAffiliation(x, affiliation) => {
+                write!(f, "Set Affiliation {} {}", x, affiliation)
+            }
         }
-        else if self.info.owner == None {
-            return ErrorCode::NoError;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Membership {
+    affiliation: OrgAffiliation,
+    role: OrgRole,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Organization {
+    org_type: OrgType,
+    members: HashMap<Description, Membership>,
+    banned: HashSet<Description>,
+    invited: HashSet<Description>,
+    applicants: HashSet<Description>,
+}
+
+impl Organization {
+    pub(crate) fn new(org_type: OrgType, owner: Description) -> Self {
+        let mut members = HashMap::with_capacity(MAX_SUBSCRIBERS);
+        let membership = Membership {
+            affiliation: OrgAffiliation::Owner,
+            role: OrgRole::Moderator,
+        };
+        members.insert(owner, membership);
+        Self {
+            org_type,
+            members,
+            banned: HashSet::new(),
+            invited: HashSet::new(),
+            applicants: HashSet::new(),
         }
-        else {
-            return ErrorCode::Invalid;
+    }
+
+    pub(crate) fn affiliation(&self, aid: &Description) -> OrgAffiliation {
+        self.members
+            .get(aid)
+            .map_or(OrgAffiliation::NonMember, |x| x.affiliation)
+    }
+
+    pub(crate) fn role(&self, aid: &Description) -> OrgRole {
+        self.members.get(aid).map_or(OrgRole::NoRole, |x| x.role)
+    }
+
+    pub(crate) fn is_member(&self, aid: &Description) -> bool {
+        self.members.contains_key(aid)
+    }
+
+    pub(crate) fn is_banned(&self, aid: &Description) -> bool {
+        self.banned.contains(aid)
+    }
+
+    /// Rank used to decide if an agent can act upon another:
+    ///  owner, admins, moderators, members and non-members from highest to lowest.
+    fn authority(&self, aid: &Description) -> u8 {
+        match (self.affiliation(aid), self.role(aid)) {
+            (OrgAffiliation::Owner, _) => 4,
+            (OrgAffiliation::Admin, _) => 3,
+            (OrgAffiliation::Member, OrgRole::Moderator) => 2,
+            (OrgAffiliation::Member, _) => 1,
+            (OrgAffiliation::NonMember, _) => 0,
         }
-    }*/
-    //AFTER WHITE PAGES DONE
-    /*
-        fn close() -> ErrorCode {}
+    }
+
+    fn is_manager(&self, aid: &Description) -> bool {
+        self.authority(aid) >= 2
+    }
 
-        fn add_member(target: AID) -> ErrorCode {}
-        fn invite_member(target: AID) -> MessageType {}
-        fn kick_member(target: AID) -> ErrorCode {}
-        fn ban_member(target: AID) -> ErrorCode {}
+    fn can_address_all(&self, aid: &Description) -> bool {
+        match self.org_type {
+            OrgType::Hierarchy => self.is_manager(aid),
+            OrgType::Team => self.is_member(aid) && self.role(aid) != OrgRole::Visitor,
+        }
+    }
 
-        fn change_owner(target: AID) -> ErrorCode {}
+    /// Check whether `sender` is allowed to address `receiver` according to this organization.
+    pub(crate) fn check_visibility(
+        &self,
+        sender: &Description,
+        receiver: &Description,
+    ) -> Result<(), ErrorCode> {
+        if !self.is_member(receiver) {
+            Ok(())
+        } else if self.is_banned(sender) {
+            Err(ErrorCode::Banned)
+        } else if self.org_type == OrgType::Hierarchy
+            && self.role(sender) == OrgRole::Visitor
+            && !self.is_manager(receiver)
+        {
+            Err(ErrorCode::NotVisible)
+        } else {
+            Ok(())
+        }
+    }
 
-        fn set_admin(target: AID) -> ErrorCode {}
-        fn set_moderator(target: AID) -> ErrorCode {}
-        fn set_participant(target: AID) -> ErrorCode {}
-        fn set_visitor(target: AID) -> ErrorCode {}
+    /// Members that `sender` can address at once, optionally filtered by role.
+    pub(crate) fn recipients(
+        &self,
+        sender: &Description,
+        role: Option<OrgRole>,
+    ) -> Result<Vec<Description>, ErrorCode> {
+        if !self.is_member(sender) {
+            return Err(ErrorCode::NotMember);
+        }
+        if !self.can_address_all(sender) {
+            return Err(ErrorCode::NotVisible);
+        }
+        Ok(self
+            .members
+            .iter()
+            .filter(|(aid, membership)| {
+                *aid != sender && role.is_none_or(|role| membership.role == role)
+            })
+            .map(|(aid, _)| aid.clone())
+            .collect())
+    }
 
-        fn lift_ban(target: AID) -> ErrorCode {}
-        fn clear_ban_list() -> ErrorCode {}
+    /// Check whether `requester` is allowed to perform `action`.
+    fn check_permission(&self, requester: &Description, action: &OrgAction) -> Result<(), Reason> {
+        let authority = self.authority(requester);
+        let allowed = match action {
+            OrgAction::Open(_) => false,
+            OrgAction::Close => self.affiliation(requester) == OrgAffiliation::Owner,
+            OrgAction::Invite(_) => match self.org_type {
+                OrgType::Hierarchy => self.is_manager(requester),
+                OrgType::Team => self.can_address_all(requester),
+            },
+            OrgAction::Join => {
+                if self.is_banned(requester) {
+                    return Err(Reason::Banned);
+                }
+                true
+            }
+            OrgAction::Admit(_) | OrgAction::LiftBan(_) => self.is_manager(requester),
+            OrgAction::Leave => {
+                if !self.is_member(requester) {
+                    return Err(Reason::NotMember);
+                }
+                self.affiliation(requester) != OrgAffiliation::Owner
+            }
+            OrgAction::Kick(target) | OrgAction::Ban(target) => {
+                self.is_manager(requester) && self.authority(target) < authority
+            }
+            OrgAction::SetRole(target, _) => authority >= 3 && self.authority(target) < authority,
+            OrgAction::SetAffiliation(..) => self.affiliation(requester) == OrgAffiliation::Owner,
+        };
+        allowed.then_some(()).ok_or(Reason::Unauthorized)
+    }
 
-        fn get_size(&self) -> usize {
-            self.info.members.len()
+    fn admit(&mut self, aid: &Description) -> Result<(), ErrorCode> {
+        if self.is_member(aid) {
+            return Err(ErrorCode::Duplicated);
         }
-        fn get_info(&self) -> OrgInfo {
-            self.info
+        if self.members.len() == MAX_SUBSCRIBERS {
+            return Err(ErrorCode::ListFull);
         }
-        fn get_org_type(&self) -> OrgType {
-            self.info.org_type
+        self.invited.remove(aid);
+        self.applicants.remove(aid);
+        let membership = Membership {
+            affiliation: OrgAffiliation::Member,
+            role: OrgRole::Participant,
+        };
+        self.members.insert(aid.clone(), membership);
+        Ok(())
+    }
+
+    fn membership_mut(&mut self, aid: &Description) -> Result<&mut Membership, ErrorCode> {
+        self.members.get_mut(aid).ok_or(ErrorCode::NotMember)
+    }
+
+    /// Remove an agent from every list of the organization.
+    ///  Returns `true` if the agent was the owner, in which case the organization should be closed.
+    pub(crate) fn purge(&mut self, aid: &Description) -> bool {
+        self.invited.remove(aid);
+        self.applicants.remove(aid);
+        self.banned.remove(aid);
+        self.members
+            .remove(aid)
+            .is_some_and(|x| x.affiliation == OrgAffiliation::Owner)
+    }
+}
+
+/// Service entity that manages the organizations of the platform.
+#[derive(Debug)]
+pub(crate) struct OrgService {
+    hap: Arc<str>,
+    hub: Hub,
+}
+
+impl OrgService {
+    pub(crate) fn new(hap: Arc<str>, rx: Rx) -> Self {
+        let hub = Hub::new(rx);
+        Self { hap, hub }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        format!("org@{}", self.hap)
+    }
+
+    pub(crate) fn service_function(&mut self) {
+        caravela_span!("service", "org", &*self.hap);
+        caravela_status!("{}: Started!", self.name());
+        loop {
+            caravela_messaging!("{}: Wating for a request...", self.name());
+            let msg_result = self.hub.receive();
+            if let Ok(msg) = msg_result {
+                if self.process_request(msg).is_err() {
+                    //TBD handle these possible errors;
+                }
+            } else {
+                //TBD handle these possible errors;
+            }
         }
+    }
 
-        fn is_member(&self, target:&AgentInfoDescription<'_> ) -> ErrorCode {
-            if self.info.members.contains(&target) {
-                return ErrorCode::Found;
+    /// Reply to a requester in the control lane of its mailbox, which is never full so no request waits behind it.
+    fn request_reply(
+        &self,
+        receiver: Description,
+        message_type: MessageType,
+        content: Content,
+    ) -> Result<(), ErrorCode> {
+        let sender = deck().read().org_aid()?.clone();
+        caravela_messaging!(
+            "{}: Replying with {} to {}",
+            self.name(),
+            message_type,
+            receiver
+        );
+        let msg = Message::new(sender, receiver, message_type, content)
+            .with_priority(MessagePriority::Control);
+        self.hub.send(msg, SyncType::NonBlocking)
+    }
+
+    /// Invite an agent with a message that fails rather than block the service if its mailbox is full.
+    fn invite(&self, target: &Description, org: &str) -> Result<(), ErrorCode> {
+        let sender = deck().read().org_aid()?.clone();
+        let invitation = ActionType::Organization(org.to_string(), OrgAction::Join);
+        let msg = Message::new(
+            sender,
+            target.clone(),
+            MessageType::Propose,
+            Content::Action(invitation),
+        );
+        self.hub.send(msg, SyncType::NonBlocking)
+    }
+
+    fn process_request(&self, msg: Message) -> Result<(), ErrorCode> {
+        let receiver = msg.sender().clone();
+        let content = msg.content().clone();
+        if msg.message_type().clone() != MessageType::Request {
+            return Ok(());
+        }
+        let Content::Action(ActionType::Organization(org, action)) = content.clone() else {
+            return self.request_reply(receiver, MessageType::NotUnderstood, content);
+        };
+        let request_type = ActionType::Organization(org.clone(), action.clone());
+        match self.check_permission(&receiver, &org, &action) {
+            Ok(()) => {
+                self.request_reply(receiver.clone(), MessageType::Agree, content.clone())?;
+                match self.do_request(&receiver, &org, &action) {
+                    Ok(()) => self.request_reply(receiver, MessageType::Inform, content),
+                    Err(error) => {
+                        let reason = Content::Reason(request_type, error.into());
+                        self.request_reply(receiver, MessageType::Failure, reason)
+                    }
+                }
             }
-            else {
-                return ErrorCode::NotFound;
+            Err(reason) => {
+                let reason = Content::Reason(request_type, reason);
+                self.request_reply(receiver, MessageType::Refuse, reason)
             }
         }
+    }
 
-        fn is_banned(&self, target:&AgentInfoDescription<'_> ) -> ErrorCode {
-            if self.info.banned.contains(&target) {
-                return ErrorCode::Found;
+    fn check_permission(
+        &self,
+        requester: &Description,
+        org: &str,
+        action: &OrgAction,
+    ) -> Result<(), Reason> {
+        let deck_guard = deck().read();
+        match (deck_guard.get_org(org), action) {
+            (Err(_), OrgAction::Open(_)) => Ok(()),
+            (Ok(_), OrgAction::Open(_)) => Err(Reason::Other(format!("{} is already open", org))),
+            (Err(error), _) => Err(error.into()),
+            (Ok(organization), action) => organization.check_permission(requester, action),
+        }
+    }
+
+    fn do_request(
+        &self,
+        requester: &Description,
+        org: &str,
+        action: &OrgAction,
+    ) -> Result<(), ErrorCode> {
+        if let OrgAction::Open(org_type) = action {
+            let organization = Organization::new(*org_type, requester.clone());
+            return deck().write().open_org(org, organization);
+        }
+        if let OrgAction::Close = action {
+            return deck().write().close_org(org);
+        }
+        let mut deck_guard = deck().write();
+        if let OrgAction::Invite(target) = action {
+            deck_guard.search_agent(target)?;
+        }
+        let organization = deck_guard.get_org_mut(org)?;
+        match action {
+            OrgAction::Invite(target) => {
+                if organization.is_banned(target) {
+                    return Err(ErrorCode::Banned);
+                }
+                if organization.applicants.contains(target) {
+                    return organization.admit(target);
+                }
+                if organization.is_member(target) {
+                    return Err(ErrorCode::Duplicated);
+                }
+                organization.invited.insert(target.clone());
+                drop(deck_guard);
+                self.invite(target, org).inspect_err(|_| {
+                    // an invitation that never arrived cannot be accepted
+                    if let Ok(organization) = deck().write().get_org_mut(org) {
+                        organization.invited.remove(target);
+                    }
+                })
+            }
+            OrgAction::Join => {
+                if organization.invited.contains(requester) {
+                    organization.admit(requester)
+                } else if organization.is_member(requester) {
+                    Err(ErrorCode::Duplicated)
+                } else {
+                    organization.applicants.insert(requester.clone());
+                    Ok(())
+                }
+            }
+            OrgAction::Admit(target) => {
+                if organization.applicants.contains(target) {
+                    organization.admit(target)
+                } else {
+                    Err(ErrorCode::NotFound)
+                }
+            }
+            OrgAction::Leave | OrgAction::Kick(_) => {
+                let target = match action {
+                    OrgAction::Kick(target) => target,
+                    _ => requester,
+                };
+                organization
+                    .members
+                    .remove(target)
+                    .map(|_| ())
+                    .ok_or(ErrorCode::NotMember)
+            }
+            OrgAction::Ban(target) => {
+                organization.purge(target);
+                organization.banned.insert(target.clone());
+                Ok(())
+            }
+            OrgAction::LiftBan(target) => organization
+                .banned
+                .remove(target)
+                .then_some(())
+                .ok_or(ErrorCode::NotFound),
+            OrgAction::SetRole(target, role) => {
+                if *role == OrgRole::NoRole {
+                    return Err(ErrorCode::InvalidRequest(role.to_string()));
+                }
+                organization.membership_mut(target)?.role = *role;
+                Ok(())
             }
-            else {
-                return ErrorCode::NotFound;
+            OrgAction::SetAffiliation(target, affiliation) => {
+                // the organization would be left without an owner
+                if target == requester {
+                    return Err(ErrorCode::InvalidRequest(affiliation.to_string()));
+                }
+                match affiliation {
+                    OrgAffiliation::NonMember => organization
+                        .members
+                        .remove(target)
+                        .map(|_| ())
+                        .ok_or(ErrorCode::NotMember),
+                    OrgAffiliation::Owner => {
+                        organization.membership_mut(target)?.affiliation = OrgAffiliation::Owner;
+                        organization.membership_mut(requester)?.affiliation = OrgAffiliation::Admin;
+                        Ok(())
+                    }
+                    x => {
+                        organization.membership_mut(target)?.affiliation = *x;
+                        Ok(())
+                    }
+                }
             }
+            OrgAction::Open(_) | OrgAction::Close => unreachable!(),
         }
-    */
+    }
 }
-*/
\ No newline at end of file
@@ -0,0 +1,349 @@
+use crate::{
+    deck::deck,
+    entity::{
+        messaging::{ActionType, Content, Message, MessageType, Reason, SyncType},
+        Description, Hub,
+    },
+    utils::json_string,
+    ErrorCode, Rx, TRACE_CAPACITY,
+};
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Whether or not any agent is being sniffed, so messaging only looks for the sniffer when needed.
+static SNIFFING: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn is_active() -> bool {
+    SNIFFING.load(Ordering::Acquire)
+}
+
+pub(crate) fn set_active(active: bool) {
+    SNIFFING.store(active, Ordering::Release);
+}
+
+/// Requests that can be aimed toward the sniffer through [`ActionType::Sniffer`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SniffAction {
+    /// Start copying the messages sent and received by the agent to the sniffer.
+    Sniff(Description),
+    /// Stop copying the messages of the agent.
+    Unsniff(Description),
+}
+
+impl Display for SniffAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SniffAction::Sniff(x) => write!(f, "Sniff {}", x),
+            SniffAction::Unsniff(x) => write!(f, "Unsniff {}", x),
+        }
+    }
+}
+
+/// Point of the messaging where a sniffed message was captured.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Capture {
+    /// The message was placed in the mailbox of its receiver.
+    Sent,
+    /// The message was taken from the mailbox by its receiver.
+    Received,
+}
+
+impl Display for Capture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Capture::Sent => write!(f, "sent"),
+            Capture::Received => write!(f, "received"),
+        }
+    }
+}
+
+/// A copy of a message exchanged by a sniffed agent.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SniffedMessage {
+    timestamp: SystemTime,
+    capture: Capture,
+    message: Message,
+}
+
+impl SniffedMessage {
+    /// Get the time at which the message was captured.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+    /// Get whether the message was captured when sent or when received.
+    pub fn capture(&self) -> Capture {
+        self.capture
+    }
+    /// Get the captured message.
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+    fn micros(&self) -> u128 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_micros())
+            .unwrap_or_default()
+    }
+}
+
+/// Messages captured by the sniffer in chronological order, up to [`TRACE_CAPACITY`].
+#[derive(Debug, Default)]
+pub(crate) struct Trace(Mutex<VecDeque<SniffedMessage>>);
+
+pub(crate) type TraceArc = Arc<Trace>;
+
+impl Trace {
+    fn lock(&self) -> MutexGuard<'_, VecDeque<SniffedMessage>> {
+        self.0.lock().expect("Sniffer trace is poisoned")
+    }
+    pub(crate) fn record(&self, capture: Capture, message: Message) {
+        self.record_at(SystemTime::now(), capture, message)
+    }
+    /// Record a message captured at an earlier time, keeping the trace in the order of the timestamps.
+    ///  Captures are mostly recorded in order, so the message is placed close to the end of the trace.
+    pub(crate) fn record_at(&self, timestamp: SystemTime, capture: Capture, message: Message) {
+        let mut trace = self.lock();
+        let mut index = trace.partition_point(|x| x.timestamp <= timestamp);
+        if trace.len() >= TRACE_CAPACITY {
+            if index == 0 {
+                // older than anything kept
+                return;
+            }
+            trace.pop_front();
+            index -= 1;
+        }
+        trace.insert(
+            index,
+            SniffedMessage {
+                timestamp,
+                capture,
+                message,
+            },
+        );
+    }
+    /// Remove a capture recorded ahead of a delivery that then failed.
+    pub(crate) fn retract(&self, timestamp: SystemTime, capture: Capture, message: &Message) {
+        let mut trace = self.lock();
+        let end = trace.partition_point(|x| x.timestamp <= timestamp);
+        let start = trace.partition_point(|x| x.timestamp < timestamp);
+        if let Some(index) = (start..end).rev().find(|index| {
+            let x = &trace[*index];
+            x.capture == capture && x.message == *message
+        }) {
+            trace.remove(index);
+        }
+    }
+}
+
+/// Handle to the sniffer of the platform, used to choose the sniffed agents and to export the captured messages.
+#[derive(Clone, Debug)]
+pub struct Sniffer {
+    aid: Description,
+    trace: TraceArc,
+}
+
+impl Sniffer {
+    pub(crate) fn new(aid: Description, trace: TraceArc) -> Self {
+        Self { aid, trace }
+    }
+    /// Get the [`Description`] of the sniffer service.
+    pub fn aid(&self) -> &Description {
+        &self.aid
+    }
+    /// Start copying the messages sent and received by the agent.
+    pub fn sniff(&self, aid: &Description) -> Result<(), ErrorCode> {
+        deck().write().sniff(aid)
+    }
+    /// Stop copying the messages sent and received by the agent.
+    pub fn unsniff(&self, aid: &Description) -> Result<(), ErrorCode> {
+        deck().write().unsniff(aid)
+    }
+    /// Get a copy of the messages captured so far, the latest [`TRACE_CAPACITY`] of them.
+    pub fn trace(&self) -> Vec<SniffedMessage> {
+        self.trace.lock().iter().cloned().collect()
+    }
+    /// Discard the messages captured so far.
+    pub fn clear(&self) {
+        self.trace.lock().clear();
+    }
+
+    /// Write the sent messages as a PlantUML sequence diagram.
+    pub fn write_plantuml(&self, writer: &mut impl Write) -> io::Result<()> {
+        let trace = self.trace();
+        writeln!(writer, "@startuml")?;
+        for participant in participants(&trace) {
+            writeln!(writer, "participant \"{}\"", participant)?;
+        }
+        for sniffed in trace.iter().filter(|x| x.capture == Capture::Sent) {
+            let msg = sniffed.message();
+            writeln!(
+                writer,
+                "\"{}\" -> \"{}\" : {} ({})",
+                msg.sender(),
+                msg.receiver(),
+                msg.message_type(),
+                escape_line(&msg.content().to_string())
+            )?;
+        }
+        writeln!(writer, "@enduml")
+    }
+
+    /// Write the sent messages as a Mermaid sequence diagram.
+    pub fn write_mermaid(&self, writer: &mut impl Write) -> io::Result<()> {
+        let trace = self.trace();
+        let participants = participants(&trace);
+        let alias = |aid: &Description| {
+            let name = aid.to_string();
+            participants
+                .iter()
+                .position(|x| *x == name)
+                .unwrap_or_default()
+        };
+        writeln!(writer, "sequenceDiagram")?;
+        for (index, name) in participants.iter().enumerate() {
+            writeln!(writer, "    participant p{} as {}", index, name)?;
+        }
+        for sniffed in trace.iter().filter(|x| x.capture == Capture::Sent) {
+            let msg = sniffed.message();
+            writeln!(
+                writer,
+                "    p{}->>p{}: {} ({})",
+                alias(msg.sender()),
+                alias(msg.receiver()),
+                msg.message_type(),
+                escape_mermaid(&msg.content().to_string())
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write every captured message as a JSON object per line, with its timestamp in microseconds since the UNIX epoch.
+    pub fn write_json_lines(&self, writer: &mut impl Write) -> io::Result<()> {
+        for sniffed in self.trace() {
+            let msg = sniffed.message();
+            writeln!(
+                writer,
+                "{{\"timestamp_us\":{},\"capture\":\"{}\",\"sender\":{},\"receiver\":{},\"performative\":{},\"content\":{}}}",
+                sniffed.micros(),
+                sniffed.capture(),
+                json_string(&msg.sender().to_string()),
+                json_string(&msg.receiver().to_string()),
+                json_string(&msg.message_type().to_string()),
+                json_string(&msg.content().to_string())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the agents present in the trace, in order of appearance.
+fn participants(trace: &[SniffedMessage]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for msg in trace.iter().map(|x| x.message()) {
+        for name in [msg.sender().to_string(), msg.receiver().to_string()] {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+fn escape_line(text: &str) -> String {
+    text.replace('\n', "\\n")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace(';', "#59;")
+        .replace('\n', "<br/>")
+}
+
+/// Service entity that handles the sniffing requests of the agents.
+#[derive(Debug)]
+pub(crate) struct SnifferService {
+    hap: Arc<str>,
+    hub: Hub,
+}
+
+impl SnifferService {
+    pub(crate) fn new(hap: Arc<str>, rx: Rx) -> Self {
+        let hub = Hub::new(rx);
+        Self { hap, hub }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        format!("sniffer@{}", self.hap)
+    }
+
+    pub(crate) fn service_function(&mut self) {
+        caravela_span!("service", "sniffer", &*self.hap);
+        caravela_status!("{}: Started!", self.name());
+        loop {
+            caravela_messaging!("{}: Wating for a request...", self.name());
+            let msg_result = self.hub.receive();
+            if let Ok(msg) = msg_result {
+                if self.process_request(msg).is_err() {
+                    //TBD handle these possible errors;
+                }
+            } else {
+                //TBD handle these possible errors;
+            }
+        }
+    }
+
+    fn request_reply(
+        &self,
+        receiver: Description,
+        message_type: MessageType,
+        content: Content,
+    ) -> Result<(), ErrorCode> {
+        let sender = deck().read().sniffer_aid()?.clone();
+        caravela_messaging!(
+            "{}: Replying with {} to {}",
+            self.name(),
+            message_type,
+            receiver
+        );
+        let msg = Message::new(sender, receiver, message_type, content);
+        self.hub.send(msg, SyncType::Blocking)
+    }
+
+    fn process_request(&self, msg: Message) -> Result<(), ErrorCode> {
+        let receiver = msg.sender().clone();
+        let content = msg.content().clone();
+        if msg.message_type().clone() != MessageType::Request {
+            return Ok(());
+        }
+        let Content::Action(ActionType::Sniffer(action)) = content.clone() else {
+            return self.request_reply(receiver, MessageType::NotUnderstood, content);
+        };
+        let target = match &action {
+            SniffAction::Sniff(x) | SniffAction::Unsniff(x) => x,
+        };
+        if deck().read().search_agent(target).is_err() {
+            let reason = Content::Reason(ActionType::Sniffer(action), Reason::NotRegistered);
+            return self.request_reply(receiver, MessageType::Refuse, reason);
+        }
+        self.request_reply(receiver.clone(), MessageType::Agree, content.clone())?;
+        let result = match &action {
+            SniffAction::Sniff(x) => deck().write().sniff(x),
+            SniffAction::Unsniff(x) => deck().write().unsniff(x),
+        };
+        match result {
+            Ok(()) => self.request_reply(receiver, MessageType::Inform, content),
+            Err(error) => {
+                let reason = Content::Reason(ActionType::Sniffer(action), error.into());
+                self.request_reply(receiver, MessageType::Failure, reason)
+            }
+        }
+    }
+}
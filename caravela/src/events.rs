@@ -0,0 +1,177 @@
+use crate::{
+    agent::AgentState,
+    messaging::{ActionType, Message, MessageType, Reason},
+    Description,
+};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        RwLock,
+    },
+};
+
+/// Identifier returned when subscribing to platform events, used to unsubscribe.
+pub type SubscriptionId = usize;
+
+type Callback = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// Observable events of the platform lifecycle and messaging.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The agent was added to the platform.
+    AgentAdded(Description),
+    /// The agent was started by the platform.
+    AgentStarted(Description),
+    /// The agent changed from one [`AgentState`] to another.
+    StateChanged(Description, AgentState, AgentState),
+    /// The agent was halted after being suspended.
+    AgentSuspended(Description),
+    /// The agent resumed its behavior after being suspended.
+    AgentResumed(Description),
+    /// The agent finished its behavior.
+    AgentTerminated(Description),
+    /// The agent panicked while executing its behavior.
+    AgentPanicked(Description),
+    /// A message was placed in the mailbox of its receiver.
+    MessageSent(Message),
+    /// A message was taken from the mailbox by its receiver.
+    MessageDelivered(Message),
+    /// A message could not be placed in the mailbox of its receiver.
+    MessageDropped(Message, Reason),
+    /// The AMS processed a request from an agent and replied with the given [`MessageType`].
+    AmsRequestProcessed(Description, ActionType, MessageType),
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::AgentAdded(x) => write!(f, "{}: Added", x),
+            Event::AgentStarted(x) => write!(f, "{}: Started", x),
+            Event::StateChanged(x, old, new) => write!(f, "{}: {} -> {}", x, old, new),
+            Event::AgentSuspended(x) => write!(f, "{}: Suspended", x),
+            Event::AgentResumed(x) => write!(f, "{}: Resumed", x),
+            Event::AgentTerminated(x) => write!(f, "{}: Terminated", x),
+            Event::AgentPanicked(x) => write!(f, "{}: Panicked", x),
+            Event::MessageSent(x) => {
+                write!(
+                    f,
+                    "{}: Sent {} to {}",
+                    x.sender(),
+                    x.message_type(),
+                    x.receiver()
+                )
+            }
+            Event::MessageDelivered(x) => write!(
+                f,
+                "{}: Delivered {} from {}",
+                x.receiver(),
+                x.message_type(),
+                x.sender()
+            ),
+            Event::MessageDropped(x, reason) => write!(
+                f,
+                "{}: Dropped {} to {} ({})",
+                x.sender(),
+                x.message_type(),
+                x.receiver(),
+                reason
+            ),
+            Event::AmsRequestProcessed(x, action, outcome) => {
+                write!(f, "{}: {} answered with {}", x, action, outcome)
+            }
+        }
+    }
+}
+
+enum Subscriber {
+    Callback(Callback),
+    Channel(Sender<Event>),
+}
+
+struct EventBus {
+    active: AtomicBool,
+    next_id: AtomicUsize,
+    subscribers: RwLock<Vec<(SubscriptionId, Subscriber)>>,
+}
+
+static BUS: EventBus = EventBus {
+    active: AtomicBool::new(false),
+    next_id: AtomicUsize::new(0),
+    subscribers: RwLock::new(Vec::new()),
+};
+
+impl EventBus {
+    fn add(&self, subscriber: Subscriber) -> SubscriptionId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut subscribers = self
+            .subscribers
+            .write()
+            .expect("Event bus is poisoned - Lost subscribers");
+        subscribers.push((id, subscriber));
+        self.active.store(true, Ordering::Release);
+        id
+    }
+
+    fn remove(&self, ids: &[SubscriptionId]) {
+        let mut subscribers = self
+            .subscribers
+            .write()
+            .expect("Event bus is poisoned - Lost subscribers");
+        subscribers.retain(|(id, _)| !ids.contains(id));
+        self.active
+            .store(!subscribers.is_empty(), Ordering::Release);
+    }
+
+    fn publish(&self, event: Event) {
+        let mut disconnected = Vec::new();
+        {
+            let subscribers = self
+                .subscribers
+                .read()
+                .expect("Event bus is poisoned - Lost subscribers");
+            for (id, subscriber) in subscribers.iter() {
+                match subscriber {
+                    Subscriber::Callback(callback) => callback(&event),
+                    Subscriber::Channel(tx) => {
+                        if tx.send(event.clone()).is_err() {
+                            disconnected.push(*id);
+                        }
+                    }
+                }
+            }
+        }
+        if !disconnected.is_empty() {
+            self.remove(&disconnected);
+        }
+    }
+}
+
+/// Whether or not there is anyone listening to platform events.
+pub(crate) fn is_active() -> bool {
+    BUS.active.load(Ordering::Acquire)
+}
+
+/// Publish the event built by `event` only if there are subscribers, so it costs a single atomic load otherwise.
+pub(crate) fn emit(event: impl FnOnce() -> Event) {
+    if is_active() {
+        BUS.publish(event());
+    }
+}
+
+/// Register a callback that is run on the thread producing each event.
+///  Callbacks must not subscribe or unsubscribe themselves.
+pub(crate) fn subscribe(callback: impl Fn(&Event) + Send + Sync + 'static) -> SubscriptionId {
+    BUS.add(Subscriber::Callback(Box::new(callback)))
+}
+
+/// Register a channel that receives a copy of each event. It is unsubscribed once the receiver is dropped.
+pub(crate) fn subscribe_channel() -> (SubscriptionId, Receiver<Event>) {
+    let (tx, rx) = channel();
+    (BUS.add(Subscriber::Channel(tx)), rx)
+}
+
+pub(crate) fn unsubscribe(id: SubscriptionId) {
+    BUS.remove(&[id]);
+}
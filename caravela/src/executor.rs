@@ -0,0 +1,182 @@
+use crate::{timer, ErrorCode};
+use std::{
+    fmt::Debug,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+static EXECUTOR: OnceLock<Box<dyn Executor>> = OnceLock::new();
+
+/// A future that can be moved to the thread of an executor.
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Runs the futures of the asynchronous agents of the process,
+///  chosen with [`Platform::set_executor`](crate::Platform::set_executor) before the first one is added.
+pub trait Executor: Send + Sync + 'static {
+    /// Run the future to completion in the background.
+    fn spawn(&self, future: BoxFuture);
+}
+
+/// Minimal executor polling every future on a single thread of its own, used when no other executor is set.
+#[derive(Debug)]
+pub struct BuiltinExecutor {
+    queue: mpsc::Sender<Arc<Task>>,
+}
+
+struct Task {
+    future: Mutex<Option<BoxFuture>>,
+    queue: mpsc::Sender<Arc<Task>>,
+    queued: AtomicBool,
+}
+
+impl Wake for Task {
+    fn wake(self: Arc<Self>) {
+        if !self.queued.swap(true, Ordering::AcqRel) {
+            let _ = self.queue.send(self.clone());
+        }
+    }
+}
+
+impl BuiltinExecutor {
+    /// Start the thread of the executor.
+    pub fn new() -> Self {
+        let (queue, tasks) = mpsc::channel::<Arc<Task>>();
+        let _ = thread::Builder::new()
+            .name("caravela-executor".to_string())
+            .spawn(move || {
+                for task in tasks {
+                    task.queued.store(false, Ordering::Release);
+                    let waker = Waker::from(task.clone());
+                    let mut slot = task.future.lock().expect("Task is poisoned");
+                    if let Some(future) = slot.as_mut() {
+                        if future
+                            .as_mut()
+                            .poll(&mut Context::from_waker(&waker))
+                            .is_ready()
+                        {
+                            *slot = None;
+                        }
+                    }
+                }
+            });
+        Self { queue }
+    }
+}
+
+impl Default for BuiltinExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor for BuiltinExecutor {
+    fn spawn(&self, future: BoxFuture) {
+        let task = Arc::new(Task {
+            future: Mutex::new(Some(future)),
+            queue: self.queue.clone(),
+            queued: AtomicBool::new(false),
+        });
+        task.wake();
+    }
+}
+
+/// Executor spawning the futures onto a [`tokio`](https://docs.rs/tokio) runtime.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug)]
+pub struct TokioExecutor(tokio::runtime::Handle);
+
+#[cfg(feature = "tokio")]
+impl TokioExecutor {
+    /// Spawn onto the runtime of the given handle.
+    pub fn new(handle: tokio::runtime::Handle) -> Self {
+        Self(handle)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Executor for TokioExecutor {
+    fn spawn(&self, future: BoxFuture) {
+        drop(self.0.spawn(future));
+    }
+}
+
+/// Choose the executor of the asynchronous agents, before the first one is added.
+pub(crate) fn set(executor: impl Executor) -> Result<(), ErrorCode> {
+    EXECUTOR
+        .set(Box::new(executor))
+        .map_err(|_| ErrorCode::Duplicated)
+}
+
+/// Spawn a future on the executor, starting the built-in one if none was set.
+pub(crate) fn spawn(future: BoxFuture) {
+    EXECUTOR
+        .get_or_init(|| Box::new(BuiltinExecutor::new()))
+        .spawn(future)
+}
+
+/// Wakes the task of an asynchronous agent when a message arrives or its state is changed by the platform.
+#[derive(Debug, Default)]
+pub(crate) struct Signal {
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Signal {
+    /// Wake `waker` on the next signal. Conditions must be checked again after registering.
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut slot = self.waker.lock().expect("Signal is poisoned");
+        if !slot.as_ref().is_some_and(|x| x.will_wake(waker)) {
+            *slot = Some(waker.clone());
+        }
+    }
+
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = self.waker.lock().expect("Signal is poisoned").take() {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) type SignalArc = Arc<Signal>;
+
+/// Future returned by [`sleep`], which is ready once its time has passed. It works with any [`Executor`].
+#[derive(Debug)]
+pub struct Sleep {
+    until: Instant,
+    id: Option<u64>,
+}
+
+/// Wait asynchronously for the given duration.
+pub fn sleep(duration: Duration) -> Sleep {
+    Sleep {
+        until: Instant::now() + duration,
+        id: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.until {
+            return Poll::Ready(());
+        }
+        let until = self.until;
+        timer::wake_at(until, &mut self.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            timer::forget(id);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use crate::{
+    agent::{behavior::Behavior, Agent, AgentBuild, AgentState},
+    deck::deck,
+    entity::dispatch,
+    messaging::{Content, Message, MessageType, SyncType},
+    platform::RESERVED_NAMES,
+    simulation,
+    utils::json_string,
+    Description, ErrorCode, Platform, DEFAULT_STACK,
+};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const STATES: [AgentState; 5] = [
+    AgentState::Initiated,
+    AgentState::Active,
+    AgentState::Waiting,
+    AgentState::Suspended,
+    AgentState::Terminated,
+];
+
+/// Priority of the agents that stand in for the external senders during a replay.
+const STUB_PRIORITY: u8 = 1;
+
+/// Whether or not delivered messages are being journaled, so messaging only takes the journal lock when needed.
+static RECORDING: AtomicBool = AtomicBool::new(false);
+static WRITER: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+fn writer() -> MutexGuard<'static, Option<LineWriter<File>>> {
+    WRITER.lock().expect("Journal is poisoned - Lost entries")
+}
+
+pub(crate) fn is_active() -> bool {
+    RECORDING.load(Ordering::Acquire)
+}
+
+/// Start journaling every delivered message into the file, replacing its contents.
+pub(crate) fn start(path: &Path) -> io::Result<()> {
+    let file = File::create(path)?;
+    *writer() = Some(LineWriter::new(file));
+    RECORDING.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Stop journaling and flush the remaining entries.
+pub(crate) fn stop() -> io::Result<()> {
+    RECORDING.store(false, Ordering::Release);
+    match writer().take() {
+        Some(mut writer) => writer.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Write a delivered message to the journal along with the state of its receiver.
+pub(crate) fn record(msg: &Message) {
+    if !is_active() {
+        return;
+    }
+    let state = deck().view().agent_state(msg.receiver());
+    let entry = JournalEntry {
+        timestamp: SystemTime::now(),
+        sender: msg.sender().to_string(),
+        receiver: msg.receiver().to_string(),
+        receiver_state: state,
+        message_type: msg.message_type().clone(),
+        content_kind: ContentKind::of(msg.content()),
+        content: msg.content().to_string(),
+    };
+    if let Some(writer) = writer().as_mut() {
+        //TBD handle these possible errors;
+        let _ = writeln!(writer, "{}", entry.to_json());
+    }
+}
+
+/// Kind of [`Content`] carried by a journaled message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContentKind {
+    /// [`Content::Expression`], replayed exactly.
+    Expression,
+    /// [`Content::Action`], only kept as text.
+    Action,
+    /// [`Content::Reason`], only kept as text.
+    Reason,
+    /// [`Content::Bytes`], kept as hexadecimal text and replayed exactly.
+    Bytes,
+    /// [`Content::Forward`], only kept as text.
+    Forward,
+}
+
+impl ContentKind {
+    fn of(content: &Content) -> Self {
+        match content {
+            Content::Expression(_) => ContentKind::Expression,
+            Content::Action(_) => ContentKind::Action,
+            Content::Reason(..) => ContentKind::Reason,
+            Content::Bytes(_) => ContentKind::Bytes,
+            Content::Forward(..) => ContentKind::Forward,
+        }
+    }
+}
+
+impl Display for ContentKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentKind::Expression => write!(f, "Expression"),
+            ContentKind::Action => write!(f, "Action"),
+            ContentKind::Reason => write!(f, "Reason"),
+            ContentKind::Bytes => write!(f, "Bytes"),
+            ContentKind::Forward => write!(f, "Forward"),
+        }
+    }
+}
+
+/// A message delivered while the journal was recording.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct JournalEntry {
+    timestamp: SystemTime,
+    sender: String,
+    receiver: String,
+    receiver_state: Option<AgentState>,
+    message_type: MessageType,
+    content_kind: ContentKind,
+    content: String,
+}
+
+impl JournalEntry {
+    /// Get the time at which the message was delivered.
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+    /// Get the name of the sender as `nickname@hap`.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+    /// Get the name of the receiver as `nickname@hap`.
+    pub fn receiver(&self) -> &str {
+        &self.receiver
+    }
+    /// Get the state of the receiver when the message was delivered, `None` if the receiver is a service.
+    pub fn receiver_state(&self) -> Option<AgentState> {
+        self.receiver_state
+    }
+    /// Get the communicative act of the message.
+    pub fn message_type(&self) -> &MessageType {
+        &self.message_type
+    }
+    /// Get the kind of content of the message.
+    pub fn content_kind(&self) -> ContentKind {
+        self.content_kind
+    }
+    /// Get the content of the message as text.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    fn nickname(name: &str) -> &str {
+        name.split('@').next().unwrap_or(name)
+    }
+
+    fn to_json(&self) -> String {
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_micros())
+            .unwrap_or_default();
+        let state = self
+            .receiver_state
+            .map(|x| json_string(&x.to_string()))
+            .unwrap_or_else(|| "null".to_string());
+        format!(
+            "{{\"timestamp_us\":{},\"sender\":{},\"receiver\":{},\"receiver_state\":{},\"performative\":{},\"content_kind\":\"{}\",\"content\":{}}}",
+            micros,
+            json_string(&self.sender),
+            json_string(&self.receiver),
+            state,
+            json_string(&format!("{:?}", self.message_type)),
+            self.content_kind,
+            json_string(&self.content)
+        )
+    }
+
+    fn from_json(line: &str) -> Option<Self> {
+        let fields = parse_object(line)?;
+        let field = |name: &str| fields.get(name).cloned().flatten();
+        let micros: u64 = field("timestamp_us")?.parse().ok()?;
+        let receiver_state = match field("receiver_state") {
+            Some(state) => Some(*STATES.iter().find(|x| x.to_string() == state)?),
+            None => None,
+        };
+        let performative = field("performative")?;
+        let content_kind = match field("content_kind")?.as_str() {
+            "Expression" => ContentKind::Expression,
+            "Action" => ContentKind::Action,
+            "Reason" => ContentKind::Reason,
+            "Bytes" => ContentKind::Bytes,
+            "Forward" => ContentKind::Forward,
+            _ => return None,
+        };
+        Some(Self {
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            sender: field("sender")?,
+            receiver: field("receiver")?,
+            receiver_state,
+            message_type: performative.parse().ok()?,
+            content_kind,
+            content: field("content")?,
+        })
+    }
+
+    /// Rebuild the content of the message, if its kind is kept exactly.
+    fn replayable(&self) -> Option<Content> {
+        match self.content_kind {
+            ContentKind::Expression => Some(Content::Expression(self.content.clone())),
+            ContentKind::Bytes => decode_hex(&self.content).map(|x| Content::Bytes(x.into())),
+            ContentKind::Action | ContentKind::Reason | ContentKind::Forward => None,
+        }
+    }
+}
+
+/// Entries read back from a journal file, in order of delivery.
+///
+/// A journal is replayed into a fresh platform to reproduce the traffic received by a set of agents:
+///  messages from the rest of the agents are injected with their original timing, while the messages exchanged
+///  among the replayed agents and with the platform services are produced again by the agents themselves.
+#[derive(Clone, Debug, Default)]
+pub struct Journal {
+    entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Read the journal written by [`Platform::record_journal`]. Lines that cannot be read are skipped.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+        for line in reader.lines() {
+            if let Some(entry) = JournalEntry::from_json(&line?) {
+                entries.push(entry);
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Get the entries of the journal.
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Entries that are injected when replaying the journal into the given agents.
+    fn injected<'a>(&'a self, agents: &'a [Description]) -> impl Iterator<Item = &'a JournalEntry> {
+        let replayed = |name: &str| {
+            let nickname = JournalEntry::nickname(name);
+            agents.iter().any(|x| x.nickname() == nickname)
+        };
+        self.entries.iter().filter(move |entry| {
+            replayed(&entry.receiver)
+                && !replayed(&entry.sender)
+                && !RESERVED_NAMES.contains(&JournalEntry::nickname(&entry.sender))
+                && entry.replayable().is_some()
+        })
+    }
+
+    /// Replay the messages received by `agents` from any other agent, which are matched by nickname.
+    ///  Missing senders are stubbed out by agents that discard whatever they receive.
+    ///  Messages whose content is neither an [`Expression`](Content::Expression) nor [`Bytes`](Content::Bytes)
+    ///  cannot be rebuilt and are skipped.
+    ///
+    /// On a simulated platform every message is queued at its original offset from the first one,
+    ///  and delivered as the [`Simulation`](crate::Simulation) advances. Otherwise this call blocks while
+    ///  the messages are sent with their original timing, without waiting for room in a full mailbox.
+    ///  Returns the number of messages queued or delivered, leaving out the ones that could not be sent.
+    pub fn replay(&self, platform: &Platform, agents: &[Description]) -> Result<usize, ErrorCode> {
+        let mut senders: HashMap<&str, Description> = HashMap::new();
+        for entry in self.injected(agents) {
+            let nickname = JournalEntry::nickname(&entry.sender);
+            if !senders.contains_key(nickname) {
+                senders.insert(nickname, stub(platform, nickname)?);
+            }
+        }
+        let Some(first) = self.injected(agents).next().map(|x| x.timestamp) else {
+            return Ok(0);
+        };
+        let started = Instant::now();
+        let mut count = 0;
+        for entry in self.injected(agents) {
+            let receiver_nickname = JournalEntry::nickname(&entry.receiver);
+            let Some(receiver) = agents.iter().find(|x| x.nickname() == receiver_nickname) else {
+                continue;
+            };
+            let sender = senders[JournalEntry::nickname(&entry.sender)].clone();
+            let Some(content) = entry.replayable() else {
+                continue;
+            };
+            let msg = Message::new(
+                sender,
+                receiver.clone(),
+                entry.message_type.clone(),
+                content,
+            );
+            let offset = entry.timestamp.duration_since(first).unwrap_or_default();
+            if simulation::is_active() {
+                simulation::schedule_after(msg, offset);
+            } else {
+                thread::sleep(offset.saturating_sub(started.elapsed()));
+                // sent like any other message, so it is checked, sniffed and counted on the way
+                if dispatch(msg, SyncType::NonBlocking).is_err() {
+                    continue;
+                }
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+make_agent!(pub(crate) Stub);
+
+impl Behavior for Stub {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.receive().map(|_| ())
+    }
+}
+
+/// Find the sender in the platform or add a started [`Stub`] in its place.
+fn stub(platform: &Platform, nickname: &str) -> Result<Description, ErrorCode> {
+    let name = format!("{}@{}", nickname, platform.name());
+    if let Ok(aid) = deck().read().get_aid_from_name(&name) {
+        return Ok(aid);
+    }
+    let aid = platform.add_agent::<Stub>(nickname, STUB_PRIORITY, DEFAULT_STACK)?;
+    platform.start(&aid)?;
+    Ok(aid)
+}
+
+/// Read a flat JSON object with string, number or null values, as written to the journal.
+fn parse_object(line: &str) -> Option<HashMap<String, Option<String>>> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = HashMap::new();
+    (chars.next()? == '{').then_some(())?;
+    loop {
+        match chars.next()? {
+            '"' => {}
+            '}' if fields.is_empty() => return Some(fields),
+            _ => return None,
+        }
+        let key = parse_string(&mut chars)?;
+        (chars.next()? == ':').then_some(())?;
+        let value = if chars.peek() == Some(&'"') {
+            chars.next();
+            Some(parse_string(&mut chars)?)
+        } else {
+            let mut raw = String::new();
+            while let Some(c) = chars.next_if(|c| *c != ',' && *c != '}') {
+                raw.push(c);
+            }
+            (raw != "null").then_some(raw)
+        };
+        fields.insert(key, value);
+        match chars.next()? {
+            ',' => {}
+            '}' => return Some(fields),
+            _ => return None,
+        }
+    }
+}
+
+/// Read the rest of a JSON string whose opening quote has been consumed.
+fn parse_string(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(text),
+            '\\' => match chars.next()? {
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                'u' => {
+                    let code: String = chars.by_ref().take(4).collect();
+                    text.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                }
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|x| u8::from_str_radix(x, 16).ok())
+        })
+        .collect()
+}
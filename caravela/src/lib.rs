@@ -5,40 +5,99 @@
 //!
 //! The agents run, communicate and interact following on the threading model included in the [`std::sync`] module,
 //!  plus this platform depends on the [`thread_priority`] crate to provide a predictive pre-emptive behavior across agents.
+//!  Lightweight agents, added with [`Platform::add_lightweight_agent`], instead share a pool of worker threads
+//!  so that thousands of them can run in a process, yielding their worker whenever they would block.
+//!  Asynchronous agents, added with [`Platform::add_async_agent`], implement [`AsyncBehavior`](behavior::AsyncBehavior)
+//!  and run on a pluggable executor, a minimal built-in one unless the `tokio` feature provides `TokioExecutor`.
+//!  Agents may also post messages later or periodically through the [`timer`] service,
+//!  whose timers end with the agents that started them.
+//!
+//! Enabling the `tracing` feature reports the platform activity as [`tracing`](https://docs.rs/tracing) events,
+//!  each agent thread running inside an `agent` span with its `nickname` and `hap`. Verbosity is then chosen at runtime
+//!  by the installed subscriber using the `caravela::status`, `caravela::messaging`, `caravela::default` and `caravela::probe` targets.
+//!  The `log` feature additionally forwards these events to the [`log`](https://docs.rs/log) crate when no subscriber is set.
+//!
+//! Enabling the `metrics` feature collects counters and histograms of the platform that can be exported
+//!  in the Prometheus text format through the `metrics` module.
+//!
+//! Enabling the `config` feature provides the `config` module, where agent types are registered by name
+//!  so a platform and its agents can be declared in a TOML or JSON file.
+//!  The `cli` feature builds upon it the `caravela` binary, which launches such a file and controls the platform interactively.
+//!
+//! Enabling the `derive` feature provides `#[derive(Agent)]` and the `#[behavior]` attribute in the `agent` module,
+//!  an alternative to [`make_agent!`] and [`make_agent_with_param!`] for agents with any set of fields.
+//!
+//! Enabling the `testing` feature provides the `testing` module, where a single agent is driven step by step
+//!  against a mock platform context to unit test its [`Behavior`](behavior::Behavior).
 #[macro_use]
 pub(crate) mod utils;
 
+/// Interactive control of a platform launched from a configuration file, as done by the `caravela` binary.
+#[cfg(feature = "cli")]
+pub mod cli;
+/// Declarative launch of a platform from a configuration file.
+#[cfg(feature = "config")]
+pub mod config;
+/// Dead-letter queue keeping the messages that could not be delivered, to be inspected and redelivered.
+pub mod dead_letter;
 pub(crate) mod deck;
 pub(crate) mod entity;
+pub(crate) mod events;
+/// Executors running the futures of asynchronous agents, and timers to await within them.
+pub mod executor;
+/// Journaling of the delivered messages and their replay into a fresh platform.
+pub mod journal;
+/// Export of platform metrics in the Prometheus text format.
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub(crate) mod platform;
+pub(crate) mod pool;
+pub(crate) mod simulation;
+/// Harness to test the behavior of a single agent without running a platform.
+#[cfg(feature = "testing")]
+pub mod testing;
+/// Timers posting messages into the mailboxes of agents, once or periodically.
+pub mod timer;
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing as __tracing;
 
 pub use {
     entity::agent::behavior,
-    entity::{agent, messaging, service, Description},
+    entity::{agent, messaging, service, Description, NameError},
+    events::{Event, SubscriptionId},
     platform::Platform,
+    simulation::{Simulation, SimulationStep},
 };
 
-use std::{
-    error::Error,
-    fmt::Display,
-    sync::mpsc::{Receiver, RecvError, SyncSender},
-};
+use std::{error::Error, fmt::Display, sync::mpsc::RecvError, time::Duration};
 use {
     agent::AgentState,
-    messaging::Message, // RequestType},
+    messaging::mailbox, // RequestType},
 };
 
 /// StackSize defined as platform dependant.
 pub type StackSize = usize;
-pub(crate) type Tx = SyncSender<Message>;
-pub(crate) type Rx = Receiver<Message>;
+pub(crate) type Tx = mailbox::Sender;
+pub(crate) type Rx = mailbox::Receiver;
 
 /// Default stack value for any given platform.
 pub const DEFAULT_STACK: usize = 30000;
 /// Maximum priority across all entities.
 ///  This value is reserved for platform service entities such as the AMS and cannot be used for user defined agents.
 pub const MAX_PRIORITY: u8 = 99;
+/// Maximum length in bytes of agent nicknames and platform names.
+pub const MAX_NAME_LENGTH: usize = 64;
 pub(crate) const MAX_SUBSCRIBERS: usize = 64;
+/// Time the AMS waits for the thread of a terminated agent to finish, before leaving it detached.
+pub const JOIN_TIMEOUT: Duration = Duration::from_secs(1);
+/// Time [`Platform::start`] waits for the thread of the agent to confirm it is running.
+pub const START_TIMEOUT: Duration = Duration::from_secs(1);
+/// Number of undeliverable messages kept in the dead-letter queue, the oldest being discarded first.
+pub const DEAD_LETTER_CAPACITY: usize = 1024;
+/// Number of captured messages kept in the trace of the sniffer, the oldest being discarded first.
+pub const TRACE_CAPACITY: usize = 4096;
 
 /// Different error codes associated with possible platform failures provided to support error handling functionality.
 #[derive(PartialEq, Debug)]
@@ -51,6 +110,12 @@ pub enum ErrorCode {
     AgentStart(thread_priority::Error),
     /// Could not create agent with the given priority.
     InvalidPriority(&'static str),
+    /// The mailbox cannot hold the given number of messages.
+    InvalidCapacity(usize),
+    /// The agent cannot be pinned to the given core.
+    InvalidAffinity(usize),
+    /// The platform configuration is not valid.
+    InvalidConfig(String),
     /// The sending half of the channel may have disconnected.
     MpscRecv(RecvError),
     /// The receiving half of the channel may have disconnected.
@@ -63,12 +128,20 @@ pub enum ErrorCode {
     Duplicated,
     /// The agent could not be found.
     NotFound,
+    /// The lightweight agent would block, it yields to the worker pool until it can continue.
+    WouldBlock,
+    /// A blocking call of the agent was interrupted because it was suspended or terminated.
+    Interrupted,
+    /// The operation did not complete in time.
+    Timeout,
+    /// The message outlived its time to live before being delivered.
+    Expired,
     /// Invalid content in message.
     InvalidContent(String),
     /// Unexpected message for a given protocol.
     InvalidMessageType,
-    /// The agent cannot have a reserved name.
-    InvalidName,
+    /// The name of the agent or platform cannot be used, for the given reason.
+    InvalidName(NameError),
     /// Unexpected request.
     InvalidRequest(String),
     /// State change not possible.
@@ -77,6 +150,12 @@ pub enum ErrorCode {
     NotRegistered,
     /// There is a platform already running.
     PlatformPresent,
+    /// The agent is not a member of the organization.
+    NotMember,
+    /// The agent is banned from the organization.
+    Banned,
+    /// The organization rules do not allow the agent to address the target.
+    NotVisible,
     /// Custom error message for the user.
     Other(String),
 }
@@ -90,21 +169,26 @@ impl Display for ErrorCode {
             ErrorCode::InvalidPriority(error) => {
                 write!(f, "Could not create agent with this priority:{}", error)
             }
-            ErrorCode::MpscRecv(x) => write!(
-                f,
-                "SyncSender was disconnected from this Receiver: {}",
-                x
-            ),
+            ErrorCode::InvalidCapacity(x) => write!(f, "Invalid mailbox capacity: {}", x),
+            ErrorCode::InvalidAffinity(x) => write!(f, "Invalid core affinity: {}", x),
+            ErrorCode::InvalidConfig(x) => write!(f, "Invalid platform configuration: {}", x),
+            ErrorCode::MpscRecv(x) => {
+                write!(f, "SyncSender was disconnected from this Receiver: {}", x)
+            }
             ErrorCode::Disconnected => write!(f, "Receiver was disconnected from this SyncSender"),
             ErrorCode::ChannelFull => write!(f, "Target agent channel was full"),
             ErrorCode::ListFull => write!(f, "Max number of agents reached"),
             ErrorCode::Duplicated => write!(f, "Agent is already present"),
             ErrorCode::NotFound => write!(f, "Agent could not be found"),
+            ErrorCode::WouldBlock => write!(f, "Agent yielded to the worker pool"),
+            ErrorCode::Interrupted => write!(f, "Agent was interrupted by a lifecycle command"),
+            ErrorCode::Timeout => write!(f, "Operation timed out"),
+            ErrorCode::Expired => write!(f, "Message expired before being delivered"),
             ErrorCode::InvalidContent(x) => {
                 write!(f, "Invalid content in message: {}", x)
             }
             ErrorCode::InvalidMessageType => write!(f, "Unexpected message received"),
-            ErrorCode::InvalidName => write!(f, "The agent cannot have a reserved name"),
+            ErrorCode::InvalidName(x) => write!(f, "Invalid name: {}", x),
             ErrorCode::InvalidRequest(x) => {
                 write!(f, "Unexpected request received: {}", x)
             }
@@ -113,6 +197,9 @@ impl Display for ErrorCode {
             }
             ErrorCode::NotRegistered => write!(f, "Target agent is not registered"),
             ErrorCode::PlatformPresent => write!(f, "There is another platform running already"),
+            ErrorCode::NotMember => write!(f, "Agent is not a member of the organization"),
+            ErrorCode::Banned => write!(f, "Agent is banned from the organization"),
+            ErrorCode::NotVisible => write!(f, "Target is not visible to the agent"),
             ErrorCode::Other(x) => write!(f, "{}", x),
         }
     }
@@ -0,0 +1,305 @@
+use crate::{
+    deck::get_deck,
+    messaging::{ActionType, Message, MessageType},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock, RwLockReadGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Upper bounds in seconds of the latency histogram buckets.
+const BUCKETS: [f64; 8] = [0.00001, 0.0001, 0.001, 0.01, 0.1, 1.0, 10.0, f64::INFINITY];
+
+/// Time a client of the exporter is given to send its request and to read the answer,
+///  since connections are answered one at a time.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+const MESSAGES: &str = "caravela_messages_total";
+const SEND_LATENCY: &str = "caravela_send_latency_seconds";
+const RECEIVE_LATENCY: &str = "caravela_receive_latency_seconds";
+const MAILBOX_HIGH_WATER: &str = "caravela_mailbox_high_water";
+const AMS_REQUESTS: &str = "caravela_ams_requests_total";
+const FAILURE_DETECTIONS: &str = "caravela_failure_detections_total";
+const DEADLINE_MISSES: &str = "caravela_deadline_misses_total";
+
+const COUNTERS: [(&str, &str); 4] = [
+    (MESSAGES, "Messages placed in a mailbox by performative."),
+    (
+        AMS_REQUESTS,
+        "Requests processed by the AMS by action and outcome.",
+    ),
+    (
+        FAILURE_DETECTIONS,
+        "Failures detected by the FDIR functionality of each agent.",
+    ),
+    (DEADLINE_MISSES, "Deadlines missed by each agent."),
+];
+
+const HISTOGRAMS: [(&str, &str); 2] = [
+    (
+        SEND_LATENCY,
+        "Time spent placing a message in the mailbox of its receiver.",
+    ),
+    (
+        RECEIVE_LATENCY,
+        "Time spent waiting for a message to arrive in the mailbox.",
+    ),
+];
+
+/// Histogram updated without locking, its sum kept in nanoseconds.
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len()],
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, value: Duration) {
+        let seconds = value.as_secs_f64();
+        self.buckets
+            .iter()
+            .zip(BUCKETS)
+            .filter(|(_, bound)| seconds <= *bound)
+            .for_each(|(bucket, _)| {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            });
+        let nanos = u64::try_from(value.as_nanos()).unwrap_or(u64::MAX);
+        self.sum.fetch_add(nanos, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Nickname and HAP of the agent a sample belongs to.
+type AgentKey = (Arc<str>, Arc<str>);
+
+/// Samples of a metric keyed by their labels. Updating an existing sample only takes the read lock,
+///  since samples are atomics, and the write lock is only taken to add a new one.
+#[derive(Debug)]
+struct Family<K, T>(RwLock<BTreeMap<K, T>>);
+
+impl<K: Ord, T: Default> Family<K, T> {
+    const fn new() -> Self {
+        Self(RwLock::new(BTreeMap::new()))
+    }
+
+    fn update(&self, key: K, update: impl Fn(&T)) {
+        if let Some(sample) = self.read().get(&key) {
+            return update(sample);
+        }
+        let mut samples = self.0.write().expect("Metrics are poisoned - Lost samples");
+        update(samples.entry(key).or_default());
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, BTreeMap<K, T>> {
+        self.0.read().expect("Metrics are poisoned - Lost samples")
+    }
+}
+
+fn increment<K: Ord>(family: &Family<K, AtomicU64>, key: K) {
+    family.update(key, |counter| {
+        counter.fetch_add(1, Ordering::Relaxed);
+    });
+}
+
+/// Messages by index of their performative in [`MessageType::ALL`].
+static MESSAGE_COUNTS: Family<usize, AtomicU64> = Family::new();
+static SEND_LATENCIES: Family<AgentKey, Histogram> = Family::new();
+static RECEIVE_LATENCIES: Family<AgentKey, Histogram> = Family::new();
+/// Requests by rendered action and outcome labels.
+static AMS_COUNTS: Family<String, AtomicU64> = Family::new();
+static FAILURE_COUNTS: Family<String, AtomicU64> = Family::new();
+static DEADLINE_COUNTS: Family<String, AtomicU64> = Family::new();
+
+fn label(name: &str, value: &str) -> String {
+    let value = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("{}=\"{}\"", name, value)
+}
+
+fn agent_label((nickname, hap): &AgentKey) -> String {
+    label("agent", &format!("{}@{}", nickname, hap))
+}
+
+fn action_name(action: &ActionType) -> &'static str {
+    match action {
+        ActionType::Search(_) => "Search",
+        ActionType::Modify(..) => "Modify",
+        ActionType::Register(_) => "Register",
+        ActionType::Deregister(_) => "Deregister",
+        ActionType::Organization(..) => "Organization",
+        ActionType::Sniffer(_) => "Sniffer",
+        ActionType::Other(_) => "Other",
+    }
+}
+
+pub(crate) fn message_sent(msg: &Message, latency: Duration) {
+    if let Some(index) = MessageType::ALL
+        .iter()
+        .position(|x| x == msg.message_type())
+    {
+        increment(&MESSAGE_COUNTS, index);
+    }
+    SEND_LATENCIES.update(msg.sender().shared_name(), |histogram| {
+        histogram.observe(latency)
+    });
+}
+
+pub(crate) fn message_received(msg: &Message, latency: Duration) {
+    RECEIVE_LATENCIES.update(msg.receiver().shared_name(), |histogram| {
+        histogram.observe(latency)
+    });
+}
+
+pub(crate) fn ams_request(action: &ActionType, outcome: &MessageType) {
+    let labels = format!(
+        "{},{}",
+        label("action", action_name(action)),
+        label("outcome", &format!("{:?}", outcome))
+    );
+    increment(&AMS_COUNTS, labels);
+}
+
+pub(crate) fn failure_detected(agent: &str) {
+    increment(&FAILURE_COUNTS, label("agent", agent));
+}
+
+pub(crate) fn deadline_missed(agent: &str) {
+    increment(&DEADLINE_COUNTS, label("agent", agent));
+}
+
+fn write_counters<K: Ord>(
+    text: &mut String,
+    metric: &str,
+    family: &Family<K, AtomicU64>,
+    labels: impl Fn(&K) -> String,
+) {
+    for (key, counter) in family.read().iter() {
+        let _ = writeln!(
+            text,
+            "{}{{{}}} {}",
+            metric,
+            labels(key),
+            counter.load(Ordering::Relaxed)
+        );
+    }
+}
+
+fn write_histograms(text: &mut String, metric: &str, family: &Family<AgentKey, Histogram>) {
+    for (key, histogram) in family.read().iter() {
+        let labels = agent_label(key);
+        for (count, bound) in histogram.buckets.iter().zip(BUCKETS) {
+            let bound = if bound.is_infinite() {
+                "+Inf".to_string()
+            } else {
+                bound.to_string()
+            };
+            let _ = writeln!(
+                text,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                metric,
+                labels,
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let sum = Duration::from_nanos(histogram.sum.load(Ordering::Relaxed)).as_secs_f64();
+        let _ = writeln!(text, "{}_sum{{{}}} {}", metric, labels, sum);
+        let count = histogram.count.load(Ordering::Relaxed);
+        let _ = writeln!(text, "{}_count{{{}}} {}", metric, labels, count);
+    }
+}
+
+/// Render every metric of the platform in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut text = String::new();
+    for (metric, help) in COUNTERS {
+        let _ = writeln!(
+            text,
+            "# HELP {} {}\n# TYPE {} counter",
+            metric, help, metric
+        );
+        match metric {
+            MESSAGES => write_counters(&mut text, metric, &MESSAGE_COUNTS, |index| {
+                label("performative", &format!("{:?}", MessageType::ALL[*index]))
+            }),
+            AMS_REQUESTS => write_counters(&mut text, metric, &AMS_COUNTS, String::clone),
+            FAILURE_DETECTIONS => write_counters(&mut text, metric, &FAILURE_COUNTS, String::clone),
+            _ => write_counters(&mut text, metric, &DEADLINE_COUNTS, String::clone),
+        }
+    }
+    for (metric, help) in HISTOGRAMS {
+        let _ = writeln!(
+            text,
+            "# HELP {} {}\n# TYPE {} histogram",
+            metric, help, metric
+        );
+        match metric {
+            SEND_LATENCY => write_histograms(&mut text, metric, &SEND_LATENCIES),
+            _ => write_histograms(&mut text, metric, &RECEIVE_LATENCIES),
+        }
+    }
+    let _ = writeln!(
+        text,
+        "# HELP {} Largest number of messages held by the mailbox of each agent.\n# TYPE {} gauge",
+        MAILBOX_HIGH_WATER, MAILBOX_HIGH_WATER
+    );
+    if let Some(deck) = get_deck() {
+        for (aid, high_water) in deck.read().mailbox_high_water() {
+            let labels = label("agent", &aid.to_string());
+            let _ = writeln!(text, "{}{{{}}} {}", MAILBOX_HIGH_WATER, labels, high_water);
+        }
+    }
+    text
+}
+
+/// Write every metric of the platform to a file, replacing its contents.
+pub fn write_to_file(path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, render())
+}
+
+/// Serve the metrics of the platform over HTTP on the given address from a background thread.
+///  Every request is answered with the output of [`render`]. Returns the address actually bound.
+pub fn serve(address: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let local_address = listener.local_addr()?;
+    thread::Builder::new()
+        .name("caravela-metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream);
+            }
+        })?;
+    Ok(local_address)
+}
+
+fn respond(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    // consume the request head, its contents do not matter
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let body = render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
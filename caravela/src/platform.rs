@@ -1,34 +1,82 @@
 use crate::{
-    deck::{deck, get_deck},
+    dead_letter::{self, DeadLetter, DeadLetterId},
+    deck::{deck, get_deck, Runner},
     entity::{
         agent::{
-            behavior::{execute, Behavior},
-            Agent, AgentBuild, AgentBuildParam, ControlBlock,
+            behavior::{execute, execute_async, AsyncBehavior, Behavior, Lightweight},
+            Agent, AgentBuild, AgentBuildParam, AgentState, AgentStats, ControlBlock,
         },
-        messaging::Message,
-        service::{ams::Ams, AmsConditions, DefaultConditions, Service},
-        Description,
+        messaging::{mailbox, LanePolicy},
+        service::{
+            ams::Ams,
+            organization::OrgService,
+            sniffer::{Sniffer, SnifferService, Trace},
+            AmsConditions, DefaultConditions, Role, Service,
+        },
+        validate_hap, validate_nickname, Description,
     },
-    ErrorCode, DEFAULT_STACK,
+    events::{self, Event, SubscriptionId},
+    executor::{self, Executor, Signal},
+    journal,
+    pool::{self, Wake},
+    simulation::{self, Simulation},
+    ErrorCode, DEFAULT_STACK, START_TIMEOUT,
 };
 use std::{
-    sync::{mpsc::sync_channel, Arc},
+    io,
+    path::Path,
+    sync::{mpsc::Receiver, Arc},
     thread,
 };
 use thread_priority::{ThreadBuilderExt, ThreadExt, ThreadPriority, ThreadPriorityValue};
 
-const RESERVED_NAMES: [&str; 1] = ["ams"];
+pub(crate) const RESERVED_NAMES: [&str; 3] = ["ams", "org", "sniffer"];
+
+/// Validate a user given priority, since the maximum value is reserved for services.
+pub(crate) fn agent_priority(priority: u8) -> Result<ThreadPriority, ErrorCode> {
+    if priority == ThreadPriorityValue::MAX {
+        return Err(ErrorCode::InvalidPriority(
+            "Max priority only allowed for Services",
+        ));
+    }
+    ThreadPriority::try_from(priority).map_err(ErrorCode::InvalidPriority)
+}
+
+/// Pin the calling thread to the given core.
+#[cfg(target_os = "linux")]
+pub(crate) fn pin_current_thread(core: usize) -> Result<(), ErrorCode> {
+    // SAFETY: the set is fully initialized before being read by sched_setaffinity, and pid 0 is the calling thread
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(core, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    (result == 0)
+        .then_some(())
+        .ok_or(ErrorCode::InvalidAffinity(core))
+}
+
+/// Pin the calling thread to the given core. Not supported on this target.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn pin_current_thread(core: usize) -> Result<(), ErrorCode> {
+    Err(ErrorCode::InvalidAffinity(core))
+}
 
 /// Represents the Host Agent Platform (HAP) and
 ///  provides the user with methods to incorporate agents into it.
 #[derive(Debug)]
 pub struct Platform {
-    name: &'static str,
+    name: Arc<str>,
 }
 
 impl Platform {
     /// Function that constructs a new [`Platform`] object with the provided name.
-    pub fn new(name: &'static str) -> Result<Self, ErrorCode> {
+    ///  The name is rejected with [`ErrorCode::InvalidName`] if it is empty, too long or has characters other than
+    ///  ASCII letters, digits, `-`, `_` and `.`.
+    pub fn new(name: impl Into<Arc<str>>) -> Result<Self, ErrorCode> {
+        let name = name.into();
+        validate_hap(&name).map_err(ErrorCode::InvalidName)?;
         if get_deck().is_some() {
             return Err(ErrorCode::PlatformPresent);
         }
@@ -39,9 +87,11 @@ impl Platform {
 
     /// Function that constructs a new [`Platform`] object with the provided name and conditions for the AMS.
     pub fn new_with_conditions<T: AmsConditions + Send + 'static>(
-        name: &'static str,
+        name: impl Into<Arc<str>>,
         conditions: T,
     ) -> Result<Self, ErrorCode> {
+        let name = name.into();
+        validate_hap(&name).map_err(ErrorCode::InvalidName)?;
         if get_deck().is_some() {
             return Err(ErrorCode::PlatformPresent);
         }
@@ -51,9 +101,18 @@ impl Platform {
             .boot_with_ams_conditions(conditions)
             .map(|_| platform)
     }
+    /// Function that constructs a new [`Platform`] that runs as a deterministic [`Simulation`] driven by `seed`.
+    ///  Agents do not run until the returned [`Simulation`] steps them.
+    pub fn new_simulated(
+        name: impl Into<Arc<str>>,
+        seed: u64,
+    ) -> Result<(Self, Simulation), ErrorCode> {
+        let platform = Self::new(name)?;
+        Ok((platform, simulation::activate(seed)))
+    }
     /// Returns the name of the platform.
-    pub fn name(&self) -> &'static str {
-        self.name
+    pub fn name(&self) -> &str {
+        &self.name
     }
     /// This method starts the Agent Management System (AMS) as [`boot_with_ams_conditions`](Self::boot_with_ams_conditions) also does,
     ///  but with default service conditions.
@@ -68,9 +127,9 @@ impl Platform {
         &self,
         conditions: T,
     ) -> Result<(), ErrorCode> {
-        let (tx, rx) = sync_channel::<Message>(1);
-        let mut ams_aid = Description::new("ams", self.name(), tx);
-        let mut ams = Ams::<T>::new(self.name, rx, conditions);
+        let (tx, rx) = mailbox::channel(1);
+        let mut ams_aid = Description::new("ams", self.name.clone(), tx);
+        let mut ams = Ams::<T>::new(self.name.clone(), rx, conditions);
 
         caravela_status!("BOOTING AMS");
         let ams_handle = thread::Builder::new()
@@ -92,41 +151,178 @@ impl Platform {
         }
     }
 
+    /// This method starts the organization service, which allows agents to open organizations and manage their memberships
+    ///  by sending [`ActionType::Organization`](crate::messaging::ActionType::Organization) requests to the `org` agent.
+    ///  If successful, it will return a `Ok(aid)` with the [`Description`] of the service.
+    pub fn boot_org_service(&self) -> Result<Description, ErrorCode> {
+        if deck().read().org_aid().is_ok() {
+            return Err(ErrorCode::Duplicated);
+        }
+        let (tx, rx) = mailbox::channel(1);
+        let mut org_aid = Description::new("org", self.name.clone(), tx);
+        let mut org = OrgService::new(self.name.clone(), rx);
+
+        caravela_status!("BOOTING ORG SERVICE");
+        let join_handle = thread::Builder::new()
+            .stack_size(DEFAULT_STACK)
+            .spawn_with_priority(ThreadPriority::Max, move |_| {
+                org.service_function();
+            })
+            .map_err(|_| ErrorCode::AgentPanic)?;
+        org_aid.set_id(join_handle.thread().id());
+        deck().write().add_org_service(org_aid.clone(), join_handle);
+        Ok(org_aid)
+    }
+
+    /// This method starts the sniffer, which copies every message sent or received by the sniffed agents.
+    ///  Agents are sniffed through the returned [`Sniffer`] handle or by sending
+    ///  [`ActionType::Sniffer`](crate::messaging::ActionType::Sniffer) requests to the `sniffer` agent.
+    pub fn boot_sniffer(&self) -> Result<Sniffer, ErrorCode> {
+        if deck().read().sniffer_aid().is_ok() {
+            return Err(ErrorCode::Duplicated);
+        }
+        let (tx, rx) = mailbox::channel(1);
+        let mut sniffer_aid = Description::new("sniffer", self.name.clone(), tx);
+        let mut sniffer = SnifferService::new(self.name.clone(), rx);
+        let trace = Arc::new(Trace::default());
+
+        caravela_status!("BOOTING SNIFFER");
+        let join_handle = thread::Builder::new()
+            .stack_size(DEFAULT_STACK)
+            .spawn_with_priority(ThreadPriority::Max, move |_| {
+                sniffer.service_function();
+            })
+            .map_err(|_| ErrorCode::AgentPanic)?;
+        sniffer_aid.set_id(join_handle.thread().id());
+        deck()
+            .write()
+            .add_sniffer(sniffer_aid.clone(), join_handle, trace.clone());
+        Ok(Sniffer::new(sniffer_aid, trace))
+    }
+
     /// This method creates agents of the given `T` type that implements [`Behavior`]
     ///  with the specified values (nickname, priority, and stack size).
+    ///  The nickname follows the rules of the platform name and cannot be the one of a platform service.
     ///  If successful, it will return a `Ok(aid)` with the [`Description`] of the agent.
     ///  This Agent is not active by default and must be started by [`start`](Self::start)
     pub fn add_agent<T: Behavior + AgentBuild + Send + 'static>(
         &self,
-        nickname: &'static str,
+        nickname: impl Into<Arc<str>>,
+        priority: u8,
+        stack_size: usize,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_agent(nickname.into(), priority, stack_size, T::agent_builder)
+    }
+
+    /// This method creates agents of the given `T` type that implements [`Behavior`]
+    ///  with the specified values (nickname, priority, and stack size, parameter).
+    ///  If successful, it will return a `Ok(aid)` with the [`Description`] of the agent.
+    ///  This Agent is not active by default and must be started by [`start`](Self::start)
+    pub fn add_agent_with_param<T: Behavior + AgentBuildParam + Send + 'static>(
+        &self,
+        nickname: impl Into<Arc<str>>,
         priority: u8,
         stack_size: usize,
+        param: T::Parameter,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_agent(nickname.into(), priority, stack_size, |base_agent| {
+            T::agent_with_param_builder(base_agent, param)
+        })
+    }
+
+    /// This method creates a lightweight agent of the given `T` type that implements [`Behavior`],
+    ///  which shares the worker pool of the platform instead of running on a thread of its own.
+    ///  Each [`Behavior::action`] runs on whichever worker is free, agents with a higher priority first.
+    ///  Where a threaded agent would block, in [`Agent::receive`], [`Agent::wait`] or sending to a full mailbox,
+    ///  a lightweight agent yields its worker: they return [`ErrorCode::WouldBlock`], which should be propagated,
+    ///  and the action is run again from its start once the agent can continue. An action that sends several messages
+    ///  should keep track of those already sent, since they are not taken back.
+    ///  The agent cannot be pinned to a core, and it runs on a thread of its own in a simulated platform.
+    ///  This Agent is not active by default and must be started by [`start`](Self::start)
+    pub fn add_lightweight_agent<T: Behavior + AgentBuild + Send + 'static>(
+        &self,
+        nickname: impl Into<Arc<str>>,
+        priority: u8,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_lightweight_agent(nickname.into(), priority, T::agent_builder)
+    }
+
+    /// This method creates a lightweight agent of the given `T` type with a parameter,
+    ///  as [`add_lightweight_agent`](Self::add_lightweight_agent) does.
+    pub fn add_lightweight_agent_with_param<T: Behavior + AgentBuildParam + Send + 'static>(
+        &self,
+        nickname: impl Into<Arc<str>>,
+        priority: u8,
+        param: T::Parameter,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_lightweight_agent(nickname.into(), priority, |base_agent| {
+            T::agent_with_param_builder(base_agent, param)
+        })
+    }
+
+    /// Set the number of worker threads that run the lightweight agents of the process,
+    ///  which defaults to the available parallelism. It must be set before the first lightweight agent is added.
+    pub fn set_pool_workers(&self, workers: usize) -> Result<(), ErrorCode> {
+        pool::set_workers(workers)
+    }
+
+    /// This method creates an asynchronous agent of the given `T` type that implements [`AsyncBehavior`],
+    ///  whose behavior is a future run by the executor of the platform, see [`set_executor`](Self::set_executor).
+    ///  It is managed as any other agent, but it cannot be pinned to a core nor run in a simulated platform.
+    ///  This Agent is not active by default and must be started by [`start`](Self::start)
+    pub fn add_async_agent<T: AsyncBehavior + AgentBuild + 'static>(
+        &self,
+        nickname: impl Into<Arc<str>>,
+        priority: u8,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_async_agent(nickname.into(), priority, T::agent_builder)
+    }
+
+    /// This method creates an asynchronous agent of the given `T` type with a parameter,
+    ///  as [`add_async_agent`](Self::add_async_agent) does.
+    pub fn add_async_agent_with_param<T: AsyncBehavior + AgentBuildParam + 'static>(
+        &self,
+        nickname: impl Into<Arc<str>>,
+        priority: u8,
+        param: T::Parameter,
+    ) -> Result<Description, ErrorCode> {
+        self.spawn_async_agent(nickname.into(), priority, |base_agent| {
+            T::agent_with_param_builder(base_agent, param)
+        })
+    }
+
+    /// Set the [`Executor`] that runs the asynchronous agents of the process, such as a
+    ///  `TokioExecutor` under the `tokio` feature. It must be set before the first asynchronous agent is added,
+    ///  otherwise the [`BuiltinExecutor`](executor::BuiltinExecutor) is used.
+    pub fn set_executor(&self, executor: impl Executor) -> Result<(), ErrorCode> {
+        executor::set(executor)
+    }
+
+    fn spawn_agent<T: Behavior + Send + 'static>(
+        &self,
+        nickname: Arc<str>,
+        priority: u8,
+        stack_size: usize,
+        build: impl FnOnce(Agent) -> T,
     ) -> Result<Description, ErrorCode> {
         // check name
-        if RESERVED_NAMES.contains(&nickname) {
-            return Err(ErrorCode::InvalidName);
-        }
+        validate_nickname(&nickname).map_err(ErrorCode::InvalidName)?;
+
         // build agent
-        let hap = self.name;
-        let (tx, rx) = sync_channel::<Message>(1);
-        let mut aid = Description::new(nickname, hap, tx);
-        let control_block = Arc::new(ControlBlock::default());
+        let hap = self.name.clone();
+        let (tx, rx) = mailbox::channel(1);
+        let mut aid = Description::new(nickname.clone(), hap.clone(), tx);
+        let control_block = Arc::new(ControlBlock::new(priority, stack_size));
         let base_agent = Agent::new(nickname, hap, rx, control_block.clone());
         if deck().read().search_agent(&aid).is_ok() {
             return Err(ErrorCode::Duplicated);
         }
 
         // check prio
-        if priority == ThreadPriorityValue::MAX {
-            return Err(ErrorCode::InvalidPriority(
-                "Max priority only allowed for Services",
-            ));
-        }
-        let thread_priority =
-            ThreadPriority::try_from(priority).map_err(ErrorCode::InvalidPriority)?;
+        let thread_priority = agent_priority(priority)?;
 
-        // spawn agent with spinlock
-        let agent = T::agent_builder(base_agent);
+        // spawn agent parked at its start gate
+        let agent = build(base_agent);
         let agent_handle = thread::Builder::new()
             .stack_size(stack_size)
             .spawn_with_priority(ThreadPriority::Min, move |_| execute(agent));
@@ -136,84 +332,295 @@ impl Platform {
 
         //Build description and insert in env lock
         aid.set_id(join_handle.thread().id());
-        deck()
-            .write()
-            .add_agent(aid.clone(), join_handle, thread_priority, control_block)?;
+        deck().write().add_agent(
+            aid.clone(),
+            Runner::Thread(join_handle),
+            thread_priority,
+            control_block.clone(),
+        )?;
+        if simulation::is_active() {
+            simulation::register(aid.clone(), control_block);
+        }
+        events::emit(|| Event::AgentAdded(aid.clone()));
         Ok(aid)
     }
 
-    /// This method creates agents of the given `T` type that implements [`Behavior`]
-    ///  with the specified values (nickname, priority, and stack size, parameter).
-    ///  If successful, it will return a `Ok(aid)` with the [`Description`] of the agent.
-    ///  This Agent is not active by default and must be started by [`start`](Self::start)
-    pub fn add_agent_with_param<T: Behavior + AgentBuildParam + Send + 'static>(
+    fn spawn_lightweight_agent<T: Behavior + Send + 'static>(
         &self,
-        nickname: &'static str,
+        nickname: Arc<str>,
         priority: u8,
-        stack_size: usize,
-        param: T::Parameter,
+        build: impl FnOnce(Agent) -> T,
     ) -> Result<Description, ErrorCode> {
-        // check name
-        if RESERVED_NAMES.contains(&nickname) {
-            return Err(ErrorCode::InvalidName);
+        if simulation::is_active() {
+            // the simulation schedules agents by their threads
+            return self.spawn_agent(nickname, priority, DEFAULT_STACK, build);
         }
+        validate_nickname(&nickname).map_err(ErrorCode::InvalidName)?;
 
-        // build agent
-        let hap = self.name;
-        let (tx, rx) = sync_channel::<Message>(1);
-        let mut aid = Description::new(nickname, hap, tx);
-        let control_block = Arc::new(ControlBlock::default());
-        let base_agent = Agent::new(nickname, hap, rx, control_block.clone());
+        let (tx, rx) = mailbox::channel(1);
+        let aid = Description::new(nickname, self.name.clone(), tx);
         if deck().read().search_agent(&aid).is_ok() {
             return Err(ErrorCode::Duplicated);
         }
+        let thread_priority = agent_priority(priority)?;
 
+        // the agent has no stack of its own, it runs on the stack of a worker
+        let control_block = Arc::new(ControlBlock::new(priority, 0));
+        let id = pool::next_id();
+        rx.set_waker(move || pool::wake(id, Wake::Message));
+        let agent = build(Agent::new_pooled(
+            aid.clone(),
+            id,
+            rx,
+            control_block.clone(),
+        ));
+        pool::add(
+            id,
+            aid.clone(),
+            control_block.clone(),
+            Box::new(Lightweight::new(agent)),
+        );
+        if let Err(error) = deck().write().add_agent(
+            aid.clone(),
+            Runner::Pooled(id),
+            thread_priority,
+            control_block,
+        ) {
+            pool::remove(id);
+            return Err(error);
+        }
+        events::emit(|| Event::AgentAdded(aid.clone()));
+        Ok(aid)
+    }
 
-        // check prio
-        if priority == ThreadPriorityValue::MAX {
-            return Err(ErrorCode::InvalidPriority(
-                "Max priority only allowed for Services",
+    fn spawn_async_agent<T: AsyncBehavior + 'static>(
+        &self,
+        nickname: Arc<str>,
+        priority: u8,
+        build: impl FnOnce(Agent) -> T,
+    ) -> Result<Description, ErrorCode> {
+        if simulation::is_active() {
+            return Err(ErrorCode::InvalidRequest(
+                "asynchronous agents cannot be simulated".to_string(),
             ));
         }
-        let thread_priority =
-            ThreadPriority::try_from(priority).map_err(ErrorCode::InvalidPriority)?;
-
-        // spawn agent with spinlock
-        let agent = T::agent_with_param_builder(base_agent, param);
-        let agent_handle = thread::Builder::new()
-            .stack_size(stack_size)
-            .spawn_with_priority(ThreadPriority::Min, move |_| {
-                execute(agent);
-            });
+        validate_nickname(&nickname).map_err(ErrorCode::InvalidName)?;
 
-        // register on env
-        let join_handle = agent_handle.map_err(|_| ErrorCode::AgentPanic)?;
+        let (tx, rx) = mailbox::channel(1);
+        let aid = Description::new(nickname, self.name.clone(), tx);
+        if deck().read().search_agent(&aid).is_ok() {
+            return Err(ErrorCode::Duplicated);
+        }
+        let thread_priority = agent_priority(priority)?;
 
-        //Build description and insert in env lock
-        aid.set_id(join_handle.thread().id());
-        deck()
-            .write()
-            .add_agent(aid.clone(), join_handle, thread_priority, control_block)?;
+        let control_block = Arc::new(ControlBlock::new(priority, 0));
+        let signal = Arc::new(Signal::default());
+        let waker = signal.clone();
+        rx.set_waker(move || waker.wake());
+        let agent = build(Agent::new_async(
+            aid.clone(),
+            rx,
+            control_block.clone(),
+            signal.clone(),
+        ));
+        deck().write().add_agent(
+            aid.clone(),
+            Runner::Async(signal),
+            thread_priority,
+            control_block,
+        )?;
+        executor::spawn(Box::pin(execute_async(agent)));
+        events::emit(|| Event::AgentAdded(aid.clone()));
         Ok(aid)
     }
 
     /// Transition the agent from the initiated state into the active state, required for it to execute its behavior.
+    ///  For an agent with its own thread, it returns once the thread leaves its start gate,
+    ///  or with [`ErrorCode::Timeout`] if it did not within [`START_TIMEOUT`];
+    ///  the agent is started nonetheless and runs once its thread is scheduled.
     pub fn start(&self, aid: &Description) -> Result<(), ErrorCode> {
         let guard = deck().read();
         let entry = guard.get_agent(aid)?;
         let thread = entry.thread();
-        if thread
-            .get_priority()
-            .map_err(ErrorCode::AgentStart)?
-            .eq(&ThreadPriority::Min)
-        {
-            return Err(ErrorCode::AgentPanic);
-        }
-        let priority = entry.priority();
-        if let Err(error) = thread.set_priority(priority) {
-            return Err(ErrorCode::AgentStart(error));
-        }
-        entry.control_block().active()
+        let gate = thread.is_some().then(|| entry.control_block());
+        match thread {
+            Some(thread) => {
+                if thread
+                    .get_priority()
+                    .map_err(ErrorCode::AgentStart)?
+                    .eq(&ThreadPriority::Min)
+                {
+                    return Err(ErrorCode::AgentPanic);
+                }
+                let priority = entry.priority();
+                if let Err(error) = thread.set_priority(priority) {
+                    return Err(ErrorCode::AgentStart(error));
+                }
+                entry.control_block().active()?;
+            }
+            None => {
+                entry.control_block().active()?;
+                entry.runner.wake();
+            }
+        }
+        drop(guard);
+        events::emit(|| Event::AgentStarted(aid.clone()));
+        events::emit(|| {
+            Event::StateChanged(aid.clone(), AgentState::Initiated, AgentState::Active)
+        });
+        match gate {
+            // under simulation the thread only runs once the simulation schedules it
+            Some(control_block) if !simulation::is_active() => {
+                control_block.await_gate(START_TIMEOUT)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Start every agent that has not been started yet, as in [`start`](Self::start),
+    ///  from the highest priority down and in the order they were added for agents of equal priority.
+    ///  It stops at the first agent that fails to start.
+    pub fn start_all(&self) -> Result<(), ErrorCode> {
+        let order = deck().read().start_order();
+        for aid in &order {
+            self.start(aid)?;
+        }
+        Ok(())
+    }
+
+    /// Suspend the agent, which halts before its next [`Behavior::action`] until resumed.
+    ///  A blocking call in progress returns [`ErrorCode::Interrupted`].
+    pub fn suspend(&self, aid: &Description) -> Result<(), ErrorCode> {
+        self.change_state(aid, AgentState::Suspended)
+    }
+
+    /// Resume the agent from the suspended or waiting states.
+    pub fn resume(&self, aid: &Description) -> Result<(), ErrorCode> {
+        self.change_state(aid, AgentState::Active)
+    }
+
+    /// Terminate the agent and remove it from the platform.
+    ///  Its thread is not joined, it finishes on its own once the agent checks its state before the next [`Behavior::action`].
+    ///  A blocking call in progress returns [`ErrorCode::Interrupted`].
+    pub fn terminate(&self, aid: &Description) -> Result<(), ErrorCode> {
+        self.change_state(aid, AgentState::Terminated)?;
+        let entry = deck().write().remove_agent(aid)?;
+        entry.runner.wake();
+        Ok(())
+    }
+
+    fn change_state(&self, aid: &Description, state: AgentState) -> Result<(), ErrorCode> {
+        let previous = deck().write().modify_agent(aid, state)?;
+        events::emit(|| Event::StateChanged(aid.clone(), previous, state));
+        Ok(())
+    }
+
+    /// Register a callback that is run for every [`Event`] produced by the platform.
+    ///  The callback runs on the thread producing the event, so it should return quickly and must not subscribe or unsubscribe.
+    pub fn subscribe(&self, callback: impl Fn(&Event) + Send + Sync + 'static) -> SubscriptionId {
+        events::subscribe(callback)
+    }
+
+    /// Register a channel that receives every [`Event`] produced by the platform.
+    ///  The subscription ends when the receiver is dropped or [`unsubscribe`](Self::unsubscribe) is called.
+    pub fn subscribe_channel(&self) -> (SubscriptionId, Receiver<Event>) {
+        events::subscribe_channel()
+    }
+
+    /// Stop delivering events to the given subscriber.
+    pub fn unsubscribe(&self, id: SubscriptionId) {
+        events::unsubscribe(id)
+    }
+
+    /// Set the owner of an agent, which is then allowed to act upon it under [`OwnershipConditions`](crate::service::OwnershipConditions).
+    pub fn set_owner(&self, aid: &Description, owner: &Description) -> Result<(), ErrorCode> {
+        let mut guard = deck().write();
+        guard.search_agent(owner)?;
+        guard.set_owner(aid, owner.clone())
+    }
+
+    /// Set the [`Role`] of an agent within the platform.
+    pub fn set_role(&self, aid: &Description, role: Role) -> Result<(), ErrorCode> {
+        deck().write().set_role(aid, role)
+    }
+
+    /// Pin the agent to the given core, which takes effect before its next [`Behavior::action`].
+    ///  Lightweight and asynchronous agents have no thread of their own and cannot be pinned.
+    pub fn set_affinity(&self, aid: &Description, core: usize) -> Result<(), ErrorCode> {
+        let cores = thread::available_parallelism().map_or(1, |x| x.get());
+        if core >= cores {
+            return Err(ErrorCode::InvalidAffinity(core));
+        }
+        let guard = deck().read();
+        let entry = guard.get_agent(aid)?;
+        if entry.thread().is_none() {
+            return Err(ErrorCode::InvalidRequest(format!(
+                "cannot pin agent {} without a thread",
+                aid
+            )));
+        }
+        entry.control_block().set_affinity(core);
+        Ok(())
+    }
+
+    /// Let the agent send on the messages embedded in the [`MessageType::Proxy`](crate::messaging::MessageType::Proxy)
+    ///  and [`MessageType::Propagate`](crate::messaging::MessageType::Propagate) it receives, before its behavior gets them.
+    pub fn set_forwarding(&self, aid: &Description, enabled: bool) -> Result<(), ErrorCode> {
+        deck()
+            .read()
+            .get_agent(aid)?
+            .control_block()
+            .set_forwarding(enabled);
+        Ok(())
+    }
+
+    /// Set the number of messages the mailbox of the agent can hold.
+    pub fn set_mailbox_capacity(
+        &self,
+        aid: &Description,
+        capacity: usize,
+    ) -> Result<(), ErrorCode> {
+        if capacity == 0 {
+            return Err(ErrorCode::InvalidCapacity(capacity));
+        }
+        deck().read().set_mailbox_capacity(aid, capacity)
+    }
+
+    /// Set how the mailbox of the agent chooses between its priority lanes, [`LanePolicy::Strict`] by default.
+    pub fn set_lane_policy(&self, aid: &Description, policy: LanePolicy) -> Result<(), ErrorCode> {
+        deck().read().set_lane_policy(aid, policy)
+    }
+
+    /// Get a snapshot of the state and counters of every agent in the platform.
+    pub fn agents(&self) -> Vec<AgentStats> {
+        deck().read().agent_stats()
+    }
+
+    /// Start writing every delivered message to a journal file, along with its timestamp and the state of its receiver.
+    ///  The file is replaced if it exists, and is read back with [`Journal::load`](crate::journal::Journal::load).
+    pub fn record_journal(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        journal::start(path.as_ref())
+    }
+
+    /// Stop journaling the delivered messages and flush the journal file.
+    pub fn stop_journal(&self) -> io::Result<()> {
+        journal::stop()
+    }
+
+    /// Get the messages that could not be delivered, oldest first, along with the reason.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        dead_letter::list(|_| true)
+    }
+
+    /// Send a dead letter again to the agent currently registered under the name of its receiver.
+    ///  The redelivered message does not expire, and it becomes a new dead letter if it still cannot be delivered.
+    pub fn redeliver(&self, id: DeadLetterId) -> Result<(), ErrorCode> {
+        dead_letter::redeliver(id, |_| true)
+    }
+
+    /// Drop every dead letter.
+    pub fn clear_dead_letters(&self) {
+        dead_letter::clear()
     }
 
     //COULD ADD PLATFORM FUNCTIONS AND CALL THEM FROM AMS AGENT
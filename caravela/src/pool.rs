@@ -0,0 +1,335 @@
+use crate::{
+    agent::ControlBlockArc,
+    entity::Description,
+    events::{self, Event},
+    ErrorCode, DEFAULT_STACK,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, OnceLock,
+    },
+    task, thread,
+    time::Instant,
+};
+
+/// Stack of the worker threads, which run the actions of many different agents.
+const WORKER_STACK: usize = 4 * DEFAULT_STACK;
+
+static POOL: OnceLock<Pool> = OnceLock::new();
+static WORKERS: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Identifier of a lightweight agent within the worker pool.
+pub(crate) type TaskId = u64;
+
+/// Reason for a lightweight agent to stop its step before the end of its action.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Yield {
+    /// The agent waits for a message to arrive in its mailbox.
+    Receive,
+    /// The agent waits until the given instant.
+    Sleep(Instant),
+    /// The agent waits for room in the mailbox of the receiver of a message.
+    Send,
+}
+
+/// Outcome of a step of a lightweight agent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Step {
+    /// The agent can run again right away.
+    Ready,
+    /// The agent yielded before it could complete its step.
+    Yielded(Yield),
+    /// The agent waits to be started or resumed.
+    Parked,
+    /// The agent finished its behavior.
+    Finished,
+}
+
+/// An agent multiplexed onto the worker pool, executed one step at a time.
+pub(crate) trait Task: Send {
+    /// Run a single scheduling quantum of the agent.
+    fn step(&mut self) -> Step;
+}
+
+/// Cause of a wake up of a lightweight agent.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Wake {
+    /// A message arrived in its mailbox.
+    Message,
+    /// Its state was changed by the platform.
+    Control,
+    /// A message was taken from a full mailbox it sends to.
+    Room,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Slot {
+    Queued,
+    Running {
+        message: bool,
+        room: bool,
+        control: bool,
+    },
+    Receiving,
+    Sending,
+    Sleeping(Instant),
+    Parked,
+}
+
+struct Entry {
+    task: Option<Box<dyn Task>>,
+    aid: Description,
+    control_block: ControlBlockArc,
+    slot: Slot,
+}
+
+#[derive(Default)]
+struct State {
+    tasks: HashMap<TaskId, Entry>,
+    ready: BinaryHeap<(u8, Reverse<u64>, TaskId)>,
+    sleeping: BTreeSet<(Instant, TaskId)>,
+    sequence: u64,
+}
+
+impl State {
+    /// Queue the task behind every other one with the same priority.
+    fn queue(&mut self, id: TaskId) {
+        let Some(entry) = self.tasks.get_mut(&id) else {
+            return;
+        };
+        if let Slot::Sleeping(until) = entry.slot {
+            self.sleeping.remove(&(until, id));
+        }
+        entry.slot = Slot::Queued;
+        self.sequence += 1;
+        let priority = entry.control_block.priority_level();
+        self.ready.push((priority, Reverse(self.sequence), id));
+    }
+
+    /// Queue every sleeping task whose time has come.
+    fn wake_sleepers(&mut self, now: Instant) {
+        while let Some(&(until, id)) = self.sleeping.first() {
+            if until > now {
+                break;
+            }
+            self.queue(id);
+        }
+    }
+
+    fn wake(&mut self, id: TaskId, cause: Wake) -> bool {
+        let Some(entry) = self.tasks.get_mut(&id) else {
+            return false;
+        };
+        match (&mut entry.slot, cause) {
+            (Slot::Running { message, .. }, Wake::Message) => *message = true,
+            (Slot::Running { room, .. }, Wake::Room) => *room = true,
+            (Slot::Running { control, .. }, Wake::Control) => *control = true,
+            (Slot::Receiving, _)
+            | (Slot::Sending, Wake::Room | Wake::Control)
+            | (Slot::Sleeping(_) | Slot::Parked, Wake::Control) => {
+                self.queue(id);
+                return true;
+            }
+            _ => (),
+        }
+        false
+    }
+
+    /// Put the task back after a step, queueing it again if it was woken while running.
+    fn settle(&mut self, id: TaskId, task: Box<dyn Task>, step: Step) -> bool {
+        if step == Step::Finished {
+            self.tasks.remove(&id);
+            return false;
+        }
+        let Some(entry) = self.tasks.get_mut(&id) else {
+            return false;
+        };
+        let Slot::Running {
+            message,
+            room,
+            control,
+        } = entry.slot
+        else {
+            return false;
+        };
+        entry.task = Some(task);
+        let slot = match step {
+            Step::Ready => Slot::Queued,
+            _ if control => Slot::Queued,
+            Step::Yielded(Yield::Receive) if message => Slot::Queued,
+            Step::Yielded(Yield::Receive) => Slot::Receiving,
+            Step::Yielded(Yield::Send) if room => Slot::Queued,
+            Step::Yielded(Yield::Send) => Slot::Sending,
+            Step::Yielded(Yield::Sleep(until)) => Slot::Sleeping(until),
+            Step::Parked | Step::Finished => Slot::Parked,
+        };
+        match slot {
+            Slot::Queued => {
+                self.queue(id);
+                true
+            }
+            Slot::Sleeping(until) => {
+                entry.slot = slot;
+                self.sleeping.insert((until, id));
+                false
+            }
+            _ => {
+                entry.slot = slot;
+                false
+            }
+        }
+    }
+}
+
+struct Pool {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+impl Pool {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("Worker pool is poisoned - Lost agents")
+    }
+
+    /// Take the highest priority queued task, running due sleepers first and waiting while there is none.
+    fn next<'a>(&self, mut state: MutexGuard<'a, State>) -> (MutexGuard<'a, State>, TaskId) {
+        loop {
+            let now = Instant::now();
+            state.wake_sleepers(now);
+            if let Some((_, _, id)) = state.ready.pop() {
+                return (state, id);
+            }
+            state = match state.sleeping.first() {
+                Some(&(until, _)) => {
+                    self.ready
+                        .wait_timeout(state, until - now)
+                        .expect("Worker pool is poisoned - Lost agents")
+                        .0
+                }
+                None => self
+                    .ready
+                    .wait(state)
+                    .expect("Worker pool is poisoned - Lost agents"),
+            };
+        }
+    }
+
+    fn work(&self) {
+        let mut state = self.lock();
+        loop {
+            let id;
+            (state, id) = self.next(state);
+            let Some(entry) = state.tasks.get_mut(&id) else {
+                continue;
+            };
+            let Some(mut task) = entry.task.take() else {
+                continue;
+            };
+            entry.slot = Slot::Running {
+                message: false,
+                room: false,
+                control: false,
+            };
+            let aid = entry.aid.clone();
+            drop(state);
+            let step = panic::catch_unwind(AssertUnwindSafe(|| task.step())).unwrap_or_else(|_| {
+                events::emit(|| Event::AgentPanicked(aid));
+                Step::Finished
+            });
+            state = self.lock();
+            if state.settle(id, task, step) {
+                self.ready.notify_one();
+            }
+        }
+    }
+}
+
+fn pool() -> &'static Pool {
+    POOL.get_or_init(|| {
+        let workers = match WORKERS.load(Ordering::Acquire) {
+            0 => thread::available_parallelism().map_or(1, |x| x.get()),
+            workers => workers,
+        };
+        for index in 0..workers {
+            let _ = thread::Builder::new()
+                .name(format!("caravela-worker-{}", index))
+                .stack_size(WORKER_STACK)
+                .spawn(|| pool().work());
+        }
+        Pool {
+            state: Mutex::default(),
+            ready: Condvar::new(),
+        }
+    })
+}
+
+/// Choose the number of worker threads, before the first lightweight agent is added.
+pub(crate) fn set_workers(workers: usize) -> Result<(), ErrorCode> {
+    if workers == 0 {
+        return Err(ErrorCode::InvalidCapacity(workers));
+    }
+    if POOL.get().is_some() {
+        return Err(ErrorCode::Duplicated);
+    }
+    WORKERS.store(workers, Ordering::Release);
+    Ok(())
+}
+
+/// Reserve the identifier of a lightweight agent, so its mailbox can wake it before it is added.
+pub(crate) fn next_id() -> TaskId {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Add a lightweight agent to the pool. It stays parked until it is woken.
+pub(crate) fn add(
+    id: TaskId,
+    aid: Description,
+    control_block: ControlBlockArc,
+    task: Box<dyn Task>,
+) {
+    pool().lock().tasks.insert(
+        id,
+        Entry {
+            task: Some(task),
+            aid,
+            control_block,
+            slot: Slot::Parked,
+        },
+    );
+}
+
+/// Wake a lightweight agent, which is queued again if it was waiting for this cause.
+pub(crate) fn wake(id: TaskId, cause: Wake) {
+    let Some(pool) = POOL.get() else {
+        return;
+    };
+    if pool.lock().wake(id, cause) {
+        pool.ready.notify_one();
+    }
+}
+
+/// Waker left with a full mailbox, which queues the lightweight agent again once there is room.
+struct RoomWaker(TaskId);
+
+impl task::Wake for RoomWaker {
+    fn wake(self: Arc<Self>) {
+        wake(self.0, Wake::Room);
+    }
+}
+
+/// Get a waker for a lightweight agent that yielded on a full mailbox.
+pub(crate) fn room_waker(id: TaskId) -> task::Waker {
+    Arc::new(RoomWaker(id)).into()
+}
+
+/// Remove a lightweight agent that could not be registered.
+pub(crate) fn remove(id: TaskId) {
+    pool().lock().tasks.remove(&id);
+}
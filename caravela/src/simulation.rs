@@ -0,0 +1,396 @@
+use crate::{
+    agent::{AgentState, ControlBlockArc},
+    entity::Description,
+    messaging::Message,
+    ErrorCode,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex, MutexGuard, OnceLock,
+    },
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+/// Longest real time the scheduler waits for a service to finish processing a request.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+/// Whether or not the platform runs as a simulation.
+pub(crate) fn is_active() -> bool {
+    ACTIVE.load(Ordering::Acquire)
+}
+
+/// Reason why a simulated agent handed control back to the scheduler.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Blocked {
+    /// The agent thread has not reached its behavior yet.
+    Spawning,
+    /// The agent waits for the platform to start it.
+    Start,
+    /// The agent finished a step and can run again.
+    Ready,
+    /// The agent waits until the virtual clock reaches the given time.
+    Sleep(Duration),
+    /// The agent waits for a message to arrive in its mailbox.
+    Receive,
+    /// The agent waits to be resumed.
+    Suspended,
+    /// The agent finished its behavior.
+    Finished,
+}
+
+/// A step executed by the simulation, recorded to replay the order in which agents ran.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimulationStep {
+    /// Virtual time at which the step started.
+    pub time: Duration,
+    /// The agent that ran during the step.
+    pub agent: Description,
+}
+
+#[derive(Debug)]
+struct SimAgent {
+    aid: Description,
+    control_block: ControlBlockArc,
+    order: usize,
+    blocked: Blocked,
+    last_step: u64,
+}
+
+impl SimAgent {
+    fn is_runnable(&self, now: Duration) -> bool {
+        let state = self.control_block.agent_state();
+        match self.blocked {
+            Blocked::Spawning | Blocked::Finished => false,
+            Blocked::Start => state != AgentState::Initiated,
+            Blocked::Ready => true,
+            Blocked::Sleep(until) => until <= now,
+            Blocked::Receive => self.aid.address().len() > 0,
+            Blocked::Suspended => state != AgentState::Suspended,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    now: Duration,
+    running: Option<ThreadId>,
+    agents: HashMap<ThreadId, SimAgent>,
+    deliveries: BTreeMap<(Duration, u64), Message>,
+    sequence: u64,
+    rng: u64,
+    max_latency: Duration,
+    steps: u64,
+    history: Vec<SimulationStep>,
+    awaiting: Option<Description>,
+}
+
+impl State {
+    /// Deterministic pseudo-random numbers (SplitMix64) drawn from the seed of the simulation.
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn latency(&mut self) -> Duration {
+        let range = self.max_latency.as_micros() as u64;
+        if range == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_micros(self.next_random() % (range + 1))
+    }
+
+    /// Queue a message for delivery at the given time, after those already queued for the same time.
+    fn enqueue(&mut self, time: Duration, msg: Message) {
+        self.sequence += 1;
+        self.deliveries.insert((time, self.sequence), msg);
+    }
+
+    /// Place every due message in the mailbox of its receiver, keeping those that do not fit for later.
+    fn deliver(&mut self) {
+        let due: Vec<_> = self
+            .deliveries
+            .range(..=(self.now, u64::MAX))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in due {
+            if let Some(msg) = self.deliveries.remove(&key) {
+                let address = msg.receiver().address().clone();
+                if let Err(ErrorCode::ChannelFull) = address.try_send(msg.clone()) {
+                    self.deliveries.insert(key, msg);
+                }
+            }
+        }
+    }
+
+    /// Highest priority runnable agent, the one that ran least recently among equals.
+    fn next_runnable(&self) -> Option<ThreadId> {
+        self.agents
+            .iter()
+            .filter(|(_, agent)| agent.is_runnable(self.now))
+            .max_by_key(|(_, agent)| {
+                (
+                    agent.control_block.priority_level(),
+                    std::cmp::Reverse(agent.last_step),
+                    std::cmp::Reverse(agent.order),
+                )
+            })
+            .map(|(id, _)| *id)
+    }
+
+    /// Earliest virtual time at which something is bound to happen.
+    fn next_event(&self) -> Option<Duration> {
+        let delivery = self.deliveries.keys().next().map(|(time, _)| *time);
+        let wake = self
+            .agents
+            .values()
+            .filter_map(|agent| match agent.blocked {
+                Blocked::Sleep(until) => Some(until),
+                _ => None,
+            });
+        wake.chain(delivery).filter(|x| *x > self.now).min()
+    }
+}
+
+#[derive(Debug)]
+struct Scheduler {
+    state: Mutex<State>,
+    baton: Condvar,
+}
+
+impl Scheduler {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .expect("Simulation is poisoned - Lost schedule")
+    }
+
+    fn wait<'a>(&self, guard: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.baton
+            .wait(guard)
+            .expect("Simulation is poisoned - Lost schedule")
+    }
+}
+
+fn scheduler() -> &'static Scheduler {
+    SCHEDULER.get().expect("Simulation has not been created")
+}
+
+/// Turn the platform into a simulation driven by the given seed.
+pub(crate) fn activate(seed: u64) -> Simulation {
+    let scheduler = Scheduler {
+        state: Mutex::new(State {
+            now: Duration::ZERO,
+            running: None,
+            agents: HashMap::new(),
+            deliveries: BTreeMap::new(),
+            sequence: 0,
+            rng: seed,
+            max_latency: Duration::from_millis(1),
+            steps: 0,
+            history: Vec::new(),
+            awaiting: None,
+        }),
+        baton: Condvar::new(),
+    };
+    let _ = SCHEDULER.set(scheduler);
+    ACTIVE.store(true, Ordering::Release);
+    Simulation { _private: () }
+}
+
+/// Add a newly spawned agent to the simulation.
+pub(crate) fn register(aid: Description, control_block: ControlBlockArc) {
+    let Some(id) = aid.id() else {
+        return;
+    };
+    let scheduler = scheduler();
+    let mut state = scheduler.lock();
+    let order = state.agents.len();
+    state.agents.insert(
+        id,
+        SimAgent {
+            aid,
+            control_block,
+            order,
+            blocked: Blocked::Spawning,
+            last_step: 0,
+        },
+    );
+    scheduler.baton.notify_all();
+}
+
+/// Hand control back to the scheduler from the agent thread and wait until it is scheduled again.
+pub(crate) fn block(reason: Blocked) {
+    let id = thread::current().id();
+    let scheduler = scheduler();
+    let mut state = scheduler.lock();
+    while !state.agents.contains_key(&id) {
+        state = scheduler.wait(state);
+    }
+    if let Some(agent) = state.agents.get_mut(&id) {
+        agent.blocked = reason;
+    }
+    if state.running == Some(id) {
+        state.running = None;
+    }
+    scheduler.baton.notify_all();
+    if reason == Blocked::Finished {
+        return;
+    }
+    while state.running != Some(id) {
+        state = scheduler.wait(state);
+    }
+}
+
+/// Block the agent thread until the virtual clock has advanced by `duration`.
+pub(crate) fn sleep(duration: Duration) {
+    let until = scheduler().lock().now + duration;
+    block(Blocked::Sleep(until));
+}
+
+/// Take the message to be delivered by the simulation if its receiver is a simulated agent,
+///  otherwise it is given back to be sent right away.
+pub(crate) fn schedule(msg: Message) -> Option<Message> {
+    if !is_active() {
+        return Some(msg);
+    }
+    let scheduler = scheduler();
+    let mut state = scheduler.lock();
+    let from_agent = state.agents.contains_key(&thread::current().id());
+    let to_agent = msg
+        .receiver()
+        .id()
+        .is_some_and(|id| state.agents.contains_key(&id));
+    if !to_agent {
+        if from_agent {
+            state.awaiting = Some(msg.receiver().clone());
+        }
+        return Some(msg);
+    }
+    let latency = if from_agent {
+        state.latency()
+    } else {
+        Duration::ZERO
+    };
+    let time = state.now + latency;
+    state.enqueue(time, msg);
+    scheduler.baton.notify_all();
+    None
+}
+
+/// Queue a message to be delivered once the virtual clock has advanced by `delay`.
+pub(crate) fn schedule_after(msg: Message, delay: Duration) {
+    let scheduler = scheduler();
+    let mut state = scheduler.lock();
+    let time = state.now + delay;
+    state.enqueue(time, msg);
+    scheduler.baton.notify_all();
+}
+
+/// Handle to drive a simulated platform.
+///  Only one agent runs at a time, handing control back to the simulation every time it completes an action,
+///  waits, receives or is suspended, so the execution is equivalent to stepping every agent from a single thread.
+///  Agents are stepped in priority order and [`Agent::wait`](crate::agent::Agent::wait) advances a virtual clock instead of sleeping.
+///  Messages between agents are delivered after a latency drawn from the seed of the simulation,
+///  so the same seed always reproduces the same execution.
+#[derive(Debug)]
+pub struct Simulation {
+    _private: (),
+}
+
+impl Simulation {
+    /// Get the current virtual time of the simulation.
+    pub fn now(&self) -> Duration {
+        scheduler().lock().now
+    }
+
+    /// Set the largest latency that can be drawn for the delivery of a message between agents. One millisecond by default.
+    pub fn set_max_latency(&self, latency: Duration) {
+        scheduler().lock().max_latency = latency;
+    }
+
+    /// Get the steps executed so far, in order.
+    pub fn history(&self) -> Vec<SimulationStep> {
+        scheduler().lock().history.clone()
+    }
+
+    /// Run a single step of the next agent, advancing the virtual clock if no agent can run at the current time.
+    ///  Returns the agent that ran, or `None` if no agent can run anymore.
+    pub fn step(&self) -> Option<Description> {
+        self.advance(None)
+    }
+
+    /// Run steps until no agent can run anymore. Returns the number of steps executed.
+    pub fn run(&self) -> usize {
+        std::iter::from_fn(|| self.step()).count()
+    }
+
+    /// Run steps until the virtual clock reaches `deadline` or no agent can run anymore.
+    ///  Returns the number of steps executed.
+    pub fn run_until(&self, deadline: Duration) -> usize {
+        std::iter::from_fn(|| self.advance(Some(deadline))).count()
+    }
+
+    fn advance(&self, deadline: Option<Duration>) -> Option<Description> {
+        let scheduler = scheduler();
+        let mut state = scheduler.lock();
+        while state.running.is_some()
+            || state
+                .agents
+                .values()
+                .any(|agent| agent.blocked == Blocked::Spawning)
+        {
+            state = scheduler.wait(state);
+        }
+        loop {
+            state.deliver();
+            if let Some(id) = state.next_runnable() {
+                state.steps += 1;
+                let (step, now) = (state.steps, state.now);
+                let agent = state.agents.get_mut(&id)?;
+                agent.last_step = step;
+                let aid = agent.aid.clone();
+                state.history.push(SimulationStep {
+                    time: now,
+                    agent: aid.clone(),
+                });
+                state.running = Some(id);
+                scheduler.baton.notify_all();
+                while state.running == Some(id) {
+                    state = scheduler.wait(state);
+                }
+                let awaiting = state.awaiting.take();
+                drop(state);
+                if let Some(service) = awaiting {
+                    settle(&service);
+                }
+                return Some(aid);
+            }
+            match state.next_event() {
+                Some(time) if deadline.is_none_or(|x| time <= x) => state.now = time,
+                _ => {
+                    if let Some(deadline) = deadline {
+                        state.now = state.now.max(deadline);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Wait for a service to process the requests sent by the last agent that ran.
+fn settle(service: &Description) {
+    let started = Instant::now();
+    while !service.address().is_idle() && started.elapsed() < SETTLE_TIMEOUT {
+        thread::yield_now();
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    entity::{
+        agent::{
+            behavior::{iterate, Behavior},
+            Agent, AgentBuild, AgentBuildParam, ControlBlock,
+        },
+        messaging::{mailbox, Content, Message, MessageType, Reason},
+        Description,
+    },
+    ErrorCode, DEFAULT_STACK,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// Name of the platform agents under test believe they run in.
+pub const TEST_HAP: &str = "test";
+/// Number of messages the mailbox of an agent under test can hold.
+const MAILBOX_CAPACITY: usize = 64;
+/// Priority given to agents under test.
+const TEST_PRIORITY: u8 = 1;
+
+/// How the fake AMS of a [`TestHarness`] answers the requests of the agent under test.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub enum AmsReply {
+    /// Reply with [`MessageType::Agree`] followed by [`MessageType::Inform`], as when the request succeeds.
+    #[default]
+    Accept,
+    /// Reply with [`MessageType::Refuse`] and the given [`Reason`].
+    Refuse(Reason),
+    /// Reply with [`MessageType::Agree`] followed by [`MessageType::Failure`] with the given [`Reason`].
+    Failure(Reason),
+    /// Do not reply at all.
+    Silent,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    peers: HashMap<String, Description>,
+    outbox: Vec<Message>,
+    ams_reply: AmsReply,
+    waited: Duration,
+}
+
+/// Platform context of an agent built by a [`TestHarness`].
+///  It stands in for the deck and the messaging of a real platform, so nothing is shared between harnesses.
+#[derive(Debug)]
+pub(crate) struct MockContext {
+    aid: Description,
+    ams: Description,
+    state: Mutex<MockState>,
+}
+
+pub(crate) type MockContextArc = Arc<MockContext>;
+
+impl MockContext {
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state
+            .lock()
+            .expect("Mock context is poisoned - Lost messages")
+    }
+
+    pub(crate) fn aid(&self) -> &Description {
+        &self.aid
+    }
+
+    pub(crate) fn ams(&self) -> &Description {
+        &self.ams
+    }
+
+    /// Find a peer or the AMS by its full name.
+    pub(crate) fn get_aid_from_name(&self, name: &str) -> Result<Description, ErrorCode> {
+        if self.ams.name() == name {
+            return Ok(self.ams.clone());
+        }
+        self.state()
+            .peers
+            .values()
+            .find(|x| x.name() == name)
+            .cloned()
+            .ok_or(ErrorCode::NotFound)
+    }
+
+    /// Capture a message sent by the agent, answering it if it is a request for the AMS.
+    pub(crate) fn send(&self, msg: Message) -> Result<(), ErrorCode> {
+        let replies = if *msg.receiver() == self.ams && *msg.message_type() == MessageType::Request
+        {
+            self.ams_replies(msg.content())
+        } else {
+            Vec::new()
+        };
+        self.state().outbox.push(msg);
+        for (message_type, content) in replies {
+            let reply = Message::new(self.ams.clone(), self.aid.clone(), message_type, content);
+            self.aid.address().try_send(reply)?;
+        }
+        Ok(())
+    }
+
+    fn ams_replies(&self, content: &Content) -> Vec<(MessageType, Content)> {
+        let Content::Action(action) = content else {
+            return vec![(MessageType::NotUnderstood, content.clone())];
+        };
+        match self.state().ams_reply.clone() {
+            AmsReply::Accept => vec![
+                (MessageType::Agree, content.clone()),
+                (MessageType::Inform, content.clone()),
+            ],
+            AmsReply::Refuse(reason) => {
+                vec![(MessageType::Refuse, Content::Reason(action.clone(), reason))]
+            }
+            AmsReply::Failure(reason) => vec![
+                (MessageType::Agree, content.clone()),
+                (
+                    MessageType::Failure,
+                    Content::Reason(action.clone(), reason),
+                ),
+            ],
+            AmsReply::Silent => Vec::new(),
+        }
+    }
+
+    /// Account for the time the agent asked to wait, without sleeping.
+    pub(crate) fn wait(&self, duration: Duration) {
+        self.state().waited += duration;
+    }
+}
+
+/// Drives a single agent step by step without a [`Platform`](crate::Platform) nor any thread.
+///
+/// Messages sent by the agent are captured instead of delivered, messages can be injected into its mailbox,
+///  and requests for the AMS are answered according to the configured [`AmsReply`].
+///  [`Agent::receive`] returns [`ErrorCode::MpscRecv`] instead of blocking when the mailbox is empty,
+///  and [`Agent::wait`] returns right away, adding up the time in [`waited`](Self::waited).
+///  Any agent built with `make_agent!` or `make_agent_with_param!` can be tested, and harnesses are independent
+///  of each other and of any running platform.
+#[derive(Debug)]
+pub struct TestHarness<T: Behavior> {
+    behavior: T,
+    context: MockContextArc,
+    finished: bool,
+}
+
+impl<T: Behavior + AgentBuild> TestHarness<T> {
+    /// Build an agent of type `T` with the given nickname.
+    pub fn new(nickname: impl Into<Arc<str>>) -> Self {
+        Self::build(nickname, T::agent_builder)
+    }
+}
+
+impl<T: Behavior + AgentBuildParam> TestHarness<T> {
+    /// Build an agent of type `T` with the given nickname and parameter.
+    pub fn new_with_param(nickname: impl Into<Arc<str>>, param: T::Parameter) -> Self {
+        Self::build(nickname, |agent| T::agent_with_param_builder(agent, param))
+    }
+}
+
+impl<T: Behavior> TestHarness<T> {
+    fn build(nickname: impl Into<Arc<str>>, builder: impl FnOnce(Agent) -> T) -> Self {
+        let nickname = nickname.into();
+        let (tx, rx) = mailbox::channel(MAILBOX_CAPACITY);
+        let (ams_tx, _) = mailbox::channel(1);
+        let context = Arc::new(MockContext {
+            aid: Description::new(nickname.clone(), TEST_HAP, tx),
+            ams: Description::new("ams", TEST_HAP, ams_tx),
+            state: Mutex::default(),
+        });
+        let control_block = Arc::new(ControlBlock::new(TEST_PRIORITY, DEFAULT_STACK));
+        let _ = control_block.active();
+        let agent = Agent::new_mock(
+            nickname,
+            TEST_HAP.into(),
+            rx,
+            control_block,
+            context.clone(),
+        );
+        Self {
+            behavior: builder(agent),
+            context,
+            finished: false,
+        }
+    }
+
+    /// Get the agent under test.
+    pub fn behavior(&self) -> &T {
+        &self.behavior
+    }
+
+    /// Get the agent under test as mutable, to inspect or arrange its fields.
+    pub fn behavior_mut(&mut self) -> &mut T {
+        &mut self.behavior
+    }
+
+    /// Get the [`Description`] of the agent under test.
+    pub fn aid(&self) -> &Description {
+        self.context.aid()
+    }
+
+    /// Get the [`Description`] of the fake AMS.
+    pub fn ams(&self) -> &Description {
+        self.context.ams()
+    }
+
+    /// Make an agent with the given nickname known to the agent under test, so it can be addressed by name.
+    ///  Messages sent to peers are only captured.
+    pub fn add_peer(&self, nickname: &str) -> Description {
+        let (tx, _) = mailbox::channel(1);
+        let peer = Description::new(nickname, TEST_HAP, tx);
+        self.context
+            .state()
+            .peers
+            .insert(nickname.to_string(), peer.clone());
+        peer
+    }
+
+    /// Choose how the fake AMS answers the following requests. Requests are accepted by default.
+    pub fn set_ams_reply(&self, reply: AmsReply) {
+        self.context.state().ams_reply = reply;
+    }
+
+    /// Place a message from `sender` in the mailbox of the agent under test.
+    pub fn inject(
+        &self,
+        sender: &Description,
+        message_type: MessageType,
+        content: Content,
+    ) -> Result<(), ErrorCode> {
+        let msg = Message::new(sender.clone(), self.aid().clone(), message_type, content);
+        self.aid().address().try_send(msg)
+    }
+
+    /// Run [`Behavior::setup`].
+    pub fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.behavior.setup()
+    }
+
+    /// Run [`Behavior::action`] alone, without the FDIR functions nor [`Behavior::done`].
+    pub fn action(&mut self) -> Result<(), ErrorCode> {
+        self.behavior.action()
+    }
+
+    /// Run [`Behavior::done`] alone.
+    pub fn done(&mut self) -> bool {
+        self.behavior.done()
+    }
+
+    /// Run a single iteration of the behavior as the platform does: [`Behavior::action`], the FDIR functions
+    ///  and [`Behavior::done`], deregistering from the fake AMS once done. Returns whether the agent is done.
+    pub fn step(&mut self) -> bool {
+        if !self.finished && iterate(&mut self.behavior) {
+            let _ = self.behavior.as_ref().takedown();
+            self.finished = true;
+        }
+        self.finished
+    }
+
+    /// Run up to `steps` iterations, stopping early once the agent is done. Returns the number of iterations run.
+    pub fn run(&mut self, steps: usize) -> usize {
+        let mut count = 0;
+        while count < steps && !self.finished {
+            self.step();
+            count += 1;
+        }
+        count
+    }
+
+    /// Whether or not the agent has reached the end of its life cycle.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Get a copy of the messages sent by the agent that have not been taken yet, in order.
+    pub fn sent(&self) -> Vec<Message> {
+        self.context.state().outbox.clone()
+    }
+
+    /// Take the messages sent by the agent so far, in order.
+    pub fn take_sent(&self) -> Vec<Message> {
+        std::mem::take(&mut self.context.state().outbox)
+    }
+
+    /// Get the total time the agent asked to wait.
+    pub fn waited(&self) -> Duration {
+        self.context.state().waited
+    }
+
+    /// Take the first message of the given type sent to the agent named `receiver`,
+    ///  running up to `steps` iterations until it is sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no such message is sent within `steps` iterations.
+    #[track_caller]
+    pub fn expect_sent(
+        &mut self,
+        receiver: &str,
+        message_type: MessageType,
+        steps: usize,
+    ) -> Message {
+        let mut remaining = steps;
+        loop {
+            if let Some(msg) = self.take_matching(receiver, &message_type) {
+                return msg;
+            }
+            if remaining == 0 || self.finished {
+                break;
+            }
+            self.step();
+            remaining -= 1;
+        }
+        panic!(
+            "{}: expected {} to {} within {} steps, sent {:?}",
+            self.aid(),
+            message_type,
+            receiver,
+            steps,
+            self.sent()
+        );
+    }
+
+    /// Assert that the agent sends nothing during the next `steps` iterations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any message is sent.
+    #[track_caller]
+    pub fn expect_silent(&mut self, steps: usize) {
+        self.run(steps);
+        let sent = self.take_sent();
+        assert!(
+            sent.is_empty(),
+            "{}: expected no messages, sent {:?}",
+            self.aid(),
+            sent
+        );
+    }
+
+    fn take_matching(&self, receiver: &str, message_type: &MessageType) -> Option<Message> {
+        let mut state = self.context.state();
+        let position = state.outbox.iter().position(|x| {
+            (x.receiver().nickname() == receiver || x.receiver().name() == receiver)
+                && x.message_type() == message_type
+        })?;
+        Some(state.outbox.remove(position))
+    }
+}
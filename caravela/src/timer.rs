@@ -0,0 +1,200 @@
+use crate::{
+    entity::{dispatch, Description},
+    messaging::{Message, SyncType},
+    ErrorCode,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex, MutexGuard, OnceLock,
+    },
+    task::Waker,
+    thread,
+    time::{Duration, Instant},
+};
+
+static TIMERS: OnceLock<TimerService> = OnceLock::new();
+static NEXT_TIMER: AtomicU64 = AtomicU64::new(0);
+
+/// Handle to a timer started by an agent, used to cancel it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+#[derive(Debug)]
+enum Timer {
+    /// A message started by its owner, posted again every period if there is one.
+    Post {
+        owner: Description,
+        msg: Message,
+        period: Option<Duration>,
+    },
+    /// A future of the executor, such as [`Sleep`](crate::executor::Sleep), woken once.
+    Wake(Waker),
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+    due: BTreeMap<(Instant, u64), Timer>,
+    index: HashMap<u64, Instant>,
+}
+
+impl Queue {
+    fn insert(&mut self, at: Instant, id: u64, timer: Timer) {
+        self.index.insert(id, at);
+        self.due.insert((at, id), timer);
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Timer> {
+        let at = self.index.remove(&id)?;
+        self.due.remove(&(at, id))
+    }
+}
+
+#[derive(Debug, Default)]
+struct TimerService {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl TimerService {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().expect("Timer service is poisoned")
+    }
+
+    fn run(&self) {
+        let mut queue = self.lock();
+        loop {
+            let now = Instant::now();
+            let mut fired = Vec::new();
+            let mut woken = Vec::new();
+            while let Some(entry) = queue.due.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                let ((at, id), timer) = entry.remove_entry();
+                match timer {
+                    Timer::Post {
+                        ref msg,
+                        period: Some(period),
+                        ..
+                    } => {
+                        fired.push(msg.clone());
+                        queue.insert((at + period).max(now), id, timer);
+                    }
+                    Timer::Post { msg, .. } => {
+                        queue.index.remove(&id);
+                        fired.push(msg);
+                    }
+                    Timer::Wake(waker) => {
+                        queue.index.remove(&id);
+                        woken.push(waker);
+                    }
+                }
+            }
+            if !fired.is_empty() || !woken.is_empty() {
+                // delivered unlocked, the deck may be waiting to cancel the timers of an agent
+                drop(queue);
+                for msg in fired {
+                    let _ = dispatch(msg, SyncType::NonBlocking);
+                }
+                woken.into_iter().for_each(Waker::wake);
+                queue = self.lock();
+                continue;
+            }
+            queue = match queue.due.first_key_value() {
+                Some((&(until, _), _)) => {
+                    self.changed
+                        .wait_timeout(queue, until - now)
+                        .expect("Timer service is poisoned")
+                        .0
+                }
+                None => self.changed.wait(queue).expect("Timer service is poisoned"),
+            };
+        }
+    }
+}
+
+/// The single thread running the timers of the agents and the sleeps of the executor.
+fn timers() -> &'static TimerService {
+    TIMERS.get_or_init(|| {
+        let _ = thread::Builder::new()
+            .name("caravela-timers".to_string())
+            .spawn(|| timers().run());
+        TimerService::default()
+    })
+}
+
+/// Post `msg` once `delay` has passed, and then every `period` if there is one.
+pub(crate) fn start(
+    owner: Description,
+    msg: Message,
+    delay: Duration,
+    period: Option<Duration>,
+) -> TimerHandle {
+    let id = NEXT_TIMER.fetch_add(1, Ordering::Relaxed);
+    schedule(
+        Instant::now() + delay,
+        id,
+        Timer::Post { owner, msg, period },
+    );
+    TimerHandle(id)
+}
+
+/// Wake `waker` once `at` has passed, replacing the waker kept under `id` by an earlier call.
+pub(crate) fn wake_at(at: Instant, id: &mut Option<u64>, waker: &Waker) {
+    let id = *id.get_or_insert_with(|| NEXT_TIMER.fetch_add(1, Ordering::Relaxed));
+    schedule(at, id, Timer::Wake(waker.clone()));
+}
+
+/// Drop the waker kept under `id`, once its future no longer waits.
+pub(crate) fn forget(id: u64) {
+    if let Some(timers) = TIMERS.get() {
+        timers.lock().remove(id);
+    }
+}
+
+fn schedule(at: Instant, id: u64, timer: Timer) {
+    let timers = timers();
+    let mut queue = timers.lock();
+    let first = queue.due.first_key_value().map(|(&key, _)| key);
+    queue.remove(id);
+    queue.insert(at, id, timer);
+    if first.is_none_or(|first| (at, id) < first) {
+        timers.changed.notify_one();
+    }
+}
+
+/// Cancel a timer of `owner`, which is [`ErrorCode::NotFound`] once it fired for the last time.
+pub(crate) fn cancel(owner: &Description, handle: TimerHandle) -> Result<(), ErrorCode> {
+    let mut queue = TIMERS.get().ok_or(ErrorCode::NotFound)?.lock();
+    let at = *queue.index.get(&handle.0).ok_or(ErrorCode::NotFound)?;
+    if queue
+        .due
+        .get(&(at, handle.0))
+        .is_none_or(|timer| !matches!(timer, Timer::Post { owner: x, .. } if x == owner))
+    {
+        return Err(ErrorCode::NotFound);
+    }
+    queue.remove(handle.0);
+    Ok(())
+}
+
+/// Drop every timer started by the agent or posting to it, once it leaves the platform.
+pub(crate) fn cancel_all(aid: &Description) {
+    let Some(timers) = TIMERS.get() else {
+        return;
+    };
+    let mut queue = timers.lock();
+    let gone: Vec<_> = queue
+        .due
+        .iter()
+        .filter(|(_, timer)| {
+            matches!(timer, Timer::Post { owner, msg, .. } if owner == aid || msg.receiver() == aid)
+        })
+        .map(|(&(_, id), _)| id)
+        .collect();
+    for id in gone {
+        queue.remove(id);
+    }
+}
@@ -1,36 +1,82 @@
-/// Macro used for simple messages activated by the user as a feature.
+/// Macro used for simple messages from user code. Emitted as a `tracing` event
+///  with the `caravela::probe` target when the `tracing` feature is enabled.
 #[macro_export]
-#[cfg(feature = "dbg-probe")]
+#[cfg(feature = "tracing")]
 macro_rules! caravela_probe {
     ($($arg:tt)*) => {{
-    println!("[PROBE] {}",format_args!($($arg)*));
+        $crate::__tracing::info!(target: "caravela::probe", $($arg)*);
     }};
 }
 
+/// Macro used for simple messages from user code. Does nothing unless the `tracing` feature is enabled.
 #[macro_export]
-#[cfg(not(feature = "dbg-probe"))]
+#[cfg(not(feature = "tracing"))]
 macro_rules! caravela_probe {
     ($($arg:tt)*) => {{}};
 }
 
 macro_rules! caravela_status {
     ($($arg:tt)*) => {{
-        #[cfg(feature="dbg-status")]
-        println!("[STATUS] {}",format_args!($($arg)*));
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "caravela::status", $($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
     }};
 }
 macro_rules! caravela_messaging {
     ($($arg:tt)*) => {{
-        #[cfg(feature="dbg-messaging")]
-        println!("[MESSAGING] {}",format_args!($($arg)*));
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "caravela::messaging", $($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    }};
+}
+/// Report a message with its sender, receiver and performative as structured fields.
+macro_rules! caravela_message {
+    ($msg:expr, $text:literal) => {{
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            target: "caravela::messaging",
+            sender = %$msg.sender(),
+            receiver = %$msg.receiver(),
+            performative = %$msg.message_type(),
+            $text
+        );
     }};
 }
 macro_rules! caravela_dflt {
     ($($arg:tt)*) => {{
-        #[cfg(feature="dbg-default")]
-        println!("[DEFAULT] {}",format_args!($($arg)*));
+        #[cfg(feature = "tracing")]
+        tracing::trace!(target: "caravela::default", $($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
     }};
 }
+/// Record a sample through the given function of the metrics module when the `metrics` feature is enabled.
+macro_rules! caravela_metric {
+    ($function:ident($($arg:expr),*)) => {{
+        #[cfg(feature = "metrics")]
+        $crate::metrics::$function($($arg),*);
+        #[cfg(not(feature = "metrics"))]
+        if false {
+            let _ = ($($arg),*);
+        }
+    }};
+}
+/// Enter a span named after the kind of entity running on the current thread,
+///  which is left at the end of the enclosing block.
+macro_rules! caravela_span {
+    ($kind:literal, $nickname:expr, $hap:expr) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!($kind, nickname = $nickname, hap = $hap).entered();
+    };
+}
 
 /// Macro to define agent types without parameters
 #[macro_export]
@@ -78,3 +124,22 @@ macro_rules! make_agent_with_param {
         }
     };
 }
+
+/// Quote and escape the text as a JSON string.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -11,6 +11,7 @@ use caravela::{
     ErrorCode, Platform, DEFAULT_STACK,
 };
 use std::error::Error;
+use tracing_subscriber::EnvFilter;
 
 //Defining agent types
 make_agent!(Sender);
@@ -27,7 +28,7 @@ impl Behavior for Sender {
             MessageType::Inform,
             Content::Expression("This is a message".to_string()),
         )?;
-        self.agent.wait(200);
+        self.agent.wait(200)?;
         Ok(())
     }
 
@@ -55,6 +56,12 @@ impl Behavior for Receiver {
 
 // main entry
 fn main() -> Result<(), Box<dyn Error>> {
+    // report platform activity, verbosity is set through RUST_LOG (e.g. RUST_LOG=caravela=trace)
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("caravela=debug")),
+        )
+        .init();
     // new platform
     let agent_platform = Platform::new("example")?;
     // add agents
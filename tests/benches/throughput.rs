@@ -0,0 +1,87 @@
+//! Message throughput of a ring of 1000 agents, each forwarding the tokens it receives to the next one by nickname.
+//!  Run with `cargo bench -p tests --bench throughput`.
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+const AGENTS: usize = 1000;
+const TOKENS: usize = 8;
+const LAPS: usize = 10;
+
+make_agent_with_param!(Relay, (usize, Sender<()>));
+make_agent!(Starter);
+
+impl Behavior for Relay {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let hops = msg
+            .content()
+            .to_string()
+            .parse::<usize>()
+            .unwrap_or_default()
+            + 1;
+        let (index, tx) = &self.param;
+        if hops == AGENTS * LAPS {
+            let _ = tx.send(());
+            return Ok(());
+        }
+        self.agent.send_to(
+            &format!("relay-{}", (index + 1) % AGENTS),
+            MessageType::Inform,
+            Content::Expression(hops.to_string()),
+        )
+    }
+}
+
+impl Behavior for Starter {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        for token in 0..TOKENS {
+            self.agent.send_to(
+                &format!("relay-{}", token * AGENTS / TOKENS),
+                MessageType::Inform,
+                Content::Expression("0".to_string()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("bench_throughput")?;
+    let (tx, rx) = channel();
+    for index in 0..AGENTS {
+        agent_platform.add_agent_with_param::<Relay>(
+            format!("relay-{}", index),
+            1,
+            DEFAULT_STACK,
+            (index, tx.clone()),
+        )?;
+    }
+    agent_platform.start_all()?;
+
+    let starter = agent_platform.add_agent::<Starter>("starter", 1, DEFAULT_STACK)?;
+    let started = Instant::now();
+    agent_platform.start(&starter)?;
+    for _ in 0..TOKENS {
+        rx.recv_timeout(Duration::from_secs(60))?;
+    }
+    let elapsed = started.elapsed();
+
+    let messages = TOKENS * LAPS * AGENTS;
+    println!(
+        "{} agents: {} messages in {:?}, {:.0} messages/s",
+        AGENTS,
+        messages,
+        elapsed,
+        messages as f64 / elapsed.as_secs_f64()
+    );
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Requester, Sender<Message>);
+
+impl Behavior for Requester {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.agent.add_contact("ams")
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let aid = self.agent.aid()?;
+        let modify = ModifyAgent::Priority(MAX_PRIORITY);
+        let content = Content::Action(ActionType::Modify(aid, modify));
+        self.agent.send_to("ams", MessageType::Request, content)?;
+        for _ in 0..2 {
+            let msg = self.agent.receive()?;
+            let _ = self.param.send(msg);
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn modify_fails_with_reason() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_ams")?;
+    let (tx, rx) = channel();
+    let requester =
+        agent_platform.add_agent_with_param::<Requester>("Requester", 1, DEFAULT_STACK, tx)?;
+    agent_platform.start(&requester)?;
+
+    let agree = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(agree.message_type(), &MessageType::Agree);
+    let failure = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(failure.message_type(), &MessageType::Failure);
+    let Content::Reason(ActionType::Modify(_, ModifyAgent::Priority(_)), reason) =
+        failure.content()
+    else {
+        panic!("unexpected content: {}", failure.content());
+    };
+    assert!(matches!(reason, Reason::InvalidPriority(_)));
+    Ok(())
+}
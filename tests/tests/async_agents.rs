@@ -0,0 +1,123 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+make_agent!(Gateway);
+
+impl AsyncBehavior for Gateway {
+    async fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive_async().await?;
+        // the mailbox of the client holds a single message, so the gateway awaits room for the next one
+        for word in msg.content().to_string().split(' ') {
+            self.agent
+                .send_to_aid_async(
+                    msg.sender().clone(),
+                    MessageType::Inform,
+                    Content::Expression(word.to_uppercase()),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+make_agent_with_param!(Ticker, Sender<u64>);
+
+impl AsyncBehavior for Ticker {
+    async fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait_async(10).await?;
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        let iterations = self.agent.stats().iterations;
+        if iterations == 3 {
+            let _ = self.param.send(iterations);
+        }
+        iterations == 3
+    }
+}
+
+make_agent_with_param!(Client, Sender<String>);
+
+impl Behavior for Client {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.agent.send_to(
+            "gateway",
+            MessageType::Request,
+            Content::Expression("one two three".to_string()),
+        )
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(20)?;
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(msg.content().to_string());
+        Ok(())
+    }
+}
+
+#[test]
+fn async_agents_share_the_lifecycle() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_async")?;
+    let (_, events) = agent_platform.subscribe_channel();
+
+    let gateway = agent_platform.add_async_agent::<Gateway>("gateway", 5)?;
+    let (ticks_tx, ticks_rx) = channel();
+    let ticker = agent_platform.add_async_agent_with_param::<Ticker>("ticker", 5, ticks_tx)?;
+    let (words_tx, words_rx) = channel();
+    let client =
+        agent_platform.add_agent_with_param::<Client>("client", 1, DEFAULT_STACK, words_tx)?;
+    assert_eq!(
+        agent_platform.add_async_agent::<Gateway>("gateway", 5),
+        Err(ErrorCode::Duplicated)
+    );
+    assert!(matches!(
+        agent_platform.set_affinity(&gateway, 0),
+        Err(ErrorCode::InvalidRequest(_))
+    ));
+
+    agent_platform.start(&gateway)?;
+    agent_platform.start(&ticker)?;
+    agent_platform.start(&client)?;
+
+    for word in ["ONE", "TWO", "THREE"] {
+        assert_eq!(words_rx.recv_timeout(Duration::from_secs(2))?, word);
+    }
+    assert_eq!(ticks_rx.recv_timeout(Duration::from_secs(2))?, 3);
+
+    let state = |nickname: &str| {
+        agent_platform
+            .agents()
+            .into_iter()
+            .find(|x| x.nickname == nickname)
+            .map(|x| x.state)
+    };
+    agent_platform.suspend(&gateway)?;
+    assert_eq!(state("gateway"), Some(AgentState::Suspended));
+    agent_platform.resume(&gateway)?;
+    assert_eq!(state("gateway"), Some(AgentState::Active));
+    // the gateway is waiting for a message, the termination interrupts it
+    agent_platform.terminate(&gateway)?;
+    assert_eq!(state("gateway"), None);
+
+    let mut terminated = Vec::new();
+    while terminated.len() < 2 {
+        if let Event::AgentTerminated(aid) = events.recv_timeout(Duration::from_secs(2))? {
+            terminated.push(aid);
+        }
+    }
+    assert!(terminated.contains(&gateway));
+    assert!(terminated.contains(&ticker));
+    // a finished agent asks the AMS to deregister it without waiting for the answer
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while state("ticker").is_some() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    assert_eq!(state("ticker"), None);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::service::OwnershipConditions;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent!(Target);
+make_agent_with_param!(Requester, (Description, Sender<Message>));
+
+impl Behavior for Target {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(50)?;
+        Ok(())
+    }
+}
+
+impl Behavior for Requester {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.agent.add_contact("ams")
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let target = self.param.0.clone();
+        let modify = ModifyAgent::State(StateOp::Suspend);
+        let content = Content::Action(ActionType::Modify(target, modify));
+        self.agent.send_to("ams", MessageType::Request, content)?;
+        let msg = self.agent.receive()?;
+        let _ = self.param.1.send(msg);
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn only_owner_may_suspend() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new_with_conditions("test_auth", OwnershipConditions)?;
+    let (tx, rx) = channel();
+    let target = agent_platform.add_agent::<Target>("Target", 1, DEFAULT_STACK)?;
+    let intruder = agent_platform.add_agent_with_param::<Requester>(
+        "Intruder",
+        1,
+        DEFAULT_STACK,
+        (target.clone(), tx.clone()),
+    )?;
+    let owner = agent_platform.add_agent_with_param::<Requester>(
+        "Owner",
+        1,
+        DEFAULT_STACK,
+        (target.clone(), tx),
+    )?;
+    agent_platform.set_owner(&target, &owner)?;
+    agent_platform.start(&target)?;
+
+    agent_platform.start(&intruder)?;
+    let refusal = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(refusal.message_type(), &MessageType::Refuse);
+    assert!(matches!(
+        refusal.content(),
+        Content::Reason(_, Reason::Unauthorized)
+    ));
+
+    agent_platform.start(&owner)?;
+    let agree = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(agree.message_type(), &MessageType::Agree);
+    Ok(())
+}
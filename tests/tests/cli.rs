@@ -0,0 +1,124 @@
+use caravela::agent::*;
+use caravela::cli::*;
+use caravela::config::*;
+use caravela::ErrorCode;
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const CONFIG: &str = r#"
+hap = "test_cli"
+
+[[agents]]
+nickname = "Echo"
+type = "Echo"
+priority = 10
+
+[[agents]]
+nickname = "Sink"
+type = "Sink"
+priority = 10
+"#;
+
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+    fn wait_for(&self, pattern: &str) -> bool {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(2) {
+            if self.text().contains(pattern) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn control_session() -> Result<(), Box<dyn Error>> {
+    let registry = || {
+        let mut registry = AgentRegistry::new();
+        register_builtins(&mut registry);
+        registry
+    };
+    let output = Output::default();
+    // refused before any platform is launched, so the next session can still launch its own
+    let taken = CONFIG.replace("\"Sink\"\ntype", "\"operator\"\ntype");
+    assert!(matches!(
+        Session::new(
+            PlatformConfig::from_toml(&taken)?,
+            registry(),
+            Console::new(output.clone())
+        ),
+        Err(ErrorCode::InvalidConfig(_))
+    ));
+    let mut session = Session::new(
+        PlatformConfig::from_toml(CONFIG)?,
+        registry(),
+        Console::new(output.clone()),
+    )?;
+
+    assert!(session.execute("agents"));
+    let listing = output.text();
+    for nickname in ["Echo", "Sink", OPERATOR] {
+        assert!(listing.contains(nickname), "{} not listed", nickname);
+    }
+
+    assert!(session.execute("sniff Echo"));
+    assert!(session.execute("send Echo request hello there"));
+    assert!(output.wait_for("<- Inform Message from Echo@test_cli: hello there"));
+    assert!(session.execute("trace"));
+    assert!(output
+        .text()
+        .contains("[sent] operator@test_cli -> Echo@test_cli"));
+
+    assert!(session.execute("send Echo shout hello"));
+    assert!(output.text().contains("error: "));
+    assert!(session.execute("suspend Ghost"));
+    assert!(session.execute("bogus"));
+    assert!(output.text().contains("unknown command: bogus"));
+
+    let state = |session: &Session, nickname: &str| {
+        session
+            .platform()
+            .agents()
+            .iter()
+            .find(|x| x.nickname == nickname)
+            .map(|x| x.state)
+    };
+    session.execute("suspend Sink");
+    assert_eq!(state(&session, "Sink"), Some(AgentState::Suspended));
+    // a full mailbox does not hold the session up
+    assert!(session.execute("send Sink inform first"));
+    assert!(session.execute("send Sink inform second"));
+    assert!(output
+        .text()
+        .contains("the mailbox of Sink is full, send the message again later"));
+    session.execute("resume Sink");
+    assert_eq!(state(&session, "Sink"), Some(AgentState::Active));
+    session.execute("terminate Sink");
+    assert_eq!(state(&session, "Sink"), None);
+    session.execute("reset Sink");
+    assert_eq!(state(&session, "Sink"), Some(AgentState::Active));
+
+    session.run(io::Cursor::new("help\nquit\nagents\n"));
+    let text = output.text();
+    assert!(text.contains("caravela> "));
+    assert!(text.contains("leave the session"));
+    Ok(())
+}
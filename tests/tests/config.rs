@@ -0,0 +1,135 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::config::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Sensor, Vec<String>);
+make_agent_with_param!(Monitor, (u64, Sender<String>));
+make_agent!(Idle);
+
+impl Behavior for Sensor {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        for reading in self.param.clone() {
+            self.agent
+                .send_to("Monitor", MessageType::Inform, Content::Expression(reading))?;
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Monitor {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let (threshold, tx) = &self.param;
+        let _ = tx.send(format!("{} > {}", msg.content(), threshold));
+        Ok(())
+    }
+}
+
+impl Behavior for Idle {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(10)?;
+        Ok(())
+    }
+}
+
+const CONFIG: &str = r#"
+hap = "test_config"
+ams_conditions = "ownership"
+start = ["Monitor", "Sensor"]
+
+[[agents]]
+nickname = "Sensor"
+type = "Sensor"
+priority = 10
+params = ["low", "high"]
+
+[[agents]]
+nickname = "Monitor"
+type = "Monitor"
+priority = 20
+stack = 65536
+mailbox = 4
+affinity = 0
+params = { threshold = 3 }
+
+[[agents]]
+nickname = "Idle"
+type = "Idle"
+priority = 5
+"#;
+
+#[test]
+fn launch_from_config() -> Result<(), Box<dyn Error>> {
+    let (tx, rx) = channel();
+    let mut registry = AgentRegistry::new();
+    registry
+        .register::<Idle>("Idle")
+        .register_deserialized::<Sensor>("Sensor")
+        .register_with_param::<Monitor, _>("Monitor", move |params| {
+            let threshold = params["threshold"]
+                .as_u64()
+                .ok_or(ErrorCode::InvalidConfig("missing threshold".to_string()))?;
+            Ok((threshold, tx.clone()))
+        });
+
+    let config = PlatformConfig::from_toml(CONFIG)?;
+    assert_eq!(config.agents.len(), 3);
+    assert_eq!(config.agents[1].mailbox, Some(4));
+    let json = PlatformConfig::from_json(
+        r#"{"hap": "test_config", "ams_conditions": "ownership", "start": ["Monitor", "Sensor"],
+            "agents": [
+                {"nickname": "Sensor", "type": "Sensor", "priority": 10, "params": ["low", "high"]},
+                {"nickname": "Monitor", "type": "Monitor", "priority": 20, "stack": 65536, "mailbox": 4,
+                 "affinity": 0, "params": {"threshold": 3}},
+                {"nickname": "Idle", "type": "Idle", "priority": 5}
+            ]}"#,
+    )?;
+    assert_eq!(json, config);
+
+    // nothing is spawned for invalid configurations
+    let mut unknown = config.clone();
+    unknown.agents[2].agent_type = "Missing".to_string();
+    assert!(matches!(
+        unknown.launch(&registry),
+        Err(ErrorCode::InvalidConfig(_))
+    ));
+    let mut undeclared = config.clone();
+    undeclared.start = Some(vec!["Ghost".to_string()]);
+    assert!(matches!(
+        undeclared.launch(&registry),
+        Err(ErrorCode::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        PlatformConfig::from_toml("hap = 3"),
+        Err(ErrorCode::InvalidConfig(_))
+    ));
+
+    let (platform, agents) = config.launch(&registry)?;
+    assert_eq!(platform.name(), "test_config");
+    let nicknames: Vec<_> = agents.iter().map(|x| x.nickname()).collect();
+    assert_eq!(nicknames, ["Sensor", "Monitor", "Idle"]);
+    let readings: Vec<String> = (0..2)
+        .map(|_| rx.recv_timeout(Duration::from_millis(2000)))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(readings, ["low > 3", "high > 3"]);
+
+    let stats = platform.agents();
+    let monitor = stats
+        .iter()
+        .find(|x| x.nickname == "Monitor")
+        .ok_or("monitor")?;
+    assert_eq!((monitor.priority, monitor.stack_size), (20, 65536));
+    // not in the start order, so it is only added
+    let idle = stats.iter().find(|x| x.nickname == "Idle").ok_or("idle")?;
+    assert_eq!(idle.state, AgentState::Initiated);
+    Ok(())
+}
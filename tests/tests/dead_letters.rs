@@ -0,0 +1,150 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::dead_letter::DeadLetter;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+type Report = Sender<String>;
+
+enum Order {
+    Send(Description, &'static str, Option<Duration>),
+    Redeliver,
+}
+
+make_agent_with_param!(Courier, (Receiver<Order>, Report));
+make_agent_with_param!(Sleepy, Report);
+make_agent_with_param!(Listener, Report);
+
+impl Behavior for Courier {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let Ok(order) = self.param.0.recv() else {
+            return self.agent.wait(60_000);
+        };
+        match order {
+            Order::Send(aid, text, ttl) => {
+                let content = Content::Expression(text.to_string());
+                // the receiver may be gone already
+                let _ = match ttl {
+                    Some(ttl) => {
+                        self.agent
+                            .send_to_aid_with_ttl(aid, MessageType::Inform, content, ttl)
+                    }
+                    None => self.agent.send_to_aid(aid, MessageType::Inform, content),
+                };
+            }
+            Order::Redeliver => {
+                let letters = self.agent.dead_letters()?;
+                let letter = letters
+                    .iter()
+                    .find(|letter| *letter.reason() == Reason::NotRegistered)
+                    .ok_or(ErrorCode::NotFound)?;
+                let result = self.agent.redeliver(letter.id());
+                let _ = self.param.1.send(format!("redelivered: {:?}", result));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Behavior for Sleepy {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        // long enough for what is sent meanwhile to expire
+        self.agent.wait(150)
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(format!("sleepy: {}", msg.content()));
+        Ok(())
+    }
+}
+
+impl Behavior for Listener {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(format!("listener: {}", msg.content()));
+        Ok(())
+    }
+}
+
+/// Wait until the platform holds a dead letter with the given content.
+fn dead_letter(agent_platform: &Platform, content: &str) -> Result<DeadLetter, ErrorCode> {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        let letters = agent_platform.dead_letters();
+        if let Some(letter) = letters
+            .into_iter()
+            .find(|letter| letter.message().content().to_string() == content)
+        {
+            return Ok(letter);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    Err(ErrorCode::Timeout)
+}
+
+#[test]
+fn undeliverable_messages_are_kept_and_redelivered() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_dead_letters")?;
+    let (tx, rx) = channel();
+    let (orders, orders_rx) = channel();
+    let sleepy =
+        agent_platform.add_agent_with_param::<Sleepy>("sleepy", 1, DEFAULT_STACK, tx.clone())?;
+    let listener = agent_platform.add_agent_with_param::<Listener>(
+        "listener",
+        1,
+        DEFAULT_STACK,
+        tx.clone(),
+    )?;
+    agent_platform.add_agent_with_param::<Courier>(
+        "courier",
+        1,
+        DEFAULT_STACK,
+        (orders_rx, tx.clone()),
+    )?;
+    agent_platform.start_all()?;
+
+    // expired while waiting in the mailbox, then redelivered without expiry by the platform
+    orders.send(Order::Send(
+        sleepy,
+        "stale",
+        Some(Duration::from_millis(10)),
+    ))?;
+    let letter = dead_letter(&agent_platform, "stale")?;
+    assert_eq!(*letter.reason(), Reason::Expired);
+    assert_eq!(letter.message().sender().nickname(), "courier");
+    assert!(letter.message().expires_at().is_some());
+    agent_platform.redeliver(letter.id())?;
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2))?, "sleepy: stale");
+    assert_eq!(
+        agent_platform.redeliver(letter.id()),
+        Err(ErrorCode::NotFound)
+    );
+
+    // sent to an agent that left, then redelivered by the sender to the agent now under that name
+    agent_platform.terminate(&listener)?;
+    orders.send(Order::Send(listener, "late", None))?;
+    let letter = dead_letter(&agent_platform, "late")?;
+    assert_eq!(*letter.reason(), Reason::NotRegistered);
+    agent_platform.add_agent_with_param::<Listener>("listener", 1, DEFAULT_STACK, tx)?;
+    agent_platform.start_all()?;
+    orders.send(Order::Redeliver)?;
+    let mut reports = [
+        rx.recv_timeout(Duration::from_secs(2))?,
+        rx.recv_timeout(Duration::from_secs(2))?,
+    ];
+    reports.sort();
+    assert_eq!(reports, ["listener: late", "redelivered: Ok(())"]);
+    assert!(agent_platform.dead_letters().iter().all(|letter| letter
+        .message()
+        .content()
+        .to_string()
+        != "late"));
+
+    agent_platform.clear_dead_letters();
+    assert!(agent_platform.dead_letters().is_empty());
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use caravela::agent::*;
+use caravela::messaging::*;
+use caravela::testing::*;
+use caravela::*;
+
+#[derive(Debug)]
+struct Thresholds {
+    low: u64,
+    high: u64,
+}
+
+#[derive(Agent)]
+#[agent(param = Thresholds)]
+struct Monitor {
+    #[agent]
+    core: Agent,
+    high: u64,
+    low: u64,
+    #[agent(default)]
+    alarms: Vec<u64>,
+}
+
+#[behavior]
+impl Monitor {
+    #[action]
+    fn check(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.core.receive()?;
+        let reading: u64 = msg.content().to_string().parse().unwrap_or_default();
+        if reading < self.low || reading > self.high {
+            self.alarms.push(reading);
+            self.core.send_to(
+                "Operator",
+                MessageType::Inform,
+                Content::Expression(format!("alarm {}", reading)),
+            )?;
+        }
+        Ok(())
+    }
+
+    #[done]
+    fn enough(&self) -> bool {
+        self.alarms.len() >= 2
+    }
+}
+
+#[derive(Agent)]
+#[agent(param = Vec<u64>)]
+struct Replayer<T: Default + Send + 'static> {
+    #[agent(param)]
+    readings: Vec<u64>,
+    #[agent]
+    core: Agent,
+    cursor: T,
+}
+
+#[behavior]
+impl<T: Default + Send + 'static> Replayer<T> {
+    #[setup]
+    fn check_readings(&self) -> Result<(), ErrorCode> {
+        if self.readings.is_empty() {
+            Err(ErrorCode::InvalidContent("no readings".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Agent)]
+struct Counter {
+    #[agent]
+    core: Agent,
+    count: u32,
+}
+
+#[behavior]
+impl Counter {
+    #[action]
+    fn count(&mut self) -> Result<(), ErrorCode> {
+        self.count += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn derived_agents_are_built_and_scheduled() {
+    let mut harness =
+        TestHarness::<Monitor>::new_with_param("Monitor", Thresholds { low: 10, high: 20 });
+    assert_eq!(harness.behavior().low, 10);
+    assert_eq!(harness.behavior().high, 20);
+    assert!(harness.behavior().alarms.is_empty());
+    let sensor = harness.add_peer("Sensor");
+    harness.add_peer("Operator");
+    for reading in ["15", "25", "12", "3"] {
+        harness
+            .inject(
+                &sensor,
+                MessageType::Inform,
+                Content::Expression(reading.to_string()),
+            )
+            .expect("inject");
+    }
+    assert_eq!(harness.run(10), 4);
+    assert!(harness.is_finished());
+    let alarm = harness.expect_sent("Operator", MessageType::Inform, 0);
+    assert_eq!(
+        alarm.content(),
+        &Content::Expression("alarm 25".to_string())
+    );
+    assert_eq!(harness.behavior().alarms, [25, 3]);
+
+    let mut harness = TestHarness::<Replayer<usize>>::new_with_param("Replayer", vec![]);
+    assert_eq!(
+        harness.setup(),
+        Err(ErrorCode::InvalidContent("no readings".to_string()))
+    );
+    let harness = TestHarness::<Replayer<usize>>::new_with_param("Replayer", vec![1, 2]);
+    assert_eq!(harness.behavior().readings, [1, 2]);
+    assert_eq!(harness.behavior().cursor, 0);
+
+    let mut harness = TestHarness::<Counter>::new("Counter");
+    assert_eq!(harness.behavior().as_ref().aid(), Ok(harness.aid().clone()));
+    assert_eq!(harness.run(3), 3);
+    assert!(!harness.is_finished());
+    assert_eq!(harness.behavior().count, 3);
+}
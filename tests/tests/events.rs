@@ -0,0 +1,56 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::time::Duration;
+
+make_agent!(Echo);
+
+impl Behavior for Echo {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let aid = self.agent.aid()?;
+        self.agent.send_to_aid(
+            aid,
+            MessageType::Inform,
+            Content::Expression("echo".to_string()),
+        )?;
+        self.agent.receive().map(|_| ())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn lifecycle_and_messaging_events() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_events")?;
+    let (_, events) = agent_platform.subscribe_channel();
+    let echo = agent_platform.add_agent::<Echo>("Echo", 1, DEFAULT_STACK)?;
+    agent_platform.start(&echo)?;
+
+    let mut received = Vec::new();
+    loop {
+        let event = events.recv_timeout(Duration::from_millis(2000))?;
+        let deregistered = matches!(
+            &event,
+            Event::AmsRequestProcessed(aid, ActionType::Deregister(_), MessageType::Inform) if aid == &echo
+        );
+        received.push(event);
+        if deregistered {
+            break;
+        }
+    }
+
+    assert_eq!(received[0], Event::AgentAdded(echo.clone()));
+    assert_eq!(received[1], Event::AgentStarted(echo.clone()));
+    assert!(received
+        .iter()
+        .any(|x| matches!(x, Event::MessageSent(msg) if msg.receiver() == &echo)));
+    assert!(received
+        .iter()
+        .any(|x| matches!(x, Event::MessageDelivered(msg) if msg.sender() == &echo)));
+    assert!(received.contains(&Event::AgentTerminated(echo.clone())));
+    Ok(())
+}
@@ -0,0 +1,158 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::testing::*;
+use caravela::*;
+use std::error::Error;
+use std::time::Duration;
+
+make_agent!(Responder);
+make_agent_with_param!(Searcher, u64);
+
+impl Behavior for Responder {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.agent.add_contact("Logger")
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        msg.message_type().is_message_type(&MessageType::Request)?;
+        self.agent.send_to_aid(
+            msg.sender().clone(),
+            MessageType::Inform,
+            msg.content().clone(),
+        )?;
+        self.agent.send_to_all(
+            MessageType::Inform,
+            Content::Expression("answered".to_string()),
+        )
+    }
+}
+
+impl Behavior for Searcher {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let target = self.agent.aid()?;
+        self.agent.send_to(
+            "ams",
+            MessageType::Request,
+            Content::Action(ActionType::Search(target)),
+        )?;
+        let reply = self.agent.receive()?;
+        self.agent.wait(self.param)?;
+        reply.message_type().is_message_type(&MessageType::Agree)
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn replies_to_injected_messages() {
+    let mut harness = TestHarness::<Responder>::new("Responder");
+    assert_eq!(harness.setup(), Err(ErrorCode::NotFound));
+    let logger = harness.add_peer("Logger");
+    let pinger = harness.add_peer("Pinger");
+    harness.setup().expect("setup");
+
+    // nothing to answer yet
+    assert_eq!(
+        harness.action(),
+        Err(ErrorCode::MpscRecv(std::sync::mpsc::RecvError))
+    );
+    harness.expect_silent(2);
+
+    let content = Content::Expression("ping".to_string());
+    harness
+        .inject(&pinger, MessageType::Request, content.clone())
+        .expect("inject");
+    let reply = harness.expect_sent("Pinger", MessageType::Inform, 1);
+    assert_eq!(reply.receiver(), &pinger);
+    assert_eq!(reply.sender(), harness.aid());
+    assert_eq!(reply.content(), &content);
+    let log = harness.expect_sent("Logger", MessageType::Inform, 0);
+    assert_eq!(log.receiver(), &logger);
+    assert!(harness.sent().is_empty());
+
+    harness
+        .inject(&pinger, MessageType::Inform, content)
+        .expect("inject");
+    assert_eq!(harness.action(), Err(ErrorCode::InvalidMessageType));
+    assert!(!harness.done());
+    assert!(!harness.step());
+
+    let stats = harness.behavior().as_ref().stats();
+    assert_eq!(stats.messages_received, 2);
+    assert_eq!(stats.messages_sent, 2);
+    assert_eq!(stats.iterations, 4);
+}
+
+#[test]
+#[should_panic(expected = "expected Inform Message to Pinger within 3 steps")]
+fn expectations_fail_without_messages() {
+    let mut harness = TestHarness::<Responder>::new("Responder");
+    harness.add_peer("Logger");
+    harness.setup().expect("setup");
+    harness.expect_sent("Pinger", MessageType::Inform, 3);
+}
+
+#[test]
+fn fake_ams_replies() {
+    let mut harness = TestHarness::<Searcher>::new_with_param("Searcher", 250);
+    assert!(harness.step());
+    assert!(harness.is_finished());
+    assert_eq!(harness.run(5), 0);
+    assert_eq!(harness.waited(), Duration::from_millis(250));
+    let search = harness.expect_sent("ams", MessageType::Request, 0);
+    assert_eq!(
+        search.content(),
+        &Content::Action(ActionType::Search(harness.aid().clone()))
+    );
+    // takedown deregisters from the fake AMS once done
+    let deregister = harness.expect_sent("ams", MessageType::Request, 0);
+    assert_eq!(
+        deregister.content(),
+        &Content::Action(ActionType::Deregister(harness.aid().clone()))
+    );
+
+    let mut harness = TestHarness::<Searcher>::new_with_param("Searcher", 0);
+    harness.set_ams_reply(AmsReply::Refuse(Reason::Unauthorized));
+    assert_eq!(harness.action(), Err(ErrorCode::InvalidMessageType));
+    harness.set_ams_reply(AmsReply::Silent);
+    assert_eq!(
+        harness.action(),
+        Err(ErrorCode::MpscRecv(std::sync::mpsc::RecvError))
+    );
+    harness.set_ams_reply(AmsReply::Failure(Reason::NotRegistered));
+    harness.action().expect("agreed");
+    let failure = harness.behavior().as_ref().receive().expect("failure");
+    assert_eq!(failure.message_type(), &MessageType::Failure);
+    assert_eq!(failure.sender(), harness.ams());
+    assert!(matches!(
+        failure.content(),
+        Content::Reason(ActionType::Search(_), Reason::NotRegistered)
+    ));
+}
+
+#[test]
+fn harnesses_stay_out_of_the_platform() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_harness")?;
+    let (_, events) = agent_platform.subscribe_channel();
+    let mut harness = TestHarness::<Responder>::new("Responder");
+    let pinger = harness.add_peer("Pinger");
+    harness.add_peer("Logger");
+    harness.setup()?;
+    let content = Content::Expression("ping".to_string());
+    harness.inject(&pinger, MessageType::Request, content.clone())?;
+    harness.expect_sent("Pinger", MessageType::Inform, 1);
+    // left in the mailbox once the harness is gone
+    harness.inject(&pinger, MessageType::Request, content)?;
+    drop(harness);
+
+    assert!(events.try_iter().all(|event| !matches!(
+        event,
+        Event::MessageDelivered(_) | Event::MessageDropped(..)
+    )));
+    assert!(agent_platform.dead_letters().is_empty());
+    Ok(())
+}
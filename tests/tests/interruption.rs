@@ -0,0 +1,165 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Names the agents whose blocking call was interrupted.
+type Report = Sender<&'static str>;
+
+make_agent_with_param!(Listener, Report);
+make_agent_with_param!(Flooder, Report);
+make_agent_with_param!(Sleeper, Report);
+make_agent!(Sink);
+make_agent_with_param!(Controller, (Description, StateOp, Sender<Message>));
+
+fn report(tx: &Report, agent: &'static str, err: &ErrorCode) {
+    if *err == ErrorCode::Interrupted {
+        let _ = tx.send(agent);
+    }
+}
+
+impl Behavior for Listener {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent
+            .receive()
+            .map(|_| ())
+            .inspect_err(|err| report(&self.param, "listener", err))
+    }
+}
+
+impl Behavior for Flooder {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent
+            .send_to(
+                "sink",
+                MessageType::Inform,
+                Content::Expression("flood".to_string()),
+            )
+            .inspect_err(|err| report(&self.param, "flooder", err))
+    }
+}
+
+impl Behavior for Sleeper {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent
+            .wait(60_000)
+            .inspect_err(|err| report(&self.param, "sleeper", err))
+    }
+}
+
+impl Behavior for Sink {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        // never reads its mailbox
+        self.agent.wait(60_000)
+    }
+}
+
+impl Behavior for Controller {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        self.agent.add_contact("ams")
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let (target, op, tx) = &self.param;
+        let modify = ModifyAgent::State(*op);
+        let content = Content::Action(ActionType::Modify(target.clone(), modify));
+        self.agent.send_to("ams", MessageType::Request, content)?;
+        let msg = self.agent.receive()?;
+        let _ = tx.send(msg);
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+fn control(
+    agent_platform: &Platform,
+    nickname: &str,
+    target: &Description,
+    op: StateOp,
+    replies: &Receiver<Message>,
+    tx: &Sender<Message>,
+) -> Result<Message, Box<dyn Error>> {
+    let controller = agent_platform.add_agent_with_param::<Controller>(
+        nickname,
+        1,
+        DEFAULT_STACK,
+        (target.clone(), op, tx.clone()),
+    )?;
+    agent_platform.start(&controller)?;
+    Ok(replies.recv_timeout(Duration::from_secs(2))?)
+}
+
+#[test]
+fn lifecycle_commands_interrupt_blocked_agents() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_interruption")?;
+    let (report_tx, reports) = channel();
+    let (reply_tx, replies) = channel();
+    let interrupted = |agent: &'static str| -> Result<(), Box<dyn Error>> {
+        assert_eq!(reports.recv_timeout(Duration::from_secs(2))?, agent);
+        Ok(())
+    };
+
+    // blocked in receive: suspended, resumed, then terminated through the AMS
+    let listener = agent_platform.add_agent_with_param::<Listener>(
+        "listener",
+        1,
+        DEFAULT_STACK,
+        report_tx.clone(),
+    )?;
+    agent_platform.start(&listener)?;
+    std::thread::sleep(Duration::from_millis(50));
+    agent_platform.suspend(&listener)?;
+    interrupted("listener")?;
+    agent_platform.resume(&listener)?;
+    std::thread::sleep(Duration::from_millis(50));
+    let started = Instant::now();
+    let reply = control(
+        &agent_platform,
+        "controller-0",
+        &listener,
+        StateOp::Terminate,
+        &replies,
+        &reply_tx,
+    )?;
+    assert_eq!(reply.message_type(), &MessageType::Agree);
+    assert!(started.elapsed() < JOIN_TIMEOUT);
+    interrupted("listener")?;
+
+    // blocked sending to a full mailbox
+    let sink = agent_platform.add_agent::<Sink>("sink", 1, DEFAULT_STACK)?;
+    agent_platform.set_mailbox_capacity(&sink, 1)?;
+    agent_platform.start(&sink)?;
+    let flooder = agent_platform.add_agent_with_param::<Flooder>(
+        "flooder",
+        1,
+        DEFAULT_STACK,
+        report_tx.clone(),
+    )?;
+    agent_platform.start(&flooder)?;
+    std::thread::sleep(Duration::from_millis(50));
+    let reply = control(
+        &agent_platform,
+        "controller-1",
+        &flooder,
+        StateOp::Terminate,
+        &replies,
+        &reply_tx,
+    )?;
+    assert_eq!(reply.message_type(), &MessageType::Agree);
+    interrupted("flooder")?;
+
+    // waiting
+    let sleeper =
+        agent_platform.add_agent_with_param::<Sleeper>("sleeper", 1, DEFAULT_STACK, report_tx)?;
+    agent_platform.start(&sleeper)?;
+    std::thread::sleep(Duration::from_millis(50));
+    agent_platform.terminate(&sleeper)?;
+    interrupted("sleeper")?;
+    Ok(())
+}
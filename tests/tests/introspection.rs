@@ -0,0 +1,78 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Counter, Sender<AgentStats>);
+make_agent!(Echo);
+
+impl Behavior for Counter {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.send_to(
+            "Echo",
+            MessageType::Inform,
+            Content::Expression("hello".to_string()),
+        )?;
+        self.agent.receive()?;
+        Err(ErrorCode::Other("expected".to_string()))
+    }
+
+    fn done(&mut self) -> bool {
+        let _ = self.param.send(self.agent.stats());
+        true
+    }
+}
+
+impl Behavior for Echo {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        self.agent.send_to_aid(
+            msg.sender().clone(),
+            MessageType::Inform,
+            msg.content().clone(),
+        )?;
+        // stay alive to be inspected by the platform
+        self.agent.wait(1000)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn agent_and_platform_stats() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_introspection")?;
+    let (tx, rx) = channel();
+    let counter =
+        agent_platform.add_agent_with_param::<Counter>("Counter", 1, DEFAULT_STACK, tx)?;
+    let echo = agent_platform.add_agent::<Echo>("Echo", 2, DEFAULT_STACK)?;
+
+    let initial = agent_platform.agents();
+    assert_eq!(initial.len(), 2);
+    assert!(initial
+        .iter()
+        .all(|x| x.state == AgentState::Initiated && x.uptime.is_zero()));
+
+    agent_platform.start(&echo)?;
+    agent_platform.start(&counter)?;
+    let stats = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(stats.nickname, "Counter");
+    assert_eq!(stats.priority, 1);
+    assert_eq!(stats.stack_size, DEFAULT_STACK);
+    assert_eq!(stats.messages_sent, 1);
+    assert_eq!(stats.messages_received, 1);
+    assert_eq!(stats.iterations, 1);
+    assert_eq!(stats.last_error, Some("expected".to_string()));
+
+    let echo_stats = agent_platform
+        .agents()
+        .into_iter()
+        .find(|x| x.nickname == "Echo")
+        .ok_or("Echo is missing")?;
+    assert_eq!(echo_stats.priority, 2);
+    assert_eq!(echo_stats.messages_sent, 1);
+    assert_eq!(echo_stats.messages_received, 1);
+    assert!(!echo_stats.uptime.is_zero());
+    Ok(())
+}
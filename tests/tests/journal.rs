@@ -0,0 +1,116 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::journal::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+const READINGS: usize = 4;
+
+make_agent_with_param!(Monitor, Sender<String>);
+make_agent!(Sensor);
+
+impl Behavior for Monitor {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(msg.content().to_string());
+        Ok(())
+    }
+}
+
+impl Behavior for Sensor {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        for reading in 0..READINGS {
+            let content = match reading {
+                // the last reading is raw
+                3 => Content::Bytes(vec![0x00, 0x03, 0xff].into()),
+                _ => Content::Expression(format!("reading \"{}\"\n", reading)),
+            };
+            self.agent
+                .send_to("Monitor", MessageType::Inform, content)?;
+            self.agent.wait(30)?;
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn record_and_replay() -> Result<(), Box<dyn Error>> {
+    let path = std::env::temp_dir().join(format!("caravela-journal-{}.jsonl", std::process::id()));
+    let agent_platform = Platform::new("test_journal")?;
+    agent_platform.record_journal(&path)?;
+    let (tx, rx) = channel();
+    let monitor =
+        agent_platform.add_agent_with_param::<Monitor>("Monitor", 1, DEFAULT_STACK, tx)?;
+    let sensor = agent_platform.add_agent::<Sensor>("Sensor", 1, DEFAULT_STACK)?;
+    agent_platform.start(&monitor)?;
+    agent_platform.start(&sensor)?;
+    let recorded: Vec<String> = (0..READINGS)
+        .map(|_| rx.recv_timeout(Duration::from_millis(2000)))
+        .collect::<Result<_, _>>()?;
+    // the sensor deregistering is journaled too, wait until it is gone before stopping
+    let started = Instant::now();
+    while agent_platform.agents().len() > 1 && started.elapsed() < Duration::from_secs(2) {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    agent_platform.stop_journal()?;
+
+    let journal = Journal::load(&path)?;
+    let readings: Vec<_> = journal
+        .entries()
+        .iter()
+        .filter(|x| x.receiver() == "Monitor@test_journal")
+        .collect();
+    assert_eq!(readings.len(), READINGS);
+    assert_eq!(recorded[3], "0003ff");
+    for (index, (entry, content)) in readings.iter().zip(&recorded).enumerate() {
+        assert_eq!(entry.sender(), "Sensor@test_journal");
+        assert_eq!(entry.message_type(), &MessageType::Inform);
+        let kind = match index {
+            3 => ContentKind::Bytes,
+            _ => ContentKind::Expression,
+        };
+        assert_eq!(entry.content_kind(), kind);
+        assert_eq!(entry.content(), content);
+        assert_eq!(entry.receiver_state(), Some(AgentState::Active));
+    }
+    assert!(readings
+        .windows(2)
+        .all(|x| x[1].timestamp() >= x[0].timestamp() + Duration::from_millis(25)));
+    let deregister = journal
+        .entries()
+        .iter()
+        .find(|x| x.receiver() == "ams@test_journal")
+        .ok_or("deregistration not journaled")?;
+    assert_eq!(deregister.content_kind(), ContentKind::Action);
+    assert_eq!(deregister.receiver_state(), None);
+
+    // the sensor is gone, so it is stubbed out while its readings are injected again
+    let started = Instant::now();
+    let monitors = [monitor];
+    assert_eq!(journal.replay(&agent_platform, &monitors)?, READINGS);
+    assert!(started.elapsed() >= Duration::from_millis(75));
+    let replayed: Vec<String> = (0..READINGS)
+        .map(|_| rx.recv_timeout(Duration::from_millis(2000)))
+        .collect::<Result<_, _>>()?;
+    assert_eq!(replayed, recorded);
+    assert!(agent_platform
+        .agents()
+        .iter()
+        .any(|x| x.nickname == "Sensor"));
+
+    // replaying never blocks on a full mailbox, the readings that find no room are not counted
+    agent_platform.suspend(&monitors[0])?;
+    assert_eq!(journal.replay(&agent_platform, &monitors)?, 1);
+    agent_platform.resume(&monitors[0])?;
+    assert_eq!(rx.recv_timeout(Duration::from_millis(2000))?, recorded[0]);
+    assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
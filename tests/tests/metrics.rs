@@ -0,0 +1,93 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Faulty, Sender<()>);
+make_agent!(Listener);
+
+impl Behavior for Faulty {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.send_to(
+            "Listener",
+            MessageType::Inform,
+            Content::Expression("status".to_string()),
+        )?;
+        self.agent.deadline_missed();
+        Err(ErrorCode::Other("fault".to_string()))
+    }
+
+    fn failure_detection(&mut self, action_result: &Result<(), ErrorCode>) -> bool {
+        action_result.is_err()
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Listener {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.receive()?;
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Drop for Faulty {
+    fn drop(&mut self) {
+        let _ = self.param.send(());
+    }
+}
+
+#[test]
+fn prometheus_export() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_metrics")?;
+    let (tx, rx) = channel();
+    let faulty = agent_platform.add_agent_with_param::<Faulty>("Faulty", 1, DEFAULT_STACK, tx)?;
+    let listener = agent_platform.add_agent::<Listener>("Listener", 1, DEFAULT_STACK)?;
+    agent_platform.start(&listener)?;
+    agent_platform.start(&faulty)?;
+    rx.recv_timeout(Duration::from_millis(2000))?;
+    // the AMS records the deregistration of both agents after joining their threads
+    let deregistered = "caravela_ams_requests_total{action=\"Deregister\",outcome=\"Inform\"} 2";
+    let mut text = metrics::render();
+    for _ in 0..100 {
+        if text.contains(deregistered) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        text = metrics::render();
+    }
+    assert!(text.contains("# TYPE caravela_messages_total counter"));
+    assert!(text.contains("caravela_messages_total{performative=\"Inform\"} 1"));
+    assert!(text.contains(deregistered));
+    assert!(text.contains("caravela_failure_detections_total{agent=\"Faulty@test_metrics\"} 1"));
+    assert!(text.contains("caravela_deadline_misses_total{agent=\"Faulty@test_metrics\"} 1"));
+    assert!(text.contains("caravela_send_latency_seconds_count{agent=\"Faulty@test_metrics\"} 2"));
+    assert!(text.contains(
+        "caravela_receive_latency_seconds_bucket{agent=\"Listener@test_metrics\",le=\"+Inf\"} 2"
+    ));
+
+    let address = metrics::serve("127.0.0.1:0")?;
+    let mut stream = TcpStream::connect(address)?;
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("# TYPE caravela_mailbox_high_water gauge"));
+
+    let path = std::env::temp_dir().join("caravela_metrics_test.prom");
+    metrics::write_to_file(&path)?;
+    assert!(std::fs::read_to_string(&path)?.contains("caravela_messages_total"));
+    std::fs::remove_file(path)?;
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Worker, (usize, Sender<String>));
+
+impl Behavior for Worker {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let (index, tx) = &self.param;
+        let next = format!("worker-{}", index + 1);
+        if self
+            .agent
+            .send_to(
+                &next,
+                MessageType::Inform,
+                Content::Expression(next.clone()),
+            )
+            .is_err()
+        {
+            let _ = tx.send(self.agent.name());
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn runtime_names_are_validated() -> Result<(), Box<dyn Error>> {
+    let invalid_name = |result: Result<Platform, ErrorCode>| match result {
+        Err(ErrorCode::InvalidName(reason)) => reason,
+        other => panic!(
+            "expected an invalid name, got {:?}",
+            other.map(|x| x.name().to_string())
+        ),
+    };
+    assert_eq!(invalid_name(Platform::new("")), NameError::Empty);
+    assert_eq!(
+        invalid_name(Platform::new("x".repeat(MAX_NAME_LENGTH + 1))),
+        NameError::TooLong(MAX_NAME_LENGTH + 1)
+    );
+    assert_eq!(
+        invalid_name(Platform::new("test@names")),
+        NameError::InvalidCharacter('@')
+    );
+
+    let hap = format!("test_names.{}", std::process::id());
+    let agent_platform = Platform::new(hap.clone())?;
+    assert_eq!(agent_platform.name(), hap);
+
+    let (tx, rx) = channel();
+    let mut workers = Vec::new();
+    for index in 0..3 {
+        let nickname = format!("worker-{}", index);
+        workers.push(agent_platform.add_agent_with_param::<Worker>(
+            nickname,
+            1,
+            DEFAULT_STACK,
+            (index, tx.clone()),
+        )?);
+    }
+    assert_eq!(workers[2].name(), format!("worker-2@{}", hap));
+
+    let add = |nickname: &str| {
+        agent_platform.add_agent_with_param::<Worker>(nickname, 1, DEFAULT_STACK, (0, tx.clone()))
+    };
+    assert_eq!(
+        add("sniffer"),
+        Err(ErrorCode::InvalidName(NameError::Reserved(
+            "sniffer".to_string()
+        )))
+    );
+    assert_eq!(
+        add("worker 3"),
+        Err(ErrorCode::InvalidName(NameError::InvalidCharacter(' ')))
+    );
+    assert_eq!(add(""), Err(ErrorCode::InvalidName(NameError::Empty)));
+    assert_eq!(add("worker-0"), Err(ErrorCode::Duplicated));
+
+    for worker in &workers {
+        agent_platform.start(worker)?;
+    }
+    // only the last worker has no successor to inform
+    let last = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(last, format!("worker-2@{}", hap));
+    Ok(())
+}
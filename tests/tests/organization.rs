@@ -0,0 +1,283 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::service::organization::*;
+use caravela::*;
+use std::error::Error;
+use std::fmt::Display;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+enum Order {
+    Request(OrgAction),
+    Join,
+    Send(Description),
+    Broadcast(Option<OrgRole>),
+    Receive,
+}
+
+make_agent_with_param!(Leader, Description);
+make_agent_with_param!(Recruit, Sender<Message>);
+make_agent_with_param!(Member, (Receiver<Order>, Sender<String>));
+
+fn org_request(agent: &Agent, action: OrgAction) -> Result<Message, ErrorCode> {
+    let content = Content::Action(ActionType::Organization("crew".to_string(), action));
+    agent.send_to("org", MessageType::Request, content)?;
+    agent
+        .receive()?
+        .message_type()
+        .is_message_type(&MessageType::Agree)?;
+    agent.receive()
+}
+
+impl Behavior for Leader {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        org_request(&self.agent, OrgAction::Open(OrgType::Team))?;
+        org_request(&self.agent, OrgAction::Invite(self.param.clone()))?;
+        // wait for the recruit to join
+        self.agent.receive()?;
+        self.agent.send_to_org(
+            "crew",
+            Some(OrgRole::Participant),
+            MessageType::Inform,
+            Content::Expression("welcome".to_string()),
+        )
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Recruit {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let invitation = self.agent.receive()?;
+        invitation
+            .message_type()
+            .is_message_type(&MessageType::Propose)?;
+        let joined = org_request(&self.agent, OrgAction::Join)?;
+        joined
+            .message_type()
+            .is_message_type(&MessageType::Inform)?;
+        self.agent.send_to(
+            "Leader",
+            MessageType::Inform,
+            Content::Expression("joined".to_string()),
+        )?;
+        let welcome = self.agent.receive()?;
+        let _ = self.param.send(welcome);
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Member {
+    /// Make a request to the organization service, returning the type of the final answer.
+    fn request(&self, org: &str, action: OrgAction) -> Result<String, ErrorCode> {
+        let content = Content::Action(ActionType::Organization(org.to_string(), action));
+        self.agent.send_to("org", MessageType::Request, content)?;
+        let mut answer = self.agent.receive()?;
+        if *answer.message_type() == MessageType::Agree {
+            answer = self.agent.receive()?;
+        }
+        Ok(format!("{:?}", answer.message_type()))
+    }
+}
+
+impl Behavior for Member {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let Ok(order) = self.param.0.recv() else {
+            return self.agent.wait(60_000);
+        };
+        let nickname = self.agent.aid()?.nickname().to_string();
+        let content = |to: &dyn Display| Content::Expression(format!("{} to {}", nickname, to));
+        let report = match order {
+            Order::Request(action) => self.request("guild", action)?,
+            Order::Join => {
+                let invitation = self.agent.receive()?;
+                invitation
+                    .message_type()
+                    .is_message_type(&MessageType::Propose)?;
+                self.request("guild", OrgAction::Join)?
+            }
+            Order::Send(aid) => {
+                let content = content(&aid.nickname());
+                format!(
+                    "{:?}",
+                    self.agent.send_to_aid(aid, MessageType::Inform, content)
+                )
+            }
+            Order::Broadcast(role) => format!(
+                "{:?}",
+                self.agent.send_to_org(
+                    "guild",
+                    role,
+                    MessageType::Inform,
+                    content(&format!("{:?}", role))
+                )
+            ),
+            Order::Receive => self.agent.receive()?.content().to_string(),
+        };
+        let _ = self.param.1.send(report);
+        Ok(())
+    }
+}
+
+/// Give an order to a member and wait for its report.
+fn order(
+    member: &Sender<Order>,
+    reports: &Receiver<String>,
+    order: Order,
+) -> Result<String, Box<dyn Error>> {
+    member.send(order)?;
+    Ok(reports.recv_timeout(Duration::from_millis(2000))?)
+}
+
+#[test]
+fn invite_join_and_broadcast() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_org")?;
+    agent_platform.boot_org_service()?;
+    let (tx, rx) = channel();
+    let recruit =
+        agent_platform.add_agent_with_param::<Recruit>("Recruit", 1, DEFAULT_STACK, tx)?;
+    let leader = agent_platform.add_agent_with_param::<Leader>(
+        "Leader",
+        1,
+        DEFAULT_STACK,
+        recruit.clone(),
+    )?;
+    agent_platform.start(&recruit)?;
+    agent_platform.start(&leader)?;
+
+    let welcome = rx.recv_timeout(Duration::from_millis(2000))?;
+    assert_eq!(welcome.message_type(), &MessageType::Inform);
+    assert_eq!(welcome.sender(), &leader);
+    assert_eq!(
+        welcome.content(),
+        &Content::Expression("welcome".to_string())
+    );
+    hierarchy_roles_bans_and_ownership(&agent_platform)
+}
+
+/// Drive a hierarchy through roles, bans and the limits of its ownership.
+fn hierarchy_roles_bans_and_ownership(agent_platform: &Platform) -> Result<(), Box<dyn Error>> {
+    let (tx, rx) = channel();
+    let mut members = Vec::new();
+    let mut orders = Vec::new();
+    for nickname in ["chief", "peer", "guest", "loner"] {
+        let (order_tx, order_rx) = channel();
+        members.push(agent_platform.add_agent_with_param::<Member>(
+            nickname,
+            1,
+            DEFAULT_STACK,
+            (order_rx, tx.clone()),
+        )?);
+        orders.push(order_tx);
+    }
+    agent_platform.start_all()?;
+    let loner = orders.pop().expect("loner was added");
+    let [chief, peer, guest] = &orders[..] else {
+        unreachable!()
+    };
+    let request = |member, action| order(member, &rx, Order::Request(action));
+
+    assert_eq!(
+        request(chief, OrgAction::Open(OrgType::Hierarchy))?,
+        "Inform"
+    );
+    for aid in &members[1..3] {
+        assert_eq!(request(chief, OrgAction::Invite(aid.clone()))?, "Inform");
+    }
+    assert_eq!(order(peer, &rx, Order::Join)?, "Inform");
+    assert_eq!(order(guest, &rx, Order::Join)?, "Inform");
+    let action = OrgAction::SetRole(members[2].clone(), OrgRole::Visitor);
+    assert_eq!(request(chief, action)?, "Inform");
+
+    // only the members with the role are addressed
+    let broadcast = |role| Order::Broadcast(Some(role));
+    assert_eq!(order(chief, &rx, broadcast(OrgRole::Visitor))?, "Ok(())");
+    assert_eq!(order(guest, &rx, Order::Receive)?, "chief to Some(Visitor)");
+    assert_eq!(order(chief, &rx, broadcast(OrgRole::Moderator))?, "Ok(())");
+    assert_eq!(
+        order(chief, &rx, broadcast(OrgRole::Participant))?,
+        "Ok(())"
+    );
+    assert_eq!(
+        order(peer, &rx, Order::Receive)?,
+        "chief to Some(Participant)"
+    );
+    assert_eq!(order(chief, &rx, Order::Broadcast(None))?, "Ok(())");
+    assert_eq!(order(guest, &rx, Order::Receive)?, "chief to None");
+    assert_eq!(order(peer, &rx, Order::Receive)?, "chief to None");
+    assert_eq!(
+        order(guest, &rx, Order::Send(members[1].clone()))?,
+        "Err(NotVisible)"
+    );
+
+    // visitors of a hierarchy only address the members in charge
+    assert_eq!(
+        order(guest, &rx, Order::Broadcast(None))?,
+        "Err(NotVisible)"
+    );
+    assert_eq!(
+        order(guest, &rx, Order::Send(members[0].clone()))?,
+        "Ok(())"
+    );
+    assert_eq!(order(chief, &rx, Order::Receive)?, "guest to chief");
+
+    // banned agents cannot join nor address the members until the ban is lifted
+    assert_eq!(
+        request(chief, OrgAction::Ban(members[1].clone()))?,
+        "Inform"
+    );
+    assert_eq!(
+        order(peer, &rx, Order::Send(members[0].clone()))?,
+        "Err(Banned)"
+    );
+    assert_eq!(request(peer, OrgAction::Join)?, "Refuse");
+    assert_eq!(
+        request(chief, OrgAction::LiftBan(members[1].clone()))?,
+        "Inform"
+    );
+    assert_eq!(order(peer, &rx, Order::Send(members[0].clone()))?, "Ok(())");
+    assert_eq!(order(chief, &rx, Order::Receive)?, "peer to chief");
+
+    // invitations that cannot be delivered fail and leave no one invited
+    assert_eq!(
+        order(chief, &rx, Order::Send(members[3].clone()))?,
+        "Ok(())"
+    );
+    assert_eq!(
+        request(chief, OrgAction::Invite(members[3].clone()))?,
+        "Failure"
+    );
+    assert_eq!(order(&loner, &rx, Order::Receive)?, "chief to loner");
+    assert_eq!(
+        order(&loner, &rx, Order::Request(OrgAction::Join))?,
+        "Inform"
+    );
+    // the loner only applied, so it still has to be admitted
+    assert_eq!(
+        request(chief, OrgAction::Admit(members[3].clone()))?,
+        "Inform"
+    );
+    drop(loner);
+    agent_platform.terminate(&members[3])?;
+    assert_eq!(
+        request(chief, OrgAction::Invite(members[3].clone()))?,
+        "Failure"
+    );
+
+    // the owner cannot give up the organization without closing it
+    for affiliation in [OrgAffiliation::Owner, OrgAffiliation::NonMember] {
+        let action = OrgAction::SetAffiliation(members[0].clone(), affiliation);
+        assert_eq!(request(chief, action)?, "Failure");
+    }
+    assert_eq!(request(chief, OrgAction::Leave)?, "Refuse");
+    assert_eq!(request(guest, OrgAction::Close)?, "Refuse");
+    assert_eq!(request(chief, OrgAction::Close)?, "Inform");
+    Ok(())
+}
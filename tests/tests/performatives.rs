@@ -0,0 +1,193 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+type Report = Sender<(&'static str, String)>;
+
+make_agent_with_param!(
+    Client,
+    (Report, Description, Description, Description, Description)
+);
+make_agent_with_param!(Parser, Report);
+make_agent_with_param!(Relay, Report);
+make_agent_with_param!(Listener, Report);
+
+fn expression(text: &str) -> Content {
+    Content::Expression(text.to_string())
+}
+
+fn forward(targets: &[&Description], text: &str) -> Content {
+    Content::Forward(
+        targets.iter().map(|&x| x.clone()).collect(),
+        MessageType::Inform,
+        Arc::new(expression(text)),
+    )
+}
+
+impl Behavior for Client {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let (_, parser, relay, listener, ghost) = &self.param;
+        for text in ["42", "forty-two"] {
+            self.agent
+                .send_to_aid(parser.clone(), MessageType::Request, expression(text))?;
+        }
+        self.agent.send_to_aid(
+            relay.clone(),
+            MessageType::Proxy,
+            forward(&[listener], "via proxy"),
+        )?;
+        self.agent.send_to_aid(
+            relay.clone(),
+            MessageType::Propagate,
+            forward(&[listener], "via propagate"),
+        )?;
+        // the gone agent in the middle keeps no one else from getting it
+        self.agent.send_to_aid(
+            relay.clone(),
+            MessageType::Proxy,
+            forward(&[listener, ghost, listener], "past the ghost"),
+        )?;
+        // nothing to forward
+        self.agent
+            .send_to_aid(relay.clone(), MessageType::Proxy, expression("nowhere"))
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let report = format!("{} from {}", msg.message_type(), msg.sender().nickname());
+        let _ = self.param.0.send(("client", report));
+        Ok(())
+    }
+}
+
+impl Behavior for Parser {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let value: u32 = msg.parse_content()?;
+        let _ = self.param.send(("parser", format!("parsed {}", value)));
+        if self
+            .agent
+            .reply(&msg, MessageType::Propose, msg.shared_content())
+            == Err(ErrorCode::InvalidMessageType)
+        {
+            let _ = self
+                .param
+                .send(("parser", "invalid reply refused".to_string()));
+        }
+        self.agent
+            .reply(&msg, MessageType::Agree, msg.shared_content())
+    }
+}
+
+impl Behavior for Relay {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let report = format!(
+            "{} from {}: {}",
+            msg.message_type(),
+            msg.sender().nickname(),
+            msg.content()
+        );
+        let _ = self.param.send(("relay", report));
+        Ok(())
+    }
+}
+
+impl Behavior for Listener {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let report = format!("{} from {}", msg.content(), msg.sender().nickname());
+        let _ = self.param.send(("listener", report));
+        Ok(())
+    }
+}
+
+#[test]
+fn replies_follow_the_interaction_protocols() -> Result<(), Box<dyn Error>> {
+    assert_eq!(MessageType::ALL.len(), 23);
+    assert_eq!(
+        "rejectproposal".parse::<MessageType>()?,
+        MessageType::RejectProposal
+    );
+    assert_eq!("Proxy".parse::<MessageType>()?, MessageType::Proxy);
+    assert!(MessageType::Propose.is_reply_to(&MessageType::CallForProposal));
+    assert!(MessageType::RejectProposal.is_reply_to(&MessageType::Propose));
+    assert!(MessageType::Refuse.is_reply_to(&MessageType::Request));
+    assert!(!MessageType::Propose.is_reply_to(&MessageType::Request));
+    assert!(MessageType::NotUnderstood.is_reply_to(&MessageType::Inform));
+    assert!(!MessageType::NotUnderstood.is_reply_to(&MessageType::NotUnderstood));
+    assert!(MessageType::Inform.replies().is_empty());
+    Ok(())
+}
+
+#[test]
+fn contents_are_understood_and_forwarded() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_performatives")?;
+    let (tx, rx) = channel();
+    let parser =
+        agent_platform.add_agent_with_param::<Parser>("parser", 1, DEFAULT_STACK, tx.clone())?;
+    let relay =
+        agent_platform.add_agent_with_param::<Relay>("relay", 1, DEFAULT_STACK, tx.clone())?;
+    let listener = agent_platform.add_agent_with_param::<Listener>(
+        "listener",
+        1,
+        DEFAULT_STACK,
+        tx.clone(),
+    )?;
+    let ghost =
+        agent_platform.add_agent_with_param::<Listener>("ghost", 1, DEFAULT_STACK, tx.clone())?;
+    agent_platform.start(&ghost)?;
+    agent_platform.terminate(&ghost)?;
+    agent_platform.set_forwarding(&relay, true)?;
+    agent_platform.add_agent_with_param::<Client>(
+        "client",
+        1,
+        DEFAULT_STACK,
+        (tx, parser, relay, listener, ghost),
+    )?;
+    agent_platform.start_all()?;
+
+    let mut reports: HashMap<&str, Vec<String>> = HashMap::new();
+    while let Ok((reporter, report)) = rx.recv_timeout(Duration::from_millis(500)) {
+        reports.entry(reporter).or_default().push(report);
+    }
+    for x in reports.values_mut() {
+        x.sort();
+    }
+    assert_eq!(
+        reports["client"],
+        [
+            "Agree Message from parser",
+            "Failure Message from relay",
+            "NotUnderstood Message from parser",
+            "NotUnderstood Message from relay",
+        ]
+    );
+    assert_eq!(reports["parser"], ["invalid reply refused", "parsed 42"]);
+    assert_eq!(
+        reports["listener"],
+        [
+            "past the ghost from relay",
+            "past the ghost from relay",
+            "via propagate from relay",
+            "via proxy from relay"
+        ]
+    );
+    // the behavior of the relay sees the proxies and acts upon the propagated message
+    assert_eq!(
+        reports["relay"],
+        [
+            "Inform Message from client: via propagate",
+            "Proxy Message from client: Inform Message (past the ghost) to listener@test_performatives ghost@test_performatives listener@test_performatives",
+            "Proxy Message from client: Inform Message (via proxy) to listener@test_performatives",
+            "Proxy Message from client: nowhere",
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+const RELAYS: usize = 200;
+const BURST: usize = 5;
+
+make_agent_with_param!(Relay, (usize, Sender<usize>));
+
+impl Behavior for Relay {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let hops = msg
+            .content()
+            .to_string()
+            .parse::<usize>()
+            .unwrap_or_default()
+            + 1;
+        let (index, tx) = &self.param;
+        if index + 1 == RELAYS {
+            let _ = tx.send(hops);
+        } else {
+            self.agent.send_to(
+                &format!("relay-{}", index + 1),
+                MessageType::Inform,
+                Content::Expression(hops.to_string()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+make_agent_with_param!(Sleeper, Sender<u32>);
+
+impl Behavior for Sleeper {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(20)?;
+        Ok(())
+    }
+
+    fn failure_detection(&mut self, _: &Result<(), ErrorCode>) -> bool {
+        self.agent.stats().iterations == 3
+    }
+
+    fn failure_recovery(&mut self, _: &Result<(), ErrorCode>) {
+        let _ = self.param.send(self.agent.stats().iterations as u32);
+    }
+}
+
+make_agent!(Idle);
+
+impl Behavior for Idle {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.receive().map(|_| ())
+    }
+}
+
+make_agent!(Starter);
+
+impl Behavior for Starter {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(50)?;
+        self.agent.send_to(
+            "relay-0",
+            MessageType::Inform,
+            Content::Expression("0".to_string()),
+        )
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+make_agent_with_param!(Burst, usize);
+
+impl Behavior for Burst {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        // one message per action, since a yielded action is run again from its start
+        self.agent.send_to(
+            "sink",
+            MessageType::Inform,
+            Content::Expression(self.param.to_string()),
+        )?;
+        self.param -= 1;
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        self.param == 0
+    }
+}
+
+make_agent_with_param!(Sink, Sender<usize>);
+
+impl Behavior for Sink {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self
+            .param
+            .send(msg.content().to_string().parse().unwrap_or_default());
+        Ok(())
+    }
+}
+
+#[test]
+fn lightweight_agents_share_the_pool() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_pool")?;
+    assert_eq!(
+        agent_platform.set_pool_workers(0),
+        Err(ErrorCode::InvalidCapacity(0))
+    );
+    agent_platform.set_pool_workers(2)?;
+
+    let (tx, rx) = channel();
+    let mut relays = Vec::with_capacity(RELAYS);
+    for index in 0..RELAYS {
+        relays.push(agent_platform.add_lightweight_agent_with_param::<Relay>(
+            format!("relay-{}", index),
+            1 + (index % 10) as u8,
+            (index, tx.clone()),
+        )?);
+    }
+    assert_eq!(
+        agent_platform.add_lightweight_agent_with_param::<Relay>("relay-0", 1, (0, tx.clone())),
+        Err(ErrorCode::Duplicated)
+    );
+    assert_eq!(
+        agent_platform.set_pool_workers(4),
+        Err(ErrorCode::Duplicated)
+    );
+    assert!(matches!(
+        agent_platform.set_affinity(&relays[0], 0),
+        Err(ErrorCode::InvalidRequest(_))
+    ));
+
+    let (sleeps_tx, sleeps_rx) = channel();
+    let sleeper =
+        agent_platform.add_lightweight_agent_with_param::<Sleeper>("sleeper", 5, sleeps_tx)?;
+    let idle = agent_platform.add_lightweight_agent::<Idle>("idle", 1)?;
+    let starter = agent_platform.add_agent::<Starter>("starter", 1, DEFAULT_STACK)?;
+
+    for relay in &relays {
+        agent_platform.start(relay)?;
+    }
+    agent_platform.start(&sleeper)?;
+    agent_platform.start(&idle)?;
+    agent_platform.start(&starter)?;
+
+    // every relay yields its worker while it waits for the token, so two workers carry it around
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5))?, RELAYS);
+    assert_eq!(sleeps_rx.recv_timeout(Duration::from_secs(2))?, 3);
+
+    let state = |nickname: &str| {
+        agent_platform
+            .agents()
+            .into_iter()
+            .find(|x| x.nickname == nickname)
+            .map(|x| x.state)
+    };
+    agent_platform.suspend(&idle)?;
+    assert_eq!(state("idle"), Some(AgentState::Suspended));
+    agent_platform.resume(&idle)?;
+    assert_eq!(state("idle"), Some(AgentState::Active));
+    agent_platform.terminate(&idle)?;
+    assert_eq!(state("idle"), None);
+
+    // senders yield while the mailbox is full, rather than holding every worker the receiver needs
+    let (tx, rx) = channel();
+    let sink = agent_platform.add_lightweight_agent_with_param::<Sink>("sink", 1, tx)?;
+    agent_platform.start(&sink)?;
+    for index in 0..2 {
+        let burst = agent_platform.add_lightweight_agent_with_param::<Burst>(
+            format!("burst-{}", index),
+            5,
+            BURST,
+        )?;
+        agent_platform.start(&burst)?;
+    }
+    let mut received = (0..2 * BURST)
+        .map(|_| rx.recv_timeout(Duration::from_secs(2)))
+        .collect::<Result<Vec<usize>, _>>()?;
+    received.sort();
+    let expected: Vec<usize> = (1..=BURST).flat_map(|x| [x, x]).collect();
+    assert_eq!(received, expected);
+    Ok(())
+}
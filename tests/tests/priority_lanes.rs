@@ -0,0 +1,132 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+type Report = Sender<(String, String)>;
+
+make_agent_with_param!(Producer, (Description, Description, Report));
+make_agent_with_param!(Inbox, (Report, bool));
+
+impl Producer {
+    fn send(
+        &self,
+        aid: &Description,
+        text: &str,
+        priority: MessagePriority,
+    ) -> Result<(), ErrorCode> {
+        let content = Content::Expression(text.to_string());
+        self.agent
+            .send_to_aid_with_priority(aid.clone(), MessageType::Inform, content, priority)
+    }
+}
+
+impl Behavior for Producer {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let (inbox, aged, report) = &self.param;
+        for index in 0..3 {
+            self.send(inbox, &format!("low-{}", index), MessagePriority::Low)?;
+        }
+        for index in 0..3 {
+            self.send(inbox, &format!("normal-{}", index), MessagePriority::Normal)?;
+        }
+        for index in 0..2 {
+            self.send(inbox, &format!("high-{}", index), MessagePriority::High)?;
+        }
+        if self.send(inbox, "control", MessagePriority::Control)
+            == Err(ErrorCode::InvalidPriority(
+                "Control priority only allowed for the AMS",
+            ))
+        {
+            let _ = report.send(("producer".to_string(), "control refused".to_string()));
+        }
+
+        self.send(aged, "low", MessagePriority::Low)?;
+        for index in 0..4 {
+            self.send(aged, &format!("high-{}", index), MessagePriority::High)?;
+        }
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Inbox {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        // everything is queued by the time the mailbox is read
+        self.agent.wait(100)?;
+        if self.param.1 {
+            // the mailbox is full, yet the reply of the AMS gets in ahead of every message
+            self.agent.add_contact("ams")?;
+            let content = Content::Action(ActionType::Search(self.agent.aid()?));
+            self.agent.send_to("ams", MessageType::Request, content)?;
+            self.agent.wait(50)?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let nickname = self.agent.aid()?.nickname().to_string();
+        let report = match msg.priority() {
+            MessagePriority::Control => msg.sender().nickname().to_string(),
+            _ => msg.content().to_string(),
+        };
+        let _ = self.param.0.send((nickname, report));
+        Ok(())
+    }
+}
+
+#[test]
+fn higher_lanes_are_received_first() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_priority_lanes")?;
+    let (tx, rx) = channel();
+    let inbox = agent_platform.add_agent_with_param::<Inbox>(
+        "inbox",
+        1,
+        DEFAULT_STACK,
+        (tx.clone(), true),
+    )?;
+    let aged = agent_platform.add_agent_with_param::<Inbox>(
+        "aged",
+        1,
+        DEFAULT_STACK,
+        (tx.clone(), false),
+    )?;
+    agent_platform.set_mailbox_capacity(&inbox, 8)?;
+    agent_platform.set_mailbox_capacity(&aged, 8)?;
+    agent_platform.set_lane_policy(&aged, LanePolicy::Aging(2))?;
+    agent_platform.add_agent_with_param::<Producer>(
+        "producer",
+        1,
+        DEFAULT_STACK,
+        (inbox, aged, tx),
+    )?;
+    agent_platform.start_all()?;
+
+    let mut reports: [Vec<String>; 3] = Default::default();
+    while let Ok((nickname, report)) = rx.recv_timeout(Duration::from_millis(500)) {
+        let index = ["inbox", "aged", "producer"]
+            .iter()
+            .position(|x| *x == nickname)
+            .ok_or("unknown reporter")?;
+        reports[index].push(report);
+    }
+    let [inbox, aged, producer] = reports;
+    assert_eq!(producer, ["control refused"]);
+
+    let replies = inbox.iter().take_while(|x| *x == "ams").count();
+    assert!(replies > 0);
+    assert_eq!(
+        inbox[replies..],
+        ["high-0", "high-1", "normal-0", "normal-1", "normal-2", "low-0", "low-1", "low-2"]
+    );
+    // the low message is taken once two messages overtook it
+    assert_eq!(aged, ["high-0", "high-1", "low", "high-2", "high-3"]);
+    Ok(())
+}
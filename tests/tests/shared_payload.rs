@@ -0,0 +1,79 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+const LISTENERS: usize = 3;
+
+make_agent!(Sensor);
+make_agent_with_param!(Listener, Sender<(String, Arc<Content>)>);
+
+impl Behavior for Sensor {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        for index in 0..LISTENERS {
+            self.agent.add_contact(&format!("listener-{}", index))?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let reading: Arc<[u8]> = vec![0xca, 0xfe, 0x00, 0x2a].into();
+        self.agent
+            .send_to_all(MessageType::Inform, Content::Bytes(reading))
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Listener {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let nickname = self.agent.aid()?.nickname().to_string();
+        let _ = self.param.send((nickname.clone(), msg.shared_content()));
+        if nickname == "listener-0" && msg.sender().nickname() == "sensor" {
+            // forwarded as received, without copying the reading
+            self.agent
+                .send_to("listener-1", MessageType::Inform, msg.shared_content())?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn broadcast_shares_one_payload() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_shared_payload")?;
+    let (tx, rx) = channel();
+    for index in 0..LISTENERS {
+        agent_platform.add_agent_with_param::<Listener>(
+            format!("listener-{}", index),
+            1,
+            DEFAULT_STACK,
+            tx.clone(),
+        )?;
+    }
+    agent_platform.add_agent::<Sensor>("sensor", 1, DEFAULT_STACK)?;
+    agent_platform.start_all()?;
+
+    let received: Vec<_> = (0..LISTENERS + 1)
+        .map(|_| rx.recv_timeout(Duration::from_secs(2)))
+        .collect::<Result<_, _>>()?;
+    let (_, first) = &received[0];
+    assert_eq!(first.to_string(), "cafe002a");
+    assert!(received
+        .iter()
+        .all(|(_, content)| Arc::ptr_eq(content, first)));
+    assert_eq!(
+        received
+            .iter()
+            .filter(|(nickname, _)| nickname == "listener-1")
+            .count(),
+        2
+    );
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::{Duration, Instant};
+
+make_agent_with_param!(Ticker, u32);
+make_agent_with_param!(Counter, (Sender<String>, u32));
+
+impl Behavior for Ticker {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.param += 1;
+        self.agent.send_to(
+            "Counter",
+            MessageType::Inform,
+            Content::Expression(format!("tick {}", self.param)),
+        )?;
+        self.agent.wait(100)?;
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        self.param == 3
+    }
+}
+
+impl Behavior for Counter {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        if let Content::Expression(text) = self.agent.receive()?.content() {
+            let _ = self.param.0.send(text.clone());
+        }
+        self.param.1 += 1;
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        self.param.1 == 3
+    }
+}
+
+#[test]
+fn virtual_time_and_priority_order() -> Result<(), Box<dyn Error>> {
+    let (agent_platform, simulation) = Platform::new_simulated("test_simulation", 7)?;
+    let (tx, rx) = channel();
+    let counter =
+        agent_platform.add_agent_with_param::<Counter>("Counter", 1, DEFAULT_STACK, (tx, 0))?;
+    let ticker = agent_platform.add_agent_with_param::<Ticker>("Ticker", 2, DEFAULT_STACK, 0)?;
+    agent_platform.start(&counter)?;
+    agent_platform.start(&ticker)?;
+
+    let started = Instant::now();
+    let steps = simulation.run();
+    // three waits of 100ms pass in virtual time only
+    assert!(started.elapsed() < Duration::from_millis(300));
+    assert!(simulation.now() >= Duration::from_millis(300));
+
+    let ticks: Vec<String> = rx.try_iter().collect();
+    assert_eq!(ticks, ["tick 1", "tick 2", "tick 3"]);
+
+    let history = simulation.history();
+    assert_eq!(history.len(), steps);
+    assert_eq!(history[0].agent, ticker);
+    assert!(history.windows(2).all(|x| x[0].time <= x[1].time));
+    assert_eq!(simulation.step(), None);
+    assert!(agent_platform.agents().is_empty());
+    Ok(())
+}
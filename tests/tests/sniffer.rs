@@ -0,0 +1,92 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::service::sniffer::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Pinger, Sender<Message>);
+make_agent!(Ponger);
+
+impl Behavior for Pinger {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.send_to(
+            "Ponger",
+            MessageType::Request,
+            Content::Expression("ping".to_string()),
+        )?;
+        let pong = self.agent.receive()?;
+        let _ = self.param.send(pong);
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+impl Behavior for Ponger {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let ping = self.agent.receive()?;
+        self.agent.send_to_aid(
+            ping.sender().clone(),
+            MessageType::Inform,
+            Content::Expression("pong".to_string()),
+        )
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn sniff_conversation() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_sniffer")?;
+    let sniffer = agent_platform.boot_sniffer()?;
+    let (tx, rx) = channel();
+    let pinger = agent_platform.add_agent_with_param::<Pinger>("Pinger", 1, DEFAULT_STACK, tx)?;
+    let ponger = agent_platform.add_agent::<Ponger>("Ponger", 1, DEFAULT_STACK)?;
+    sniffer.sniff(&pinger)?;
+    agent_platform.start(&ponger)?;
+    agent_platform.start(&pinger)?;
+    rx.recv_timeout(Duration::from_millis(2000))?;
+
+    let captures: Vec<_> = sniffer
+        .trace()
+        .iter()
+        .map(|x| (x.capture(), x.message().sender().clone()))
+        .collect();
+    // the pinger deregistering from the AMS afterwards is captured as well
+    assert_eq!(
+        captures[..4],
+        [
+            (Capture::Sent, pinger.clone()),
+            (Capture::Received, pinger.clone()),
+            (Capture::Sent, ponger.clone()),
+            (Capture::Received, ponger.clone()),
+        ]
+    );
+
+    let mut plantuml = Vec::new();
+    sniffer.write_plantuml(&mut plantuml)?;
+    let plantuml = String::from_utf8(plantuml)?;
+    assert!(plantuml.starts_with("@startuml"));
+    assert!(plantuml
+        .contains("\"Pinger@test_sniffer\" -> \"Ponger@test_sniffer\" : Request Message (ping)"));
+
+    let mut mermaid = Vec::new();
+    sniffer.write_mermaid(&mut mermaid)?;
+    assert!(String::from_utf8(mermaid)?.contains("p1->>p0: Inform Message (pong)"));
+
+    let mut json = Vec::new();
+    sniffer.write_json_lines(&mut json)?;
+    let json = String::from_utf8(json)?;
+    assert!(json.lines().count() >= captures.len());
+    assert!(json
+        .lines()
+        .all(|line| line.starts_with("{\"timestamp_us\":") && line.ends_with('}')));
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent_with_param!(Worker, Sender<String>);
+
+impl Behavior for Worker {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let _ = self.param.send(self.agent.aid()?.nickname().to_string());
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn start_all_by_priority_then_addition() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_start_all")?;
+    let (tx, rx) = channel();
+    let low = agent_platform.add_agent_with_param::<Worker>("low", 1, DEFAULT_STACK, tx.clone())?;
+    let first =
+        agent_platform.add_agent_with_param::<Worker>("first", 10, DEFAULT_STACK, tx.clone())?;
+    let early =
+        agent_platform.add_agent_with_param::<Worker>("early", 20, DEFAULT_STACK, tx.clone())?;
+    let light =
+        agent_platform.add_lightweight_agent_with_param::<Worker>("light", 5, tx.clone())?;
+    let second = agent_platform.add_agent_with_param::<Worker>("second", 10, DEFAULT_STACK, tx)?;
+
+    // the thread of an agent waits parked at its gate until it is started
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(rx.try_recv().is_err());
+    agent_platform.start(&early)?;
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2))?, "early");
+
+    let (_, events) = agent_platform.subscribe_channel();
+    agent_platform.start_all()?;
+    let mut started = Vec::new();
+    while started.len() < 4 {
+        if let Event::AgentStarted(aid) = events.recv_timeout(Duration::from_secs(2))? {
+            started.push(aid);
+        }
+    }
+    assert_eq!(started, [first, second, light, low]);
+    let mut ran: Vec<_> = (0..4)
+        .map(|_| rx.recv_timeout(Duration::from_secs(2)))
+        .collect::<Result<_, _>>()?;
+    ran.sort();
+    assert_eq!(ran, ["first", "light", "low", "second"]);
+
+    // nothing is left to start
+    agent_platform.start_all()?;
+    assert!(events
+        .try_iter()
+        .all(|x| !matches!(x, Event::AgentStarted(_))));
+    Ok(())
+}
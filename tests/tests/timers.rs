@@ -0,0 +1,194 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::messaging::*;
+use caravela::timer::TimerHandle;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+const PERIOD: Duration = Duration::from_millis(20);
+const TICKS: usize = 3;
+
+type Report = Sender<String>;
+
+make_agent_with_param!(Ticker, (Report, Option<TimerHandle>, usize));
+make_agent_with_param!(Asker, (Report, Description, Description));
+make_agent!(Echo);
+make_agent!(Silent);
+make_agent_with_param!(Beacon, Description);
+make_agent_with_param!(Counter, Report);
+
+impl Behavior for Ticker {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let own = self.agent.aid()?;
+        let tick = Content::Expression("tick".to_string());
+        if let Err(ErrorCode::InvalidRequest(_)) = self.agent.send_every(
+            own.clone(),
+            Duration::ZERO,
+            MessageType::Inform,
+            tick.clone(),
+        ) {
+            let _ = self.param.0.send("zero period refused".to_string());
+        }
+        self.param.1 =
+            Some(
+                self.agent
+                    .send_every(own.clone(), PERIOD, MessageType::Inform, tick)?,
+            );
+        let once = Content::Expression("once".to_string());
+        self.agent
+            .send_after(own, PERIOD / 2, MessageType::Inform, once)?;
+        Ok(())
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self.param.0.send(msg.content().to_string());
+        if msg.content().to_string() == "tick" {
+            self.param.2 += 1;
+            if self.param.2 == TICKS {
+                let handle = self.param.1.take().ok_or(ErrorCode::NotFound)?;
+                self.agent.cancel_timer(handle)?;
+                // a cancelled timer is gone
+                if self.agent.cancel_timer(handle) == Err(ErrorCode::NotFound) {
+                    let _ = self.param.0.send("cancelled".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Behavior for Asker {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let (_, silent, echo) = &self.param;
+        let question = || Content::Expression("ping".to_string());
+        self.agent.send_to_aid_with_reply_by(
+            silent.clone(),
+            MessageType::Request,
+            question(),
+            Duration::from_millis(50),
+        )?;
+        // the reply to the next request does not settle this cancelled deadline instead
+        let ignored = self.agent.send_to_aid_with_reply_by(
+            echo.clone(),
+            MessageType::QueryIf,
+            question(),
+            Duration::from_secs(1),
+        )?;
+        self.agent.cancel_timer(ignored)?;
+        self.agent.send_to_aid_with_reply_by(
+            echo.clone(),
+            MessageType::Request,
+            question(),
+            Duration::from_millis(200),
+        )?;
+        Ok(())
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let report = match (msg.message_type(), msg.content()) {
+            (MessageType::Failure, Content::Reason(_, Reason::NoReply(aid))) => {
+                format!("no reply from {}", aid.nickname())
+            }
+            _ => format!("reply from {}", msg.sender().nickname()),
+        };
+        let _ = self.param.0.send(report);
+        Ok(())
+    }
+}
+
+impl Behavior for Echo {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        assert!(msg.reply_by().is_some());
+        if *msg.message_type() != MessageType::Request {
+            return Ok(());
+        }
+        self.agent.send_to_aid(
+            msg.sender().clone(),
+            MessageType::Inform,
+            msg.shared_content(),
+        )
+    }
+}
+
+impl Behavior for Silent {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(60_000)
+    }
+}
+
+impl Behavior for Beacon {
+    fn setup(&mut self) -> Result<(), ErrorCode> {
+        let beat = Content::Expression("beat".to_string());
+        self.agent
+            .send_every(self.param.clone(), PERIOD, MessageType::Inform, beat)?;
+        Ok(())
+    }
+
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.wait(60_000)
+    }
+}
+
+impl Behavior for Counter {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(msg.content().to_string());
+        Ok(())
+    }
+}
+
+/// Gather the reports arriving within `span`.
+fn collect(rx: &Receiver<String>, span: Duration) -> Vec<String> {
+    let mut reports = Vec::new();
+    while let Ok(report) = rx.recv_timeout(span) {
+        reports.push(report);
+    }
+    reports
+}
+
+#[test]
+fn timers_post_messages_and_stop() -> Result<(), Box<dyn Error>> {
+    let agent_platform = Platform::new("test_timers")?;
+
+    // one-shot and periodic timers, cancelled by handle
+    let (tx, rx) = channel();
+    agent_platform.add_agent_with_param::<Ticker>("ticker", 1, DEFAULT_STACK, (tx, None, 0))?;
+    agent_platform.start_all()?;
+    let reports = collect(&rx, PERIOD * 10);
+    let count = |x: &str| reports.iter().filter(|report| *report == x).count();
+    assert_eq!(count("once"), 1);
+    assert_eq!(count("tick"), TICKS);
+    assert_eq!(count("cancelled"), 1);
+    assert_eq!(count("zero period refused"), 1);
+
+    // reply deadlines notify only when no reply arrives
+    let (tx, rx) = channel();
+    let silent = agent_platform.add_agent::<Silent>("silent", 1, DEFAULT_STACK)?;
+    let echo = agent_platform.add_agent::<Echo>("echo", 1, DEFAULT_STACK)?;
+    agent_platform.add_agent_with_param::<Asker>("asker", 1, DEFAULT_STACK, (tx, silent, echo))?;
+    agent_platform.start_all()?;
+    let mut reports = collect(&rx, Duration::from_millis(400));
+    reports.sort();
+    assert_eq!(reports, ["no reply from silent", "reply from echo"]);
+
+    // the timers of an agent end with it
+    let (tx, rx) = channel();
+    let counter =
+        agent_platform.add_agent_with_param::<Counter>("counter", 1, DEFAULT_STACK, tx)?;
+    let beacon =
+        agent_platform.add_agent_with_param::<Beacon>("beacon", 1, DEFAULT_STACK, counter)?;
+    agent_platform.start_all()?;
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2))?, "beat");
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2))?, "beat");
+    agent_platform.terminate(&beacon)?;
+    // a beat already due may still be on its way
+    std::thread::sleep(PERIOD * 2);
+    while rx.try_recv().is_ok() {}
+    assert!(collect(&rx, PERIOD * 5).is_empty());
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use caravela::agent::*;
+use caravela::behavior::*;
+use caravela::executor::*;
+use caravela::messaging::*;
+use caravela::*;
+use std::error::Error;
+use std::sync::mpsc::{channel, Sender};
+use std::time::Duration;
+
+make_agent!(Echo);
+
+impl AsyncBehavior for Echo {
+    async fn action(&mut self) -> Result<(), ErrorCode> {
+        let msg = self.agent.receive_async().await?;
+        self.agent.wait_async(5).await?;
+        self.agent
+            .send_to_aid_async(
+                msg.sender().clone(),
+                MessageType::Inform,
+                msg.content().clone(),
+            )
+            .await
+    }
+}
+
+make_agent_with_param!(Prober, Sender<String>);
+
+impl Behavior for Prober {
+    fn action(&mut self) -> Result<(), ErrorCode> {
+        self.agent.send_to(
+            "echo",
+            MessageType::Request,
+            Content::Expression("ping".to_string()),
+        )?;
+        let msg = self.agent.receive()?;
+        let _ = self.param.send(msg.content().to_string());
+        Ok(())
+    }
+
+    fn done(&mut self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn async_agents_run_on_tokio() -> Result<(), Box<dyn Error>> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .build()?;
+    let agent_platform = Platform::new("test_tokio")?;
+    agent_platform.set_executor(TokioExecutor::new(runtime.handle().clone()))?;
+    assert_eq!(
+        agent_platform.set_executor(BuiltinExecutor::new()),
+        Err(ErrorCode::Duplicated)
+    );
+
+    let echo = agent_platform.add_async_agent::<Echo>("echo", 5)?;
+    let (tx, rx) = channel();
+    let prober = agent_platform.add_agent_with_param::<Prober>("prober", 1, DEFAULT_STACK, tx)?;
+    agent_platform.start(&echo)?;
+    agent_platform.start(&prober)?;
+    assert_eq!(rx.recv_timeout(Duration::from_secs(2))?, "ping");
+    Ok(())
+}